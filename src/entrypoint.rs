@@ -1,9 +1,11 @@
 use crate::{
+    error::OptionsError,
     instruction::{decode_instruction, InstructionType},
     processor,
 };
 use solana_program::{
-    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, msg, pubkey::Pubkey,
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, msg,
+    program_error::PrintProgramError, pubkey::Pubkey,
 };
 
 entrypoint!(process_instruction);
@@ -11,23 +13,82 @@ pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
+) -> ProgramResult {
+    if let Err(error) = dispatch(program_id, accounts, instruction_data) {
+        error.print::<OptionsError>();
+        return Err(error);
+    }
+    Ok(())
+}
+
+fn dispatch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
 ) -> ProgramResult {
     let action = decode_instruction(program_id, instruction_data)?;
     match action {
         InstructionType::Bid { instruction } => {
             msg!("received bid");
-            return processor::initialise_contract(program_id, accounts, instruction);
+            processor::initialise_contract(program_id, accounts, instruction)
         }
         InstructionType::Ask { instruction } => {
-            return processor::initialise_contract(program_id, accounts, instruction)
+            processor::initialise_contract(program_id, accounts, instruction)
+        }
+        InstructionType::AcceptBid { lots, offer_seq } => {
+            processor::accept_bid(program_id, accounts, lots, offer_seq)
+        }
+        InstructionType::AcceptAsk { lots, offer_seq } => {
+            processor::accept_ask(program_id, accounts, lots, offer_seq)
+        }
+        InstructionType::Execute { lots } => {
+            processor::execute_contract(program_id, accounts, lots)
+        }
+        InstructionType::CancelOffer => processor::cancel_offer(program_id, accounts),
+        InstructionType::CrankCancel => processor::crank_cancel(program_id, accounts),
+        InstructionType::AmendOffer { premium_qty } => {
+            processor::amend_offer(program_id, accounts, premium_qty)
+        }
+        InstructionType::Expire => processor::expire_contract(program_id, accounts),
+        InstructionType::CreateMint {
+            contract_type,
+            contract_data,
+        } => processor::create_mint(program_id, accounts, contract_type, contract_data),
+        InstructionType::MigrateContract => processor::migrate_contract(program_id, accounts),
+        InstructionType::CrankExpire => processor::crank_expire(program_id, accounts),
+        InstructionType::SettleAtExpiry => processor::settle_at_expiry(program_id, accounts),
+        InstructionType::RedeemWriter { lots } => {
+            processor::redeem_writer(program_id, accounts, lots)
+        }
+        InstructionType::CloseOut { lots } => processor::close_out(program_id, accounts, lots),
+        InstructionType::ExercisePooled {
+            contract_type,
+            contract_data,
+            lots,
+        } => processor::exercise_pooled(program_id, accounts, contract_type, contract_data, lots),
+        InstructionType::RedeemPooled {
+            contract_type,
+            contract_data,
+            lots,
+        } => processor::redeem_pooled(program_id, accounts, contract_type, contract_data, lots),
+        InstructionType::CloseOutPooled {
+            contract_type,
+            contract_data,
+            lots,
+        } => processor::close_out_pooled(program_id, accounts, contract_type, contract_data, lots),
+        InstructionType::PlaceOrder { instruction } => {
+            processor::place_order(program_id, accounts, instruction)
+        }
+        InstructionType::CancelOrder => processor::cancel_order(program_id, accounts),
+        InstructionType::AcceptQuote { quote } => {
+            processor::accept_quote(program_id, accounts, quote)
         }
-        InstructionType::AcceptBid => return processor::accept_bid(program_id, accounts),
-        InstructionType::AcceptAsk => return processor::accept_ask(program_id, accounts),
-        InstructionType::Execute => return processor::execute_contract(program_id, accounts),
-        InstructionType::CancelOffer => return processor::cancel_offer(accounts),
-        InstructionType::Expire => return processor::expire_contract(accounts),
-        InstructionType::CreateMint { seeds } => {
-            return processor::create_mint(program_id, accounts, seeds)
+        InstructionType::ClaimSettlement { lots } => {
+            processor::claim_settlement(program_id, accounts, lots)
         }
-    };
+        InstructionType::MigrateSeries {
+            contract_type,
+            contract_data,
+        } => processor::migrate_series(program_id, accounts, contract_type, contract_data),
+    }
 }
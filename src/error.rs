@@ -0,0 +1,129 @@
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+// codes are part of the client interface, only ever append new variants
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum OptionsError {
+    #[error("invalid contract state")]
+    InvalidContractState,
+    #[error("contract expired")]
+    ContractExpired,
+    #[error("contract not expired")]
+    ContractNotExpired,
+    #[error("contract not finalised")]
+    ContractNotFinalised,
+    #[error("incorrect PDA account")]
+    IncorrectDataPda,
+    #[error("incorrect mint PDA account")]
+    IncorrectMintPda,
+    #[error("incorrect holder mint account")]
+    IncorrectHolderMint,
+    #[error("incorrect holder mint ATA")]
+    IncorrectHolderAta,
+    #[error("holder ATA not owned by signer")]
+    HolderAtaNotOwned,
+    #[error("incorrect temp account token type")]
+    IncorrectTempType,
+    #[error("incorrect temp account balance")]
+    IncorrectTempBalance,
+    #[error("incorrect temp account")]
+    IncorrectTempAccount,
+    #[error("incorrect receive account token type")]
+    IncorrectReceiveType,
+    #[error("incorrect premium receive account token type")]
+    IncorrectPremiumReceiveType,
+    #[error("incorrect writer receive account")]
+    IncorrectWriterReceive,
+    #[error("incorrect buyer account")]
+    IncorrectBuyerAccount,
+    #[error("incorrect writer account")]
+    IncorrectWriterAccount,
+    #[error("incorrect initialiser account")]
+    IncorrectInitialiserAccount,
+    #[error("account layout outdated, migrate before use")]
+    OutdatedAccountLayout,
+    #[error("invalid exercise window")]
+    InvalidExerciseWindow,
+    #[error("exercise window not open")]
+    ExerciseWindowNotOpen,
+    #[error("invalid lot count")]
+    InvalidLotCount,
+    #[error("invalid oracle account")]
+    InvalidOracle,
+    #[error("oracle price too old")]
+    StaleOraclePrice,
+    #[error("invalid settlement for contract")]
+    InvalidSettlement,
+    #[error("incorrect buyer receive account")]
+    IncorrectBuyerReceive,
+    #[error("incorrect writer mint account")]
+    IncorrectWriterMint,
+    #[error("incorrect writer mint ATA")]
+    IncorrectWriterAta,
+    #[error("contract not settled")]
+    ContractNotSettled,
+    #[error("contract collateral is pooled in the series vault")]
+    ContractPooled,
+    #[error("contract collateral is not pooled")]
+    ContractNotPooled,
+    #[error("incorrect series vault account")]
+    IncorrectSeriesVault,
+    #[error("incorrect order book account")]
+    IncorrectOrderBook,
+    #[error("order book side is full")]
+    OrderBookFull,
+    #[error("offer not found in the order book")]
+    OrderNotFound,
+    #[error("quote expired")]
+    QuoteExpired,
+    #[error("quote signature not verified")]
+    InvalidQuoteSignature,
+    #[error("quote already accepted")]
+    QuoteAlreadyAccepted,
+    #[error("incorrect quote nonce or authority account")]
+    IncorrectQuoteAccount,
+    #[error("invalid offer TTL")]
+    InvalidOfferTtl,
+    #[error("offer expired")]
+    OfferExpired,
+    #[error("offer not expired")]
+    OfferNotExpired,
+    #[error("taker is not the offer's counterparty")]
+    CounterpartyNotAllowed,
+    #[error("orders cannot name a counterparty")]
+    PrivateOrder,
+    #[error("offer amended since it was priced")]
+    OfferAmended,
+    #[error("arithmetic overflow")]
+    Overflow,
+}
+
+impl From<OptionsError> for ProgramError {
+    fn from(e: OptionsError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for OptionsError {
+    fn type_of() -> &'static str {
+        "OptionsError"
+    }
+}
+
+impl PrintProgramError for OptionsError {
+    fn print<E>(&self)
+    where
+        E: 'static
+            + std::error::Error
+            + DecodeError<E>
+            + PrintProgramError
+            + num_traits::FromPrimitive,
+    {
+        msg!("OPTIONS ERROR: {}", self);
+    }
+}
@@ -1,8 +1,17 @@
-use crate::state::{get_seed, ContractData, ContractType};
-use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use crate::state::{
+    get_seed, ContractData, ContractPDA, ContractState, ContractType, SettlementType,
+};
+use solana_program::{
+    ed25519_program,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+use spl_associated_token_account::get_associated_token_address;
 use std::convert::TryInto;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum InstructionType {
     /*
         expected accounts:
@@ -15,8 +24,19 @@ pub enum InstructionType {
           data_pda       [writable]
           system_program []
           token_program  []
+          collateral_vault [writable] (pooled only)
+          proceeds_vault   [writable] (pooled only)
+          collateral_mint  [] (pooled only)
+          proceeds_mint    [] (pooled only)
+          writer_mint      [writable] (not pooled, not created)
+        a pooled offer creates the series vaults if this is the first offer of its series,
+        any other offer the writer mint of its contract.
+        an offer with a TTL can only be taken until it runs out, then anyone may cancel it.
+        an offer naming a counterparty can only be taken by that key
     */
-    Bid { instruction: OfferData },
+    Bid {
+        instruction: OfferData,
+    },
     /*
         expected accounts:
           writer              [writable]
@@ -28,8 +48,18 @@ pub enum InstructionType {
           data_pda            [writable]
           system_program      []
           token_program       []
+          collateral_vault    [writable] (pooled only)
+          proceeds_vault      [writable] (pooled only)
+          collateral_mint     [] (pooled only)
+          proceeds_mint       [] (pooled only)
+          writer_mint         [writable] (not pooled, not created)
+        receive_acc is escrowed with asset_temp for writer token holders, so it
+        cannot be the premium account receive_ata. a pooled ask escrows asset_temp alone.
+        the TTL and counterparty work as for Bid
     */
-    Ask { instruction: OfferData },
+    Ask {
+        instruction: OfferData,
+    },
     /*
         expected accounts:
           writer              [writable, signer]
@@ -44,8 +74,22 @@ pub enum InstructionType {
           holder_mint         [writable]
           system_program      []
           token_program       []
+          writer_mint         [writable]
+          writer_ata          [writable]
+          collateral_vault    [writable] (pooled bids and later fills only)
+        fills `lots` of the bid. the first fill escrows asset_temp and strike_receive_acc;
+        any writer may fill the rest, paying the collateral from asset_temp into the first
+        fill's asset_temp, passed as collateral_vault, with its strike_receive_acc.
+        each writer gets a writer token per lot it fills, minted by the contract's own
+        writer mint unless pooled, in its associated account for that mint.
+        pooled bids take the collateral straight from asset_temp into the series vault,
+        any writer may fill them, and the last fill closes the contract.
+        with offer_seq, fails if the bid was amended since that sequence number
     */
-    AcceptBid,
+    AcceptBid {
+        lots: u64,
+        offer_seq: Option<u64>,
+    },
     /*
         expected accounts:
           buyer            [writable]
@@ -58,41 +102,67 @@ pub enum InstructionType {
           prem_receive_acc []
           system_program   []
           token_program    []
+          writer_mint      [writable]
+          writer_ata       [writable] (the writer's)
+          asset_temp       [writable] (pooled only, owned by PDA)
+          collateral_vault [writable] (pooled only)
+          writer           [writable] (pooled only)
+        fills `lots` of the ask, paying their premium. a pooled ask moves the collateral
+        of those lots into the series vault, and the last fill closes the contract.
+        offer_seq guards against amendments as for AcceptBid
     */
-    AcceptAsk,
+    AcceptAsk {
+        lots: u64,
+        offer_seq: Option<u64>,
+    },
     /*
         expected accounts:
           initialiser      [writable] (signer)
           token_temp       [writable] (owned by PDA)
           data_pda         [writable]
-          mint_account     [writable]
           system_program   []
           token_program    []
+          refund_acc       [writable] (partially filled asks only)
+          receive_acc      [writable] (unfilled asks only)
+        a partially filled offer only cancels its unfilled lots. a pooled offer has
+        nothing left in escrow for filled lots, so it is closed
     */
     CancelOffer,
     /*
         expected accounts:
-          buyer             [writable] (signer)
-          strike_temp       [writable]
-          buyer_receive     [writable]
-          buyer_holder_ata  [writable]
-          mint_pda          [writable]
-          buyer_holder_mint [writable]
-          asset_temp        [writable] (owned by PDA)
-          data_pda          [writable]
-          writer            []
-          writer_receive    []
-          system_program    []
-          token_program     []
+          holder         [writable] (signer)
+          strike_temp    [writable]
+          holder_receive [writable]
+          holder_ata     [writable]
+          mint_pda       [writable]
+          holder_mint    [writable]
+          asset_temp     [writable] (owned by PDA)
+          data_pda       [writable]
+          writer         []
+          writer_receive [writable] (owned by PDA)
+          buyer          [writable] (original buyer, rent refund)
+          system_program []
+          token_program  []
+          oracle         [] (cash-settled only)
+        any owner of holder tokens may exercise, paying out to a receive account of their choice
+        cash-settled contracts take nothing from strike_temp and pay out in strike_type
     */
-    Execute,
+    Execute {
+        lots: u64,
+    },
     /*
         expected accounts:
           writer         [writable] (signer)
           asset_temp     [writable] (owned by PDA)
           data_pda       [writable]
+          buyer          [writable]
           system_program []
           token_program  []
+          mint_pda       [writable]
+          premium_temp   [writable] (partially filled bids only)
+          refund_acc     [writable] (partially filled asks with writer tokens only)
+        the collateral of an ask's unfilled lots goes to refund_acc, which must be the
+        writer's associated account unless the writer signs
     */
     Expire,
     /*
@@ -102,17 +172,314 @@ pub enum InstructionType {
           mint_pda       [writable] (not created)
           system_program []
           token_program  []
+          rent_sysvar    []
+          writer_mint    [writable] (not created, pooled only)
+        mint_pda is the series account, recording the terms it is derived from along
+        with the open interest and the lots exercised and expired since. only a pooled
+        series has a writer mint, other contracts mint their own
+    */
+    CreateMint {
+        contract_type: ContractType,
+        contract_data: ContractData,
+    },
+    /*
+        expected accounts:
+          payer          [writable, signer]
+          data_pda       [writable]
+          mint_pda       [writable]
+          system_program []
+          writer_mint    [writable]
+          token_program  []
+          holder_mint    []
+          legacy_mint_pda [] (only for a contract of a series merged by MigrateSeries)
+        also creates the writer mint of a contract that predates it. a series account
+        migrated from the legacy layout takes its open interest from the holder mint supply
+        and stays at its address, keyed by the premium type too; the contract records
+        that seed so its holder tokens remain fungible only with that series. once
+        MigrateSeries has merged that series, passing the merged series account as
+        mint_pda moves the contract over to it
+    */
+    MigrateContract,
+    /*
+        same as Expire, but the writer does not sign
+        expected accounts:
+          writer         [writable]
+          asset_temp     [writable] (owned by PDA)
+          data_pda       [writable]
+          buyer          [writable]
+          system_program []
+          token_program  []
+          mint_pda       [writable]
+          premium_temp   [writable] (partially filled bids only)
+          refund_acc     [writable] (partially filled asks with writer tokens only)
+    */
+    CrankExpire,
+    /*
+        same as CrankExpire for auto-exercised contracts. in the money, the payout of
+        each open lot is set aside in asset_temp for its holder to claim with
+        ClaimSettlement and the writer tokens redeem the rest of the collateral.
+        the price must be published within oracle::SETTLE_WINDOW of expiry; once no
+        such price can be loaded any more, the lots expire unexercised
+        expected accounts:
+          writer         [writable]
+          asset_temp     [writable] (owned by PDA)
+          data_pda       [writable]
+          buyer          [writable]
+          system_program []
+          token_program  []
+          mint_pda       [writable]
+          oracle         []
+          premium_temp   [writable] (partially filled bids only)
+          refund_acc     [writable] (partially filled asks with writer tokens only)
+    */
+    SettleAtExpiry,
+    /*
+        expected accounts:
+          redeemer        [signer]
+          writer_ata      [writable]
+          writer_mint     [writable]
+          mint_pda        []
+          asset_temp      [writable] (owned by PDA)
+          writer_receive  [writable] (owned by PDA)
+          collateral_dest [writable]
+          proceeds_dest   [writable]
+          data_pda        [writable]
+          writer          [writable] (original writer, escrow rent)
+          buyer           [writable] (original buyer, rent refund)
+          system_program  []
+          token_program   []
+        burns `lots` writer tokens of a settled contract for their share of the
+        collateral left in asset_temp and the proceeds in writer_receive
+    */
+    RedeemWriter {
+        lots: u64,
+    },
+    /*
+        expected accounts:
+          closer          [signer]
+          holder_ata      [writable]
+          holder_mint     [writable]
+          mint_pda        [writable]
+          writer_ata      [writable]
+          writer_mint     [writable]
+          asset_temp      [writable] (owned by PDA)
+          writer_receive  [writable] (owned by PDA, unless written before the writer mint)
+          collateral_dest [writable]
+          data_pda        [writable]
+          writer          [writable] (original writer)
+          buyer           [writable] (original buyer, rent refund)
+          system_program  []
+          token_program   []
+        burns `lots` holder tokens and as many writer tokens to release their collateral
+        before expiry, only the writer can close out a contract written before the writer mint
     */
-    CreateMint { seeds: (u8, [u8; 32]) },
+    CloseOut {
+        lots: u64,
+    },
+    /*
+        expected accounts:
+          holder           [signer]
+          strike_temp      [writable]
+          holder_receive   [writable]
+          holder_ata       [writable]
+          mint_pda         [writable]
+          holder_mint      [writable]
+          collateral_vault [writable]
+          proceeds_vault   [writable]
+          token_program    []
+          oracle           [] (cash-settled only)
+        exercises holder tokens of a pooled series against its vault, the terms are
+        checked against the mint_pda they derive
+    */
+    ExercisePooled {
+        contract_type: ContractType,
+        contract_data: ContractData,
+        lots: u64,
+    },
+    /*
+        expected accounts:
+          redeemer         [signer]
+          writer_ata       [writable]
+          writer_mint      [writable]
+          mint_pda         [writable]
+          collateral_vault [writable]
+          proceeds_vault   [writable]
+          collateral_dest  [writable]
+          proceeds_dest    [writable]
+          token_program    []
+        after expiry, burns `lots` writer tokens of a pooled series for their share of
+        both vaults, every writer token being one share
+    */
+    RedeemPooled {
+        contract_type: ContractType,
+        contract_data: ContractData,
+        lots: u64,
+    },
+    /*
+        expected accounts:
+          closer           [signer]
+          holder_ata       [writable]
+          holder_mint      [writable]
+          mint_pda         [writable]
+          writer_ata       [writable]
+          writer_mint      [writable]
+          collateral_vault [writable]
+          collateral_dest  [writable]
+          token_program    []
+        before expiry, burns `lots` holder and writer tokens of a pooled series for the
+        collateral of those lots
+    */
+    CloseOutPooled {
+        contract_type: ContractType,
+        contract_data: ContractData,
+        lots: u64,
+    },
+    /*
+        expected accounts:
+          initialiser      [writable, signer]
+          source           [writable] (premium for a bid, collateral for an ask)
+          token_temp       [writable] (empty, escrows whatever rests)
+          receive_acc      [writable]
+          receive_ata      [writable]
+          mint_pda         [writable]
+          holder_mint      [writable]
+          data_pda         [writable]
+          system_program   []
+          token_program    []
+          collateral_vault [writable]
+          proceeds_vault   [writable]
+          collateral_mint  []
+          proceeds_mint    []
+          order_book       [writable]
+          writer_mint      [writable]
+          writer_ata       [writable] (asks only, the initialiser's)
+          then for every resting order crossed, best first, its data_pda followed by
+          bids: premium_temp [writable], buyer [writable], buyer holder_ata [writable]
+          asks: premium receive_ata [writable], writer_ata [writable],
+                asset_temp [writable], writer [writable]
+          an order closed outside the book, or offered again at another premium, is
+          dropped from the book and passes its data_pda alone
+        fills a pooled offer against the book at the resting orders' premiums, as
+        AcceptBid and AcceptAsk would, and rests the remaining lots as an offer
+        escrowed in token_temp. the book is created by the first order of its series
+        and premium type. resting orders past their TTL are dropped like closed ones.
+        orders are public and cannot name a counterparty. a full side only rests an
+        order priced better than its worst, which is evicted but stays open outside it
+    */
+    PlaceOrder {
+        instruction: OfferData,
+    },
+    /*
+        expected accounts:
+          order_book     [writable]
+          then the accounts of CancelOffer
+        cancels a resting offer and takes it off the book
+    */
+    CancelOrder,
+    /*
+        expected accounts:
+          taker             [writable, signer]
+          data_pda          [writable] (not created, unless pooled)
+          quote_nonce       [writable] (not created)
+          quote_authority   [] (the maker's delegate)
+          instructions      [] (sysvar)
+          mint_pda          [writable]
+          holder_mint       [writable]
+          writer_mint       [writable]
+          buyer             []
+          premium_source    [writable] (the buyer's)
+          buyer_receive     [writable]
+          holder_ata        [writable]
+          writer            [writable]
+          collateral_source [writable] (the writer's)
+          asset_temp        [writable] (empty and owned by PDA, or the series vault if pooled)
+          writer_receive    [writable] (empty and owned by PDA, or the series proceeds if pooled)
+          prem_receive_acc  [writable]
+          writer_ata        [writable] (created if absent, unless pooled)
+          system_program    []
+          token_program     []
+          collateral_mint   [] (pooled only)
+          proceeds_mint     [] (pooled only)
+          ata_program       [] (not pooled)
+        the taker named in a quote the maker signed off-chain, verified by the
+        Ed25519 program instruction right before this one, fills every lot of it and
+        takes the other side.
+        the maker's funds move through its delegation to quote_authority, and
+        quote_nonce records the quote as accepted. the contract is created filled with
+        its writer mint, or leaves only the series vaults funded if pooled
+    */
+    AcceptQuote {
+        quote: Quote,
+    },
+    /*
+        same as CancelOffer once the offer's TTL has run out, but the initialiser does not
+        sign and refund_acc must be its associated account
+    */
+    CrankCancel,
+    /*
+        expected accounts:
+          initialiser    [writable] (signer)
+          data_pda       [writable]
+          token_program  []
+          token_temp     [writable] (bids only, owned by PDA)
+          premium_acc    [writable] (bids only)
+          order_book     [writable] (pooled only)
+        reprices a bid or ask that nothing has filled yet and bumps its offer_seq.
+        a bid's premium_temp is topped up from premium_acc, or the difference refunded
+        to it, to escrow the new premium. an order resting in the book is repriced
+        there too, behind the orders already at its new premium
+    */
+    AmendOffer {
+        premium_qty: u64,
+    },
+    /*
+        expected accounts:
+          holder          [signer]
+          holder_ata      [writable]
+          holder_mint     [writable]
+          mint_pda        []
+          asset_temp      [writable] (owned by PDA)
+          writer_receive  [writable] (owned by PDA)
+          payout_dest     [writable]
+          data_pda        [writable]
+          writer          [writable] (original writer, escrow rent)
+          buyer           [writable] (original buyer, rent refund)
+          system_program  []
+          token_program   []
+        burns `lots` holder tokens of a contract settled in the money for the payout
+        SettleAtExpiry set aside for each. whichever of the last claim and the last
+        writer token redemption comes second closes the contract
+    */
+    ClaimSettlement {
+        lots: u64,
+    },
+    /*
+        expected accounts:
+          payer           [writable, signer]
+          legacy_mint_pda [writable] (closed)
+          mint_pda        [writable] (not created)
+          holder_mint     [writable]
+          system_program  []
+          token_program   []
+        merges a series keyed by its premium type into the series account of its
+        economic terms: the legacy series account hands the holder mint and its counters
+        over and is closed, so holder tokens of every premium type become fungible. the
+        terms are those of any contract of the series, and the series account must not
+        exist yet. its contracts keep their writer mints and move over with MigrateContract before they can be filled or settled
+    */
+    MigrateSeries {
+        contract_type: ContractType,
+        contract_data: ContractData,
+    },
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum InitParty {
     BUYER,
     WRITER,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct OfferData {
     pub contract_data: ContractData,
     pub pda: Pubkey,
@@ -121,68 +488,1590 @@ pub struct OfferData {
     pub party: InitParty,
     pub contract_type: ContractType,
     pub index_seed: [u8; 32],
+    pub index_data: [u8; 41],
+    // how long the offer can be taken for, in the unit of the contract's expiry_date
+    pub offer_ttl: Option<i64>,
+    // the only key that may take the offer
+    pub counterparty: Option<Pubkey>,
+}
+
+// a maker's premium for one side of a contract, negotiated off-chain and signed over
+// message(program_id) with the maker's key
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quote {
+    pub maker: Pubkey,
+    // the maker's side of the contract
+    pub party: InitParty,
+    // the only key that may accept the quote
+    pub taker: Pubkey,
+    pub contract_type: ContractType,
+    // premium_qty and lots as quoted
+    pub contract_data: ContractData,
+    // unix time in milliseconds, after which the quote cannot be accepted
+    pub valid_until: i64,
+    // also the contract_no of the index seed, see index_data
+    pub nonce: u64,
 }
 
+impl Quote {
+    // what the maker signs; the program id keeps a quote from being taken elsewhere
+    pub fn message(&self, program_id: &Pubkey) -> Vec<u8> {
+        let mut msg = b"optquote".to_vec();
+        msg.extend_from_slice(program_id.as_ref());
+        pack_quote(self, &mut msg);
+        msg
+    }
+
+    // the contract is indexed like an offer initialised by the maker, with a trailing
+    // QUOTE_INDEX so that a nonce never takes the index of one of the maker's offers
+    pub fn index_data(&self) -> [u8; 42] {
+        let mut index_data = [0; 42];
+        index_data[..32].copy_from_slice(self.maker.as_ref());
+        index_data[32] = self.contract_type as u8;
+        index_data[33..41].copy_from_slice(&self.nonce.to_le_bytes());
+        index_data[41] = QUOTE_INDEX;
+        index_data
+    }
+}
+
+pub const INSTRUCTION_VERSION: u8 = 1;
+
+// offer payload: [contract_type, ..contract_data, ..index_seed, ..offer_ttl, ..counterparty]
+const OFFER_LEN: usize = 1 + ContractData::LEN + 41 + 9 + 33;
+
+impl OfferData {
+    pub fn new(
+        program_id: &Pubkey,
+        party: InitParty,
+        contract_type: ContractType,
+        contract_data: ContractData,
+        index_data: [u8; 41],
+        offer_ttl: Option<i64>,
+        counterparty: Option<Pubkey>,
+    ) -> Self {
+        let seed = contract_data.get_seed();
+        let index_seed = get_seed(&index_data);
+        let (pda, bump) = Pubkey::find_program_address(&[&seed, &index_seed], program_id);
+
+        OfferData {
+            contract_data,
+            pda,
+            bump,
+            seed,
+            party,
+            contract_type,
+            index_seed,
+            index_data,
+            offer_ttl,
+            counterparty,
+        }
+    }
+}
+
+impl InstructionType {
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = vec![INSTRUCTION_VERSION];
+        match self {
+            InstructionType::Bid { instruction } => {
+                buf.push(0);
+                pack_offer_data(instruction, &mut buf);
+            }
+            InstructionType::Ask { instruction } => {
+                buf.push(1);
+                pack_offer_data(instruction, &mut buf);
+            }
+            InstructionType::AcceptBid { lots, offer_seq } => {
+                buf.push(2);
+                pack_accept(*lots, *offer_seq, &mut buf);
+            }
+            InstructionType::AcceptAsk { lots, offer_seq } => {
+                buf.push(3);
+                pack_accept(*lots, *offer_seq, &mut buf);
+            }
+            InstructionType::CancelOffer => buf.push(4),
+            InstructionType::Execute { lots } => {
+                buf.push(5);
+                buf.extend_from_slice(&lots.to_le_bytes());
+            }
+            InstructionType::Expire => buf.push(6),
+            InstructionType::CreateMint {
+                contract_type,
+                contract_data,
+            } => {
+                buf.push(7);
+                pack_terms(*contract_type, contract_data, &mut buf);
+            }
+            InstructionType::MigrateContract => buf.push(8),
+            InstructionType::CrankExpire => buf.push(9),
+            InstructionType::SettleAtExpiry => buf.push(10),
+            InstructionType::RedeemWriter { lots } => {
+                buf.push(11);
+                buf.extend_from_slice(&lots.to_le_bytes());
+            }
+            InstructionType::CloseOut { lots } => {
+                buf.push(12);
+                buf.extend_from_slice(&lots.to_le_bytes());
+            }
+            InstructionType::ExercisePooled {
+                contract_type,
+                contract_data,
+                lots,
+            } => {
+                buf.push(13);
+                pack_series(*contract_type, contract_data, *lots, &mut buf);
+            }
+            InstructionType::RedeemPooled {
+                contract_type,
+                contract_data,
+                lots,
+            } => {
+                buf.push(14);
+                pack_series(*contract_type, contract_data, *lots, &mut buf);
+            }
+            InstructionType::CloseOutPooled {
+                contract_type,
+                contract_data,
+                lots,
+            } => {
+                buf.push(15);
+                pack_series(*contract_type, contract_data, *lots, &mut buf);
+            }
+            InstructionType::PlaceOrder { instruction } => {
+                buf.push(16);
+                buf.push(match instruction.party {
+                    InitParty::BUYER => 0,
+                    InitParty::WRITER => 1,
+                });
+                pack_offer_data(instruction, &mut buf);
+            }
+            InstructionType::CancelOrder => buf.push(17),
+            InstructionType::AcceptQuote { quote } => {
+                buf.push(18);
+                pack_quote(quote, &mut buf);
+            }
+            InstructionType::CrankCancel => buf.push(19),
+            InstructionType::AmendOffer { premium_qty } => {
+                buf.push(20);
+                buf.extend_from_slice(&premium_qty.to_le_bytes());
+            }
+            InstructionType::ClaimSettlement { lots } => {
+                buf.push(21);
+                buf.extend_from_slice(&lots.to_le_bytes());
+            }
+            InstructionType::MigrateSeries {
+                contract_type,
+                contract_data,
+            } => {
+                buf.push(22);
+                pack_terms(*contract_type, contract_data, &mut buf);
+            }
+        };
+        buf
+    }
+}
+
+// instruction data: [version, instruction_type, ..payload]
 pub fn decode_instruction(
     program_id: &Pubkey,
     instruction_data: &[u8],
 ) -> Result<InstructionType, ProgramError> {
-    match instruction_data[0] {
-        0 => build_offer_data(program_id, InitParty::BUYER, instruction_data),
-        1 => build_offer_data(program_id, InitParty::WRITER, instruction_data),
-        2 => Ok(InstructionType::AcceptBid),
-        3 => Ok(InstructionType::AcceptAsk),
-        4 => Ok(InstructionType::CancelOffer),
-        5 => Ok(InstructionType::Execute),
-        6 => Ok(InstructionType::Expire),
-        7 => Ok(InstructionType::CreateMint {
-            seeds: (
-                instruction_data[1],
-                instruction_data[2..]
-                    .try_into()
-                    .map_err(|_| ProgramError::InvalidInstructionData)?,
-            ),
+    let (version, rest) = instruction_data
+        .split_first()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    if *version != INSTRUCTION_VERSION {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (tag, payload) = rest
+        .split_first()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+
+    match (tag, payload.len()) {
+        (0, _) => Ok(InstructionType::Bid {
+            instruction: unpack_offer_data(program_id, InitParty::BUYER, payload)?,
+        }),
+        (1, _) => Ok(InstructionType::Ask {
+            instruction: unpack_offer_data(program_id, InitParty::WRITER, payload)?,
+        }),
+        (2, ACCEPT_LEN) => {
+            let (lots, offer_seq) = unpack_accept(payload)?;
+            Ok(InstructionType::AcceptBid { lots, offer_seq })
+        }
+        (3, ACCEPT_LEN) => {
+            let (lots, offer_seq) = unpack_accept(payload)?;
+            Ok(InstructionType::AcceptAsk { lots, offer_seq })
+        }
+        (4, 0) => Ok(InstructionType::CancelOffer),
+        (5, 8) => Ok(InstructionType::Execute {
+            lots: unpack_lots(payload)?,
         }),
+        (6, 0) => Ok(InstructionType::Expire),
+        (7, TERMS_LEN) => {
+            let (contract_type, contract_data) = unpack_terms(payload)?;
+            Ok(InstructionType::CreateMint {
+                contract_type,
+                contract_data,
+            })
+        }
+        (8, 0) => Ok(InstructionType::MigrateContract),
+        (9, 0) => Ok(InstructionType::CrankExpire),
+        (10, 0) => Ok(InstructionType::SettleAtExpiry),
+        (11, 8) => Ok(InstructionType::RedeemWriter {
+            lots: unpack_lots(payload)?,
+        }),
+        (12, 8) => Ok(InstructionType::CloseOut {
+            lots: unpack_lots(payload)?,
+        }),
+        (13, SERIES_LEN) => {
+            let (contract_type, contract_data, lots) = unpack_series(payload)?;
+            Ok(InstructionType::ExercisePooled {
+                contract_type,
+                contract_data,
+                lots,
+            })
+        }
+        (14, SERIES_LEN) => {
+            let (contract_type, contract_data, lots) = unpack_series(payload)?;
+            Ok(InstructionType::RedeemPooled {
+                contract_type,
+                contract_data,
+                lots,
+            })
+        }
+        (15, SERIES_LEN) => {
+            let (contract_type, contract_data, lots) = unpack_series(payload)?;
+            Ok(InstructionType::CloseOutPooled {
+                contract_type,
+                contract_data,
+                lots,
+            })
+        }
+        (16, _) => {
+            let (party, offer) = payload
+                .split_first()
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            let party = match party {
+                0 => InitParty::BUYER,
+                1 => InitParty::WRITER,
+                _ => return Err(ProgramError::InvalidInstructionData),
+            };
+            Ok(InstructionType::PlaceOrder {
+                instruction: unpack_offer_data(program_id, party, offer)?,
+            })
+        }
+        (17, 0) => Ok(InstructionType::CancelOrder),
+        (18, QUOTE_LEN) => Ok(InstructionType::AcceptQuote {
+            quote: unpack_quote(payload)?,
+        }),
+        (19, 0) => Ok(InstructionType::CrankCancel),
+        (20, 8) => Ok(InstructionType::AmendOffer {
+            premium_qty: unpack_lots(payload)?,
+        }),
+        (21, 8) => Ok(InstructionType::ClaimSettlement {
+            lots: unpack_lots(payload)?,
+        }),
+        (22, TERMS_LEN) => {
+            let (contract_type, contract_data) = unpack_terms(payload)?;
+            Ok(InstructionType::MigrateSeries {
+                contract_type,
+                contract_data,
+            })
+        }
         _ => Err(ProgramError::InvalidInstructionData),
     }
 }
 
-// instruction data: [instruction_type, contract_type, ..contract_data, ..index_seed]
+fn unpack_lots(payload: &[u8]) -> Result<u64, ProgramError> {
+    Ok(u64::from_le_bytes(
+        payload
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?,
+    ))
+}
+
+// accept payload: [..lots, ..offer_seq]
+const ACCEPT_LEN: usize = 8 + 9;
+
+fn unpack_accept(payload: &[u8]) -> Result<(u64, Option<u64>), ProgramError> {
+    let (lots, offer_seq) = payload.split_at(8);
+    let offer_seq = match offer_seq[0] {
+        0 => None,
+        1 => Some(unpack_lots(&offer_seq[1..])?),
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    Ok((unpack_lots(lots)?, offer_seq))
+}
+
+fn pack_accept(lots: u64, offer_seq: Option<u64>, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&lots.to_le_bytes());
+    match offer_seq {
+        Some(offer_seq) => {
+            buf.push(1);
+            buf.extend_from_slice(&offer_seq.to_le_bytes());
+        }
+        None => buf.extend_from_slice(&[0; 9]),
+    };
+}
+
 // index_seed format: [0..32 = initialiser main pubkey, 32 = contract_type, 33..41 = contract_no (u64)]
 
-fn build_offer_data(
+fn unpack_offer_data(
     pid: &Pubkey,
     party: InitParty,
-    instruction_data: &[u8],
-) -> Result<InstructionType, ProgramError> {
-    let contract_type = match instruction_data[1] {
+    payload: &[u8],
+) -> Result<OfferData, ProgramError> {
+    if payload.len() != OFFER_LEN {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let contract_type = match payload[0] {
         0 => ContractType::CALL,
         1 => ContractType::PUT,
         _ => return Err(ProgramError::InvalidInstructionData),
     };
-    let seed: [u8; ContractData::LEN] = instruction_data[2..ContractData::LEN + 2]
+    let contract_data = ContractData::deserialize(&payload[1..ContractData::LEN + 1])
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let (index_data, offer_ttl) = payload[ContractData::LEN + 1..].split_at(41);
+    let (offer_ttl, counterparty) = offer_ttl.split_at(9);
+    let index_data: [u8; 41] = index_data
         .try_into()
         .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let offer_ttl = match offer_ttl[0] {
+        0 => None,
+        1 => Some(i64::from_le_bytes(offer_ttl[1..].try_into().unwrap())),
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    let counterparty = match counterparty[0] {
+        0 => None,
+        1 => Some(Pubkey::new_from_array(
+            counterparty[1..].try_into().unwrap(),
+        )),
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
 
-    let index_seed: [u8; 41] = instruction_data[ContractData::LEN + 2..]
-        .try_into()
+    Ok(OfferData::new(
+        pid,
+        party,
+        contract_type,
+        contract_data,
+        index_data,
+        offer_ttl,
+        counterparty,
+    ))
+}
+
+// terms payload: [contract_type, ..contract_data]
+const TERMS_LEN: usize = 1 + ContractData::LEN;
+
+fn unpack_terms(payload: &[u8]) -> Result<(ContractType, ContractData), ProgramError> {
+    let contract_type = match payload[0] {
+        0 => ContractType::CALL,
+        1 => ContractType::PUT,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    let contract_data = ContractData::deserialize(&payload[1..TERMS_LEN])
         .map_err(|_| ProgramError::InvalidInstructionData)?;
-    let contract_data = ContractData::deserialize(&seed);
+    Ok((contract_type, contract_data))
+}
+
+fn pack_terms(contract_type: ContractType, contract_data: &ContractData, buf: &mut Vec<u8>) {
+    buf.push(match contract_type {
+        ContractType::CALL => 0,
+        ContractType::PUT => 1,
+    });
+    buf.extend_from_slice(&contract_data.serialize());
+}
+
+// series payload: [..terms, ..lots]
+const SERIES_LEN: usize = TERMS_LEN + 8;
+
+fn unpack_series(payload: &[u8]) -> Result<(ContractType, ContractData, u64), ProgramError> {
+    let (contract_type, contract_data) = unpack_terms(&payload[..TERMS_LEN])?;
+    let lots = unpack_lots(&payload[TERMS_LEN..])?;
+    Ok((contract_type, contract_data, lots))
+}
+
+fn pack_series(
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    lots: u64,
+    buf: &mut Vec<u8>,
+) {
+    pack_terms(contract_type, contract_data, buf);
+    buf.extend_from_slice(&lots.to_le_bytes());
+}
+
+// quote payload: [..maker, party, ..taker, ..terms, ..valid_until, ..nonce]
+const QUOTE_LEN: usize = 65 + TERMS_LEN + 16;
+
+const QUOTE_INDEX: u8 = b'q';
+
+fn unpack_quote(payload: &[u8]) -> Result<Quote, ProgramError> {
+    let maker = Pubkey::new_from_array(
+        payload[..32]
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?,
+    );
+    let party = match payload[32] {
+        0 => InitParty::BUYER,
+        1 => InitParty::WRITER,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    let taker = Pubkey::new_from_array(
+        payload[33..65]
+            .try_into()
+            .map_err(|_| ProgramError::InvalidInstructionData)?,
+    );
+    let (contract_type, contract_data) = unpack_terms(&payload[65..65 + TERMS_LEN])?;
+    let (valid_until, nonce) = payload[65 + TERMS_LEN..].split_at(8);
+    Ok(Quote {
+        maker,
+        party,
+        taker,
+        contract_type,
+        contract_data,
+        valid_until: i64::from_le_bytes(valid_until.try_into().unwrap()),
+        nonce: unpack_lots(nonce)?,
+    })
+}
+
+fn pack_quote(quote: &Quote, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(quote.maker.as_ref());
+    buf.push(match quote.party {
+        InitParty::BUYER => 0,
+        InitParty::WRITER => 1,
+    });
+    buf.extend_from_slice(quote.taker.as_ref());
+    pack_terms(quote.contract_type, &quote.contract_data, buf);
+    buf.extend_from_slice(&quote.valid_until.to_le_bytes());
+    buf.extend_from_slice(&quote.nonce.to_le_bytes());
+}
 
-    let seed = contract_data.get_seed();
-    let index_seed = get_seed(&index_seed);
+fn pack_offer_data(od: &OfferData, buf: &mut Vec<u8>) {
+    buf.push(match od.contract_type {
+        ContractType::CALL => 0,
+        ContractType::PUT => 1,
+    });
+    buf.extend_from_slice(&od.contract_data.serialize());
+    buf.extend_from_slice(&od.index_data);
+    match od.offer_ttl {
+        Some(ttl) => {
+            buf.push(1);
+            buf.extend_from_slice(&ttl.to_le_bytes());
+        }
+        None => buf.extend_from_slice(&[0; 9]),
+    };
+    match od.counterparty {
+        Some(key) => {
+            buf.push(1);
+            buf.extend_from_slice(key.as_ref());
+        }
+        None => buf.extend_from_slice(&[0; 33]),
+    };
+}
+
+// every series account is keyed by contract type and a series seed: the economic
+// terms for new contracts, see ContractPDA.series_seed
+pub fn get_mint_pda(
+    program_id: &Pubkey,
+    contract_type: ContractType,
+    seed: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&[contract_type as u8], seed], program_id)
+}
+
+// the writer mint of a pooled series: minted by mint_pda like holder tokens, but a PDA itself
+pub fn get_writer_mint(
+    program_id: &Pubkey,
+    contract_type: ContractType,
+    seed: &[u8; 32],
+) -> (Pubkey, u8) {
+    find_series_address(program_id, contract_type, seed, b"writer")
+}
+
+// any contract but a pooled one mints its own writer tokens, so they only redeem its collateral
+pub fn get_contract_writer_mint(program_id: &Pubkey, data_pda: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[data_pda.as_ref(), b"writer"], program_id)
+}
+
+// pooled lots pay from the series vaults, so their writer tokens are the series'
+pub fn get_writer_mint_of(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+    data_pda: &Pubkey,
+) -> (Pubkey, u8) {
+    match contract.contract_data.pooled {
+        true => get_writer_mint(program_id, contract.contract_type, &contract.series_seed),
+        false => get_contract_writer_mint(program_id, data_pda),
+    }
+}
+
+// token accounts of a pooled series, both with mint_pda as their authority
+pub fn get_series_vault(
+    program_id: &Pubkey,
+    contract_type: ContractType,
+    seed: &[u8; 32],
+) -> (Pubkey, u8) {
+    find_series_address(program_id, contract_type, seed, b"vault")
+}
+
+pub fn get_series_proceeds(
+    program_id: &Pubkey,
+    contract_type: ContractType,
+    seed: &[u8; 32],
+) -> (Pubkey, u8) {
+    find_series_address(program_id, contract_type, seed, b"proceeds")
+}
+
+fn find_series_address(
+    program_id: &Pubkey,
+    contract_type: ContractType,
+    seed: &[u8; 32],
+    label: &[u8],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&[contract_type as u8], seed, label], program_id)
+}
+
+// one book per series and premium type, as premiums in different tokens are not comparable
+pub fn get_order_book(
+    program_id: &Pubkey,
+    contract_type: ContractType,
+    seed: &[u8; 32],
+    premium_type: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&[contract_type as u8], seed, b"book", premium_type.as_ref()],
+        program_id,
+    )
+}
+
+pub fn get_data_pda(program_id: &Pubkey, seed: &[u8; 32], index_seed: &[u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[seed, index_seed], program_id).0
+}
+
+pub fn create_mint(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    holder_mint: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+) -> Instruction {
+    let series_seed = contract_data.series_seed();
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract_type, &series_seed);
+    let (writer_mint, _bump) = get_writer_mint(program_id, contract_type, &series_seed);
+
+    let data = InstructionType::CreateMint {
+        contract_type,
+        contract_data: contract_data.clone(),
+    }
+    .pack();
 
-    let (pda, bump) = Pubkey::find_program_address(&[&seed, &index_seed], pid);
+    let accounts = vec![
+        AccountMeta::new(*sender, true),
+        AccountMeta::new(*holder_mint, true),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(writer_mint, false),
+    ];
+    Instruction::new_with_bytes(*program_id, &data, accounts)
+}
 
-    let od = OfferData {
+#[allow(clippy::too_many_arguments)]
+pub fn bid(
+    program_id: &Pubkey,
+    buyer: &Pubkey,
+    prem_temp: &Pubkey,
+    receive_acc: &Pubkey,
+    holder_mint: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    index_seed: &[u8; 41],
+    offer_ttl: Option<i64>,
+    counterparty: Option<Pubkey>,
+) -> Instruction {
+    let receive_ata = get_associated_token_address(buyer, holder_mint);
+    offer(
+        program_id,
+        InitParty::BUYER,
+        buyer,
+        prem_temp,
+        receive_acc,
+        &receive_ata,
+        holder_mint,
+        contract_type,
         contract_data,
-        pda,
-        bump,
-        seed,
         index_seed,
+        offer_ttl,
+        counterparty,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn ask(
+    program_id: &Pubkey,
+    writer: &Pubkey,
+    asset_temp: &Pubkey,
+    receive_acc: &Pubkey,
+    prem_receive_acc: &Pubkey,
+    holder_mint: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    index_seed: &[u8; 41],
+    offer_ttl: Option<i64>,
+    counterparty: Option<Pubkey>,
+) -> Instruction {
+    offer(
+        program_id,
+        InitParty::WRITER,
+        writer,
+        asset_temp,
+        receive_acc,
+        prem_receive_acc,
+        holder_mint,
+        contract_type,
+        contract_data,
+        index_seed,
+        offer_ttl,
+        counterparty,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn offer(
+    program_id: &Pubkey,
+    party: InitParty,
+    initialiser: &Pubkey,
+    token_temp: &Pubkey,
+    receive_acc: &Pubkey,
+    receive_ata: &Pubkey,
+    holder_mint: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    index_seed: &[u8; 41],
+    offer_ttl: Option<i64>,
+    counterparty: Option<Pubkey>,
+) -> Instruction {
+    let series_seed = contract_data.series_seed();
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract_type, &series_seed);
+
+    let od = OfferData::new(
+        program_id,
         party,
         contract_type,
+        contract_data.clone(),
+        *index_seed,
+        offer_ttl,
+        counterparty,
+    );
+    let data_pda = od.pda;
+    let data = match od.party {
+        InitParty::BUYER => InstructionType::Bid { instruction: od },
+        InitParty::WRITER => InstructionType::Ask { instruction: od },
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*initialiser, true),
+        AccountMeta::new(*token_temp, false),
+        AccountMeta::new(*receive_acc, false),
+        AccountMeta::new(*receive_ata, false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if contract_data.pooled {
+        let (collateral_mint, _) = contract_data.collateral(contract_type);
+        let (_, proceeds_mint) = contract_data.receive_types(contract_type);
+        let (vault, _bump) = get_series_vault(program_id, contract_type, &series_seed);
+        let (proceeds, _bump) = get_series_proceeds(program_id, contract_type, &series_seed);
+        accounts.extend([
+            AccountMeta::new(vault, false),
+            AccountMeta::new(proceeds, false),
+            AccountMeta::new_readonly(collateral_mint, false),
+            AccountMeta::new_readonly(proceeds_mint, false),
+        ]);
+    } else {
+        let (writer_mint, _bump) = get_contract_writer_mint(program_id, &data_pda);
+        accounts.push(AccountMeta::new(writer_mint, false));
+    }
+    Instruction::new_with_bytes(*program_id, &data, accounts)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn accept_bid(
+    program_id: &Pubkey,
+    writer: &Pubkey,
+    asset_temp: &Pubkey,
+    strike_receive_acc: &Pubkey,
+    prem_receive_acc: &Pubkey,
+    holder_mint: &Pubkey,
+    contract: &ContractPDA,
+    lots: u64,
+) -> Result<Instruction, ProgramError> {
+    let bd = contract
+        .buyer_data
+        .as_ref()
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract.contract_type, &contract.series_seed);
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+    let (writer_mint, _bump) = get_writer_mint_of(program_id, contract, &data_pda);
+
+    let mut accounts = vec![
+        AccountMeta::new(*writer, true),
+        AccountMeta::new(*asset_temp, false),
+        AccountMeta::new(*strike_receive_acc, false),
+        AccountMeta::new(*prem_receive_acc, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new(bd.temp_pub, false),
+        AccountMeta::new(bd.party_pub, false),
+        AccountMeta::new(bd.receive_ata, false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(writer_mint, false),
+        AccountMeta::new(get_associated_token_address(writer, &writer_mint), false),
+    ];
+    if contract.contract_data.pooled {
+        let (vault, _bump) =
+            get_series_vault(program_id, contract.contract_type, &contract.series_seed);
+        accounts.push(AccountMeta::new(vault, false));
+    } else if let Some(wd) = &contract.writer_data {
+        accounts.push(AccountMeta::new(wd.temp_pub, false));
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::AcceptBid {
+            lots,
+            offer_seq: Some(contract.offer_seq),
+        }
+        .pack(),
+        accounts,
+    ))
+}
+
+pub fn accept_ask(
+    program_id: &Pubkey,
+    buyer: &Pubkey,
+    prem_temp: &Pubkey,
+    buyer_receive: &Pubkey,
+    holder_mint: &Pubkey,
+    contract: &ContractPDA,
+    lots: u64,
+) -> Result<Instruction, ProgramError> {
+    let wd = contract
+        .writer_data
+        .as_ref()
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract.contract_type, &contract.series_seed);
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+    let (writer_mint, _bump) = get_writer_mint_of(program_id, contract, &data_pda);
+    let holder_ata = get_associated_token_address(buyer, holder_mint);
+
+    let mut accounts = vec![
+        AccountMeta::new(*buyer, true),
+        AccountMeta::new(*prem_temp, false),
+        AccountMeta::new(*buyer_receive, false),
+        AccountMeta::new(holder_ata, false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new(wd.receive_ata, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(writer_mint, false),
+        AccountMeta::new(
+            get_associated_token_address(&wd.party_pub, &writer_mint),
+            false,
+        ),
+    ];
+    if contract.contract_data.pooled {
+        let (vault, _bump) =
+            get_series_vault(program_id, contract.contract_type, &contract.series_seed);
+        accounts.extend([
+            AccountMeta::new(wd.temp_pub, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(wd.party_pub, false),
+        ]);
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::AcceptAsk {
+            lots,
+            offer_seq: Some(contract.offer_seq),
+        }
+        .pack(),
+        accounts,
+    ))
+}
+
+pub fn cancel_offer(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+) -> Result<Instruction, ProgramError> {
+    cancel_instruction(program_id, contract, InstructionType::CancelOffer)
+}
+
+pub fn crank_cancel(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+) -> Result<Instruction, ProgramError> {
+    cancel_instruction(program_id, contract, InstructionType::CrankCancel)
+}
+
+fn cancel_instruction(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+    instruction: InstructionType,
+) -> Result<Instruction, ProgramError> {
+    let initialiser_signs = instruction == InstructionType::CancelOffer;
+    let pd = match contract.init_party {
+        InitParty::BUYER => contract.buyer_data.as_ref(),
+        InitParty::WRITER => contract.writer_data.as_ref(),
+    }
+    .ok_or(ProgramError::InvalidAccountData)?;
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+
+    let mut accounts = vec![
+        AccountMeta::new(pd.party_pub, initialiser_signs),
+        AccountMeta::new(pd.temp_pub, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    // the unfilled collateral goes back to the writer's associated account
+    if contract.contract_state == ContractState::PARTIALLY_FILLED
+        && contract.init_party == InitParty::WRITER
+        && !contract.contract_data.pooled
+    {
+        let (collateral, _) = contract.contract_data.collateral(contract.contract_type);
+        accounts.push(AccountMeta::new(
+            get_associated_token_address(&pd.party_pub, &collateral),
+            false,
+        ));
+    }
+    if contract.contract_state == ContractState::ASK && !contract.contract_data.pooled {
+        accounts.push(AccountMeta::new(pd.receive_pub, false));
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &instruction.pack(),
+        accounts,
+    ))
+}
+
+// a bid's premium moves between premium_temp and the buyer's associated account
+pub fn amend_offer(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+    premium_qty: u64,
+) -> Result<Instruction, ProgramError> {
+    let pd = match contract.init_party {
+        InitParty::BUYER => contract.buyer_data.as_ref(),
+        InitParty::WRITER => contract.writer_data.as_ref(),
+    }
+    .ok_or(ProgramError::InvalidAccountData)?;
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+
+    let mut accounts = vec![
+        AccountMeta::new(pd.party_pub, true),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if contract.init_party == InitParty::BUYER {
+        accounts.extend([
+            AccountMeta::new(pd.temp_pub, false),
+            AccountMeta::new(
+                get_associated_token_address(&pd.party_pub, &contract.contract_data.premium_type),
+                false,
+            ),
+        ]);
+    }
+    if contract.contract_data.pooled {
+        let (order_book, _bump) = get_order_book(
+            program_id,
+            contract.contract_type,
+            &contract.series_seed,
+            &contract.contract_data.premium_type,
+        );
+        accounts.push(AccountMeta::new(order_book, false));
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::AmendOffer { premium_qty }.pack(),
+        accounts,
+    ))
+}
+
+pub fn execute(
+    program_id: &Pubkey,
+    holder: &Pubkey,
+    strike_temp: &Pubkey,
+    holder_receive: &Pubkey,
+    holder_mint: &Pubkey,
+    contract: &ContractPDA,
+    lots: u64,
+) -> Result<Instruction, ProgramError> {
+    let wd = contract
+        .writer_data
+        .as_ref()
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let bd = contract
+        .buyer_data
+        .as_ref()
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract.contract_type, &contract.series_seed);
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+    let holder_ata = get_associated_token_address(holder, holder_mint);
+
+    let mut accounts = vec![
+        AccountMeta::new(*holder, true),
+        AccountMeta::new(*strike_temp, false),
+        AccountMeta::new(*holder_receive, false),
+        AccountMeta::new(holder_ata, false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new(wd.temp_pub, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new(wd.party_pub, false),
+        AccountMeta::new(wd.receive_pub, false),
+        AccountMeta::new(bd.party_pub, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if contract.contract_data.settlement == SettlementType::CASH {
+        let oracle = contract
+            .contract_data
+            .oracle
+            .ok_or(ProgramError::InvalidAccountData)?;
+        accounts.push(AccountMeta::new_readonly(oracle, false));
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::Execute { lots }.pack(),
+        accounts,
+    ))
+}
+
+pub fn expire(program_id: &Pubkey, contract: &ContractPDA) -> Result<Instruction, ProgramError> {
+    expire_instruction(program_id, contract, InstructionType::Expire)
+}
+
+pub fn crank_expire(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+) -> Result<Instruction, ProgramError> {
+    expire_instruction(program_id, contract, InstructionType::CrankExpire)
+}
+
+pub fn settle_at_expiry(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+) -> Result<Instruction, ProgramError> {
+    expire_instruction(program_id, contract, InstructionType::SettleAtExpiry)
+}
+
+fn expire_instruction(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+    instruction: InstructionType,
+) -> Result<Instruction, ProgramError> {
+    let writer_signs = instruction == InstructionType::Expire;
+    let (wd, bd) = match (&contract.writer_data, &contract.buyer_data) {
+        (Some(wd), Some(bd)) => (wd, bd),
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract.contract_type, &contract.series_seed);
+
+    let mut accounts = vec![
+        AccountMeta::new(wd.party_pub, writer_signs),
+        AccountMeta::new(wd.temp_pub, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new(bd.party_pub, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(mint_pda, false),
+    ];
+    if instruction == InstructionType::SettleAtExpiry {
+        let oracle = contract
+            .contract_data
+            .oracle
+            .ok_or(ProgramError::InvalidAccountData)?;
+        accounts.push(AccountMeta::new_readonly(oracle, false));
+    }
+    if contract.contract_state == ContractState::PARTIALLY_FILLED
+        && contract.init_party == InitParty::BUYER
+    {
+        accounts.push(AccountMeta::new(bd.temp_pub, false));
+    }
+    // the unfilled collateral goes back to the writer's associated account
+    if contract.contract_state == ContractState::PARTIALLY_FILLED
+        && contract.init_party == InitParty::WRITER
+        && contract.writer_lots > 0
+    {
+        let (collateral, _) = contract.contract_data.collateral(contract.contract_type);
+        accounts.push(AccountMeta::new(
+            get_associated_token_address(&wd.party_pub, &collateral),
+            false,
+        ));
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &instruction.pack(),
+        accounts,
+    ))
+}
+
+pub fn migrate_contract(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    data_pda: &Pubkey,
+    mint_pda: &Pubkey,
+    writer_mint: &Pubkey,
+    holder_mint: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*data_pda, false),
+        AccountMeta::new(*mint_pda, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(*writer_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(*holder_mint, false),
+    ];
+    Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::MigrateContract.pack(),
+        accounts,
+    )
+}
+
+// for a contract whose legacy series MigrateSeries has merged
+pub fn migrate_merged_contract(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    data_pda: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    holder_mint: &Pubkey,
+) -> Instruction {
+    let series_seed = contract_data.series_seed();
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract_type, &series_seed);
+    let (writer_mint, _bump) = get_contract_writer_mint(program_id, data_pda);
+    let (legacy_mint_pda, _bump) =
+        get_mint_pda(program_id, contract_type, &contract_data.get_seed());
+    let mut ix = migrate_contract(
+        program_id,
+        payer,
+        data_pda,
+        &mint_pda,
+        &writer_mint,
+        holder_mint,
+    );
+    ix.accounts
+        .push(AccountMeta::new_readonly(legacy_mint_pda, false));
+    ix
+}
+
+pub fn migrate_series(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    holder_mint: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+) -> Instruction {
+    let legacy_seed = contract_data.get_seed();
+    let series_seed = contract_data.series_seed();
+    let (legacy_mint_pda, _bump) = get_mint_pda(program_id, contract_type, &legacy_seed);
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract_type, &series_seed);
+
+    let data = InstructionType::MigrateSeries {
+        contract_type,
+        contract_data: contract_data.clone(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(legacy_mint_pda, false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Instruction::new_with_bytes(*program_id, &data, accounts)
+}
+
+pub fn redeem_writer(
+    program_id: &Pubkey,
+    redeemer: &Pubkey,
+    collateral_dest: &Pubkey,
+    proceeds_dest: &Pubkey,
+    contract: &ContractPDA,
+    lots: u64,
+) -> Result<Instruction, ProgramError> {
+    let (wd, bd) = match (&contract.writer_data, &contract.buyer_data) {
+        (Some(wd), Some(bd)) => (wd, bd),
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract.contract_type, &contract.series_seed);
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+    let (writer_mint, _bump) = get_writer_mint_of(program_id, contract, &data_pda);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*redeemer, true),
+        AccountMeta::new(get_associated_token_address(redeemer, &writer_mint), false),
+        AccountMeta::new(writer_mint, false),
+        AccountMeta::new_readonly(mint_pda, false),
+        AccountMeta::new(wd.temp_pub, false),
+        AccountMeta::new(wd.receive_pub, false),
+        AccountMeta::new(*collateral_dest, false),
+        AccountMeta::new(*proceeds_dest, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new(wd.party_pub, false),
+        AccountMeta::new(bd.party_pub, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::RedeemWriter { lots }.pack(),
+        accounts,
+    ))
+}
+
+pub fn claim_settlement(
+    program_id: &Pubkey,
+    holder: &Pubkey,
+    payout_dest: &Pubkey,
+    holder_mint: &Pubkey,
+    contract: &ContractPDA,
+    lots: u64,
+) -> Result<Instruction, ProgramError> {
+    let (wd, bd) = match (&contract.writer_data, &contract.buyer_data) {
+        (Some(wd), Some(bd)) => (wd, bd),
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract.contract_type, &contract.series_seed);
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*holder, true),
+        AccountMeta::new(get_associated_token_address(holder, holder_mint), false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new_readonly(mint_pda, false),
+        AccountMeta::new(wd.temp_pub, false),
+        AccountMeta::new(wd.receive_pub, false),
+        AccountMeta::new(*payout_dest, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new(wd.party_pub, false),
+        AccountMeta::new(bd.party_pub, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::ClaimSettlement { lots }.pack(),
+        accounts,
+    ))
+}
+
+pub fn close_out(
+    program_id: &Pubkey,
+    closer: &Pubkey,
+    collateral_dest: &Pubkey,
+    holder_mint: &Pubkey,
+    contract: &ContractPDA,
+    lots: u64,
+) -> Result<Instruction, ProgramError> {
+    let (wd, bd) = match (&contract.writer_data, &contract.buyer_data) {
+        (Some(wd), Some(bd)) => (wd, bd),
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract.contract_type, &contract.series_seed);
+    let data_pda = get_data_pda(program_id, &contract.seed, &contract.index_seed);
+    let (writer_mint, _bump) = get_writer_mint_of(program_id, contract, &data_pda);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*closer, true),
+        AccountMeta::new(get_associated_token_address(closer, holder_mint), false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(get_associated_token_address(closer, &writer_mint), false),
+        AccountMeta::new(writer_mint, false),
+        AccountMeta::new(wd.temp_pub, false),
+        AccountMeta::new(wd.receive_pub, false),
+        AccountMeta::new(*collateral_dest, false),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new(wd.party_pub, false),
+        AccountMeta::new(bd.party_pub, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::CloseOut { lots }.pack(),
+        accounts,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exercise_pooled(
+    program_id: &Pubkey,
+    holder: &Pubkey,
+    strike_temp: &Pubkey,
+    holder_receive: &Pubkey,
+    holder_mint: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    lots: u64,
+) -> Result<Instruction, ProgramError> {
+    let series_seed = contract_data.series_seed();
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract_type, &series_seed);
+    let (vault, _bump) = get_series_vault(program_id, contract_type, &series_seed);
+    let (proceeds, _bump) = get_series_proceeds(program_id, contract_type, &series_seed);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*holder, true),
+        AccountMeta::new(*strike_temp, false),
+        AccountMeta::new(*holder_receive, false),
+        AccountMeta::new(get_associated_token_address(holder, holder_mint), false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(proceeds, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if contract_data.settlement == SettlementType::CASH {
+        let oracle = contract_data
+            .oracle
+            .ok_or(ProgramError::InvalidAccountData)?;
+        accounts.push(AccountMeta::new_readonly(oracle, false));
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::ExercisePooled {
+            contract_type,
+            contract_data: contract_data.clone(),
+            lots,
+        }
+        .pack(),
+        accounts,
+    ))
+}
+
+pub fn redeem_pooled(
+    program_id: &Pubkey,
+    redeemer: &Pubkey,
+    collateral_dest: &Pubkey,
+    proceeds_dest: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    lots: u64,
+) -> Instruction {
+    let series_seed = contract_data.series_seed();
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract_type, &series_seed);
+    let (writer_mint, _bump) = get_writer_mint(program_id, contract_type, &series_seed);
+    let (vault, _bump) = get_series_vault(program_id, contract_type, &series_seed);
+    let (proceeds, _bump) = get_series_proceeds(program_id, contract_type, &series_seed);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*redeemer, true),
+        AccountMeta::new(get_associated_token_address(redeemer, &writer_mint), false),
+        AccountMeta::new(writer_mint, false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(proceeds, false),
+        AccountMeta::new(*collateral_dest, false),
+        AccountMeta::new(*proceeds_dest, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::RedeemPooled {
+            contract_type,
+            contract_data: contract_data.clone(),
+            lots,
+        }
+        .pack(),
+        accounts,
+    )
+}
+
+pub fn close_out_pooled(
+    program_id: &Pubkey,
+    closer: &Pubkey,
+    collateral_dest: &Pubkey,
+    holder_mint: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    lots: u64,
+) -> Instruction {
+    let series_seed = contract_data.series_seed();
+    let (mint_pda, _bump) = get_mint_pda(program_id, contract_type, &series_seed);
+    let (writer_mint, _bump) = get_writer_mint(program_id, contract_type, &series_seed);
+    let (vault, _bump) = get_series_vault(program_id, contract_type, &series_seed);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*closer, true),
+        AccountMeta::new(get_associated_token_address(closer, holder_mint), false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(get_associated_token_address(closer, &writer_mint), false),
+        AccountMeta::new(writer_mint, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*collateral_dest, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::CloseOutPooled {
+            contract_type,
+            contract_data: contract_data.clone(),
+            lots,
+        }
+        .pack(),
+        accounts,
+    )
+}
+
+// `crossed` lists the resting orders the order is expected to cross, best first, by
+// data_pda and with the offer unless it is gone from the chain
+#[allow(clippy::too_many_arguments)]
+pub fn place_order(
+    program_id: &Pubkey,
+    party: InitParty,
+    initialiser: &Pubkey,
+    source: &Pubkey,
+    token_temp: &Pubkey,
+    receive_acc: &Pubkey,
+    receive_ata: &Pubkey,
+    holder_mint: &Pubkey,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    index_seed: &[u8; 41],
+    offer_ttl: Option<i64>,
+    crossed: &[(Pubkey, Option<&ContractPDA>)],
+) -> Result<Instruction, ProgramError> {
+    let series_seed = contract_data.series_seed();
+    let (order_book, _bump) = get_order_book(
+        program_id,
+        contract_type,
+        &series_seed,
+        &contract_data.premium_type,
+    );
+    let (writer_mint, _bump) = get_writer_mint(program_id, contract_type, &series_seed);
+    let is_ask = party == InitParty::WRITER;
+    let od = OfferData::new(
+        program_id,
+        party,
+        contract_type,
+        contract_data.clone(),
+        *index_seed,
+        offer_ttl,
+        None,
+    );
+    let mut accounts = offer(
+        program_id,
+        party,
+        initialiser,
+        token_temp,
+        receive_acc,
+        receive_ata,
+        holder_mint,
+        contract_type,
+        contract_data,
+        index_seed,
+        offer_ttl,
+        None,
+    )
+    .accounts;
+    accounts.insert(1, AccountMeta::new(*source, false));
+    accounts.extend([
+        AccountMeta::new(order_book, false),
+        AccountMeta::new(writer_mint, false),
+    ]);
+    if is_ask {
+        accounts.push(AccountMeta::new(
+            get_associated_token_address(initialiser, &writer_mint),
+            false,
+        ));
+    }
+    for (data_pda, contract) in crossed {
+        accounts.push(AccountMeta::new(*data_pda, false));
+        let contract = match contract {
+            Some(contract) => contract,
+            None => continue,
+        };
+        match is_ask {
+            true => {
+                let bd = contract
+                    .buyer_data
+                    .as_ref()
+                    .ok_or(ProgramError::InvalidAccountData)?;
+                accounts.extend([
+                    AccountMeta::new(bd.temp_pub, false),
+                    AccountMeta::new(bd.party_pub, false),
+                    AccountMeta::new(bd.receive_ata, false),
+                ]);
+            }
+            false => {
+                let wd = contract
+                    .writer_data
+                    .as_ref()
+                    .ok_or(ProgramError::InvalidAccountData)?;
+                accounts.extend([
+                    AccountMeta::new(wd.receive_ata, false),
+                    AccountMeta::new(
+                        get_associated_token_address(&wd.party_pub, &writer_mint),
+                        false,
+                    ),
+                    AccountMeta::new(wd.temp_pub, false),
+                    AccountMeta::new(wd.party_pub, false),
+                ]);
+            }
+        }
+    }
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::PlaceOrder { instruction: od }.pack(),
+        accounts,
+    ))
+}
+
+pub fn cancel_order(
+    program_id: &Pubkey,
+    contract: &ContractPDA,
+) -> Result<Instruction, ProgramError> {
+    let (order_book, _bump) = get_order_book(
+        program_id,
+        contract.contract_type,
+        &contract.series_seed,
+        &contract.contract_data.premium_type,
+    );
+    let mut accounts = vec![AccountMeta::new(order_book, false)];
+    accounts.extend(cancel_offer(program_id, contract)?.accounts);
+    Ok(Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::CancelOrder.pack(),
+        accounts,
+    ))
+}
+
+// the maker approves this as delegate of the accounts its quotes are paid from
+pub fn get_quote_authority(program_id: &Pubkey, maker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[maker.as_ref(), b"quote"], program_id)
+}
+
+// exists once the quote with this nonce has been accepted
+pub fn get_quote_nonce(program_id: &Pubkey, maker: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[maker.as_ref(), b"quote", &nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+// the Ed25519 program instruction verifying a maker's signature of quote.message, to go
+// right before AcceptQuote
+pub fn quote_signature(maker: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    // one signature, its key, signature and message all inside this instruction's data
+    let mut data = vec![1, 0];
+    for offset in [
+        48,
+        u16::MAX,
+        16,
+        u16::MAX,
+        112,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(maker.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    Instruction::new_with_bytes(ed25519_program::id(), &data, vec![])
+}
+
+// asset_temp and writer_receive are replaced by the series vaults of a pooled quote
+#[allow(clippy::too_many_arguments)]
+pub fn accept_quote(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    quote: &Quote,
+    premium_source: &Pubkey,
+    buyer_receive: &Pubkey,
+    collateral_source: &Pubkey,
+    asset_temp: &Pubkey,
+    writer_receive: &Pubkey,
+    prem_receive_acc: &Pubkey,
+    holder_mint: &Pubkey,
+) -> Instruction {
+    let ct = quote.contract_type;
+    let cd = &quote.contract_data;
+    let series_seed = cd.series_seed();
+    let (mint_pda, _bump) = get_mint_pda(program_id, ct, &series_seed);
+    let data_pda = get_data_pda(program_id, &cd.get_seed(), &get_seed(&quote.index_data()));
+    let (writer_mint, _bump) = match cd.pooled {
+        true => get_writer_mint(program_id, ct, &series_seed),
+        false => get_contract_writer_mint(program_id, &data_pda),
+    };
+    let (quote_nonce, _bump) = get_quote_nonce(program_id, &quote.maker, quote.nonce);
+    let (quote_authority, _bump) = get_quote_authority(program_id, &quote.maker);
+    let (buyer, writer) = match quote.party {
+        InitParty::BUYER => (quote.maker, *taker),
+        InitParty::WRITER => (*taker, quote.maker),
+    };
+    let (asset_temp, writer_receive) = match cd.pooled {
+        true => (
+            get_series_vault(program_id, ct, &series_seed).0,
+            get_series_proceeds(program_id, ct, &series_seed).0,
+        ),
+        false => (*asset_temp, *writer_receive),
     };
 
-    Ok(InstructionType::Bid { instruction: od })
+    let mut accounts = vec![
+        AccountMeta::new(*taker, true),
+        AccountMeta::new(data_pda, false),
+        AccountMeta::new(quote_nonce, false),
+        AccountMeta::new_readonly(quote_authority, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new(mint_pda, false),
+        AccountMeta::new(*holder_mint, false),
+        AccountMeta::new(writer_mint, false),
+        AccountMeta::new_readonly(buyer, false),
+        AccountMeta::new(*premium_source, false),
+        AccountMeta::new(*buyer_receive, false),
+        AccountMeta::new(get_associated_token_address(&buyer, holder_mint), false),
+        AccountMeta::new(writer, false),
+        AccountMeta::new(*collateral_source, false),
+        AccountMeta::new(asset_temp, false),
+        AccountMeta::new(writer_receive, false),
+        AccountMeta::new(*prem_receive_acc, false),
+        AccountMeta::new(get_associated_token_address(&writer, &writer_mint), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if cd.pooled {
+        accounts.extend([
+            AccountMeta::new_readonly(cd.collateral(ct).0, false),
+            AccountMeta::new_readonly(cd.receive_types(ct).1, false),
+        ]);
+    } else {
+        accounts.push(AccountMeta::new_readonly(
+            spl_associated_token_account::id(),
+            false,
+        ));
+    }
+    Instruction::new_with_bytes(
+        *program_id,
+        &InstructionType::AcceptQuote {
+            quote: quote.clone(),
+        }
+        .pack(),
+        accounts,
+    )
 }
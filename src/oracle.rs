@@ -0,0 +1,109 @@
+use crate::error::OptionsError;
+#[cfg(feature = "test-bpf")]
+use crate::state::TestOracle;
+use crate::state::{ContractData, ContractType};
+#[cfg(feature = "test-bpf")]
+use solana_program::program_pack::Pack;
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+use std::convert::TryFrom;
+
+// oldest price, in seconds, a contract settles against
+pub const MAX_PRICE_AGE: i64 = 60;
+
+// seconds after expiry a settlement price may be published in, so whoever settles
+// cannot wait for a level of their choosing
+pub const SETTLE_WINDOW: i64 = 300;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    // strike_type base units per 10^decimals token_type base units
+    pub price: u64,
+    pub decimals: u8,
+    // unix timestamp, seconds
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    // strike_type value of qty token_type base units, rounded down
+    pub fn value_of(&self, qty: u64) -> Result<u64, ProgramError> {
+        let scale = 10u128
+            .checked_pow(self.decimals as u32)
+            .ok_or(OptionsError::InvalidOracle)?;
+        u64::try_from(qty as u128 * self.price as u128 / scale)
+            .map_err(|_| OptionsError::InvalidOracle.into())
+    }
+}
+
+// one per price account format the program can settle against, listed in ADAPTERS
+pub trait OracleAdapter {
+    fn reads(program_id: &Pubkey, oracle: &AccountInfo) -> bool;
+    fn read_price(oracle: &AccountInfo) -> Result<OraclePrice, ProgramError>;
+
+    // None when the account is not in this adapter's format
+    fn try_read(
+        program_id: &Pubkey,
+        oracle: &AccountInfo,
+    ) -> Option<Result<OraclePrice, ProgramError>> {
+        if Self::reads(program_id, oracle) {
+            Some(Self::read_price(oracle))
+        } else {
+            None
+        }
+    }
+}
+
+type PriceReader = fn(&Pubkey, &AccountInfo) -> Option<Result<OraclePrice, ProgramError>>;
+
+// the adapters load_price tries, in order. the first that reads the account prices it
+const ADAPTERS: &[PriceReader] = &[
+    #[cfg(feature = "test-bpf")]
+    TestOracle::try_read,
+];
+
+// only test builds settle against accounts this program owns
+#[cfg(feature = "test-bpf")]
+impl OracleAdapter for TestOracle {
+    fn reads(program_id: &Pubkey, oracle: &AccountInfo) -> bool {
+        oracle.owner == program_id
+    }
+
+    fn read_price(oracle: &AccountInfo) -> Result<OraclePrice, ProgramError> {
+        let data = TestOracle::unpack_from_slice(&oracle.try_borrow_data()?)
+            .map_err(|_| OptionsError::InvalidOracle)?;
+        Ok(OraclePrice {
+            price: data.price,
+            decimals: data.decimals,
+            publish_time: data.publish_time,
+        })
+    }
+}
+
+// price from whichever adapter reads the account, no older than MAX_PRICE_AGE at `now`
+pub fn load_price(
+    program_id: &Pubkey,
+    oracle: &AccountInfo,
+    now: i64,
+) -> Result<OraclePrice, ProgramError> {
+    let price = ADAPTERS
+        .iter()
+        .find_map(|read| read(program_id, oracle))
+        .ok_or(OptionsError::InvalidOracle)??;
+    if now - price.publish_time > MAX_PRICE_AGE {
+        return Err(OptionsError::StaleOraclePrice.into());
+    }
+    Ok(price)
+}
+
+// what one lot pays its holder at `price`, capped at the collateral backing it
+pub fn intrinsic_value(
+    contract_data: &ContractData,
+    contract_type: ContractType,
+    price: &OraclePrice,
+) -> Result<u64, ProgramError> {
+    let value = price.value_of(contract_data.token_qty)?;
+    let intrinsic = match contract_type {
+        ContractType::CALL => value.saturating_sub(contract_data.strike_qty),
+        ContractType::PUT => contract_data.strike_qty.saturating_sub(value),
+    };
+    Ok(intrinsic.min(contract_data.collateral(contract_type).1))
+}
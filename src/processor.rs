@@ -1,15 +1,20 @@
+use crate::error::OptionsError;
 use crate::instruction;
-use crate::state::{ContractPDA, ContractState, ContractType::*, MintPDA, PartyData};
+use crate::oracle;
+use crate::state::{
+    get_seed, ContractData, ContractPDA, ContractState, ContractStyle::*, ContractType,
+    ContractType::*, ExpiryType, MintPDA, Order, OrderBook, PartyData, SettlementType,
+};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    clock::{Clock, SLOT_MS},
-    msg,
+    clock::Clock,
+    ed25519_program, msg,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
     system_instruction, system_program,
-    sysvar::{rent, Sysvar},
+    sysvar::{instructions, rent, Sysvar},
 };
 use spl_associated_token_account::get_associated_token_address;
 use spl_token;
@@ -27,8 +32,38 @@ pub fn initialise_contract(
         party,
         contract_type,
         index_seed,
+        offer_ttl,
+        counterparty,
+        ..
     } = instruction;
 
+    check_exercise_window(&contract_data)?;
+    check_settlement(&contract_data)?;
+    let series_seed = contract_data.series_seed();
+    let lots = contract_data.lots;
+    if lots == 0 {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    // reject offers whose totals could never be escrowed or paid
+    for qty in [
+        contract_data.token_qty,
+        contract_data.strike_qty,
+        contract_data.premium_qty,
+    ] {
+        lot_qty(qty, lots)?;
+    }
+
+    // the TTL runs on the clock the contract expires by
+    let offer_expiry = match offer_ttl {
+        Some(ttl) if ttl <= 0 => return Err(OptionsError::InvalidOfferTtl.into()),
+        Some(ttl) => Some(
+            expiry_clock(&contract_data)?
+                .checked_add(ttl)
+                .ok_or(OptionsError::InvalidOfferTtl)?,
+        ),
+        None => None,
+    };
+
     let min_rent = rent::Rent::get()?.minimum_balance(ContractPDA::LEN);
 
     let accounts = &mut accounts.iter();
@@ -42,15 +77,26 @@ pub fn initialise_contract(
     let data_pda = next_account_info(accounts)?;
     let sys_program = next_account_info(accounts)?;
     let token_program = next_account_info(accounts)?;
+    let vault_accounts = match contract_data.pooled {
+        true => Some([
+            next_account_info(accounts)?,
+            next_account_info(accounts)?,
+            next_account_info(accounts)?,
+            next_account_info(accounts)?,
+        ]),
+        false => None,
+    };
+    let writer_mint = match contract_data.pooled {
+        true => None,
+        false => Some(next_account_info(accounts)?),
+    };
 
-    let token_temp_info =
-        spl_token::state::Account::unpack_from_slice(*token_temp.try_borrow_data()?)?;
-    let rec_account_info =
-        spl_token::state::Account::unpack_from_slice(*receive_acc.try_borrow_data()?)?;
-    let receive_ata_info =
-        spl_token::state::Account::unpack_from_slice(*receive_ata.try_borrow_data()?)?;
+    let token_temp_info = unpack_token_account(token_temp)?;
+    let rec_account_info = unpack_token_account(receive_acc)?;
+    let receive_ata_info = unpack_token_account(receive_ata)?;
 
-    let mint_pda_data = MintPDA::unpack_from_slice(*mint_pda.try_borrow_data()?)?;
+    check_owner(holder_mint, &spl_token::id())?;
+    let mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
 
     msg!("asserting validity...");
     if !system_program::check_id(sys_program.key) {
@@ -60,9 +106,7 @@ pub fn initialise_contract(
         return Err(ProgramError::IncorrectProgramId);
     }
     if *data_pda.key != pda {
-        msg!("INCORRECT PDA ACCOUNT");
-        msg!("expected: {:#?}", pda);
-        return Err(ProgramError::IncorrectProgramId);
+        return Err(OptionsError::IncorrectDataPda.into());
     }
     if !initialiser.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
@@ -70,67 +114,50 @@ pub fn initialise_contract(
     if !data_pda.try_data_is_empty()? {
         return Err(ProgramError::AccountAlreadyInitialized);
     }
-    let (temp_type, temp_qty, rec_type) = match party {
+    let (buyer_rec_type, writer_rec_type) = contract_data.receive_types(contract_type);
+    let ((temp_type, temp_qty), rec_type) = match party {
         instruction::InitParty::BUYER => (
-            contract_data.premium_type,
-            contract_data.premium_qty,
-            match contract_type {
-                CALL => contract_data.token_type,
-                PUT => contract_data.strike_type,
-            },
+            (contract_data.premium_type, contract_data.premium_qty),
+            buyer_rec_type,
         ),
-        instruction::InitParty::WRITER => match contract_type {
-            CALL => (
-                contract_data.token_type,
-                contract_data.token_qty,
-                contract_data.strike_type,
-            ),
-            PUT => (
-                contract_data.strike_type,
-                contract_data.strike_qty,
-                contract_data.token_type,
-            ),
-        },
+        instruction::InitParty::WRITER => {
+            (contract_data.collateral(contract_type), writer_rec_type)
+        }
     };
 
     if token_temp_info.mint != temp_type {
-        msg!("INCORRECT TEMP ACCOUNT TYPE");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectTempType.into());
     };
-    if token_temp_info.amount != temp_qty {
-        msg!("INCORRECT TEMP ACCOUNT BALANCE");
-        return Err(ProgramError::InvalidArgument);
+    if token_temp_info.amount != lot_qty(temp_qty, lots)? {
+        return Err(OptionsError::IncorrectTempBalance.into());
     }
     if rec_account_info.mint != rec_type {
-        msg!("INCORRECT RECEIVE ACCOUNT TYPE");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectReceiveType.into());
     }
     match party {
         instruction::InitParty::WRITER => {
             if receive_ata_info.mint != contract_data.premium_type {
-                msg!("INCORRECT PREMIUM RECEIVE ACCOUNT TYPE");
-                return Err(ProgramError::InvalidInstructionData);
+                return Err(OptionsError::IncorrectPremiumReceiveType.into());
             };
+            if receive_acc.key == receive_ata.key && !contract_data.pooled {
+                return Err(OptionsError::IncorrectWriterReceive.into());
+            }
         }
         instruction::InitParty::BUYER => {
             let s1 = match contract_type {
                 CALL => &[0],
                 PUT => &[1],
             };
-            let mint_seed = contract_data.get_seed();
-            let (mint_pdak, _bump) = Pubkey::find_program_address(&[s1, &mint_seed], program_id);
+            let (mint_pdak, _bump) = Pubkey::find_program_address(&[s1, &series_seed], program_id);
             if mint_pdak != *mint_pda.key {
-                msg!("INCORRECT MINT PDA ACCOUNT");
-                return Err(ProgramError::InvalidArgument);
+                return Err(OptionsError::IncorrectMintPda.into());
             }
             if *holder_mint.key != mint_pda_data.holder_mint {
-                msg!("INCORRECT HOLDER MINT ACCOUNT");
-                return Err(ProgramError::InvalidArgument);
+                return Err(OptionsError::IncorrectHolderMint.into());
             }
             let x_ata = get_associated_token_address(initialiser.key, holder_mint.key);
             if x_ata != *receive_ata.key {
-                msg!("INCORRECT HOLDER MINT ATA");
-                return Err(ProgramError::InvalidArgument);
+                return Err(OptionsError::IncorrectHolderAta.into());
             }
         }
     };
@@ -153,6 +180,14 @@ pub fn initialise_contract(
             init_party: instruction::InitParty::BUYER,
             contract_type,
             index_seed,
+            open_lots: 0,
+            unfilled_lots: lots,
+            writer_lots: 0,
+            series_seed,
+            offer_expiry,
+            counterparty,
+            offer_seq: 0,
+            settle_payout: 0,
         },
         instruction::InitParty::WRITER => ContractPDA {
             contract_data,
@@ -170,6 +205,14 @@ pub fn initialise_contract(
             init_party: instruction::InitParty::WRITER,
             contract_type,
             index_seed,
+            open_lots: 0,
+            unfilled_lots: lots,
+            writer_lots: 0,
+            series_seed,
+            offer_expiry,
+            counterparty,
+            offer_seq: 0,
+            settle_payout: 0,
         },
     };
 
@@ -206,12 +249,60 @@ pub fn initialise_contract(
             token_program.clone(),
         ],
     )?;
+
+    // a pooled writer's proceeds go to the series vault instead
+    if party == instruction::InitParty::WRITER && !pda_data.contract_data.pooled {
+        msg!("transferring receive_acc ownership to PDA...");
+        let transfer_receive = spl_token::instruction::set_authority(
+            token_program.key,
+            receive_acc.key,
+            Some(&pda),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            initialiser.key,
+            &[initialiser.key],
+        )?;
+        invoke(
+            &transfer_receive,
+            &[
+                receive_acc.clone(),
+                initialiser.clone(),
+                token_program.clone(),
+            ],
+        )?;
+    }
+    if let Some([vault, proceeds, collateral_mint, proceeds_mint]) = vault_accounts {
+        create_series_vaults(
+            program_id,
+            &pda_data,
+            initialiser,
+            [vault, proceeds],
+            [collateral_mint, proceeds_mint],
+            mint_pda,
+            sys_program,
+        )?;
+    }
+    if let Some(writer_mint) = writer_mint {
+        create_contract_writer_mint(
+            program_id,
+            &pda_data,
+            &pda,
+            initialiser,
+            writer_mint,
+            mint_pda,
+            sys_program,
+        )?;
+    }
     msg!("updating PDA data...");
-    pda_data.pack_into_slice(*data_pda.data.borrow_mut());
+    pda_data.pack_into_slice(*data_pda.data.borrow_mut())?;
     Ok(())
 }
 
-pub fn accept_bid(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+pub fn accept_bid(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lots: u64,
+    offer_seq: Option<u64>,
+) -> Result<(), ProgramError> {
     let accounts = &mut accounts.iter();
 
     let writer = next_account_info(accounts)?;
@@ -226,45 +317,62 @@ pub fn accept_bid(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
     let holder_mint = next_account_info(accounts)?;
     let sys_program = next_account_info(accounts)?;
     let token_program = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let writer_ata = next_account_info(accounts)?;
 
     if data_pda.try_data_is_empty()? {
         return Err(ProgramError::InvalidAccountData);
     }
 
-    let writer_temp_info =
-        spl_token::state::Account::unpack_from_slice(*writer_temp.try_borrow_data()?)?;
-    let writer_receive_info =
-        spl_token::state::Account::unpack_from_slice(*writer_receive.try_borrow_data()?)?;
-    let receive_ata_info =
-        spl_token::state::Account::unpack_from_slice(*writer_receive.try_borrow_data()?)?;
-    let premium_temp_info =
-        spl_token::state::Account::unpack_from_slice(*premium_temp.try_borrow_data()?)?;
-    let mut contract_pda = ContractPDA::unpack_from_slice(*data_pda.try_borrow_data()?)?;
-    let mint_pda_data = MintPDA::unpack_from_slice(*mint_pda.try_borrow_data()?)?;
+    let writer_temp_info = unpack_token_account(writer_temp)?;
+    let writer_receive_info = unpack_token_account(writer_receive)?;
+    let receive_ata_info = unpack_token_account(receive_ata)?;
+    let premium_temp_info = unpack_token_account(premium_temp)?;
+    check_owner(buyer_holder_ata, &spl_token::id())?;
+    check_owner(holder_mint, &spl_token::id())?;
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
 
-    let clock = Clock::get()?;
-    let time = (clock.slot * SLOT_MS) as i64 + (clock.unix_timestamp * 1000);
+    let time = expiry_clock(&contract_pda.contract_data)?;
+    let pooled = contract_pda.contract_data.pooled;
 
-    let bd = contract_pda.buyer_data.unwrap();
+    let bd = contract_pda
+        .buyer_data
+        .take()
+        .ok_or(OptionsError::InvalidContractState)?;
 
     let s1 = match contract_pda.contract_type {
         CALL => &[0],
         PUT => &[1],
     };
-    let mint_seed = contract_pda.contract_data.get_seed();
+    let mint_seed = contract_pda.series_seed;
     let (mint_pdak, mint_bump) = Pubkey::find_program_address(&[s1, &mint_seed], program_id);
 
     msg!("unpacked accounts, asserting validity...");
-    match contract_pda.contract_state {
-        ContractState::BID => (),
+    let first_fill = match contract_pda.contract_state {
+        ContractState::BID => true,
+        ContractState::PARTIALLY_FILLED
+            if contract_pda.init_party == instruction::InitParty::BUYER =>
+        {
+            false
+        }
         _ => {
-            msg!("INVALID CONTRACT STATE");
-            return Err(ProgramError::InvalidArgument);
+            return Err(OptionsError::InvalidContractState.into());
         }
     };
+    // the series vault, or the escrow of the first fill of a bid
+    let collateral_dest = match pooled || !first_fill {
+        true => Some(next_account_info(accounts)?),
+        false => None,
+    };
     if time > contract_pda.contract_data.expiry_date {
-        msg!("CONTRACT HAS EXPIRED");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::ContractExpired.into());
+    }
+    if contract_pda.offer_expired(time) {
+        return Err(OptionsError::OfferExpired.into());
+    }
+    if matches!(offer_seq, Some(seq) if seq != contract_pda.offer_seq) {
+        return Err(OptionsError::OfferAmended.into());
     }
     if !system_program::check_id(sys_program.key) {
         return Err(ProgramError::IncorrectProgramId);
@@ -275,69 +383,140 @@ pub fn accept_bid(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
     if !writer.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
-    let (temp_type, temp_qty, rec_type) = match contract_pda.contract_type {
-        CALL => (
-            contract_pda.contract_data.token_type,
-            contract_pda.contract_data.token_qty,
-            contract_pda.contract_data.strike_type,
-        ),
-        PUT => (
-            contract_pda.contract_data.strike_type,
-            contract_pda.contract_data.strike_qty,
-            contract_pda.contract_data.token_type,
-        ),
-    };
+    check_counterparty(&contract_pda, writer)?;
+    let (temp_type, temp_qty) = contract_pda
+        .contract_data
+        .collateral(contract_pda.contract_type);
+    let (_, rec_type) = contract_pda
+        .contract_data
+        .receive_types(contract_pda.contract_type);
     if writer_temp_info.mint != temp_type {
-        msg!("INCORRECT WRITER_TEMP TOKEN TYPE");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectTempType.into());
     }
-    if writer_temp_info.amount != temp_qty {
-        msg!("INCORRECT ASSET_TEMP BALANCE");
-        return Err(ProgramError::InvalidArgument);
+    // any writer fills the rest of a bid, adding to the escrow of the first fill and
+    // holding its share of it by writer token
+    if !first_fill && !pooled {
+        let wd = contract_pda
+            .writer_data
+            .as_ref()
+            .ok_or(OptionsError::InvalidContractState)?;
+        if collateral_dest.map(|acc| acc.key) != Some(&wd.temp_pub) {
+            return Err(OptionsError::IncorrectTempAccount.into());
+        }
+        if *writer_receive.key != wd.receive_pub {
+            return Err(OptionsError::IncorrectWriterReceive.into());
+        }
+    }
+    fill_lots(&mut contract_pda, lots)?;
+    let collateral = lot_qty(temp_qty, lots)?;
+    // the first fill escrows writer_temp itself, so it must hold exactly the collateral
+    if (collateral_dest.is_some() && writer_temp_info.amount < collateral)
+        || (collateral_dest.is_none() && writer_temp_info.amount != collateral)
+    {
+        return Err(OptionsError::IncorrectTempBalance.into());
     }
     if writer_receive_info.mint != rec_type {
-        msg!("INCORRECT RECEIVE_ACCOUNT TOKEN TYPE");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectReceiveType.into());
     }
     if *premium_temp.key != bd.temp_pub {
-        msg!("INCORRECT PREMIUM_TEMP ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectTempAccount.into());
     }
     if receive_ata_info.mint != contract_pda.contract_data.premium_type {
-        msg!("INCORRECT PREMIUM_RECEIVE ACCOUNT TOKEN TYPE");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectPremiumReceiveType.into());
     }
     if *buyer.key != bd.party_pub {
-        msg!("INCORRECT BUYER ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectBuyerAccount.into());
     }
     if *buyer_holder_ata.key != bd.receive_ata {
-        msg!("INCORRECT BUYER HOLDER ATA");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectHolderAta.into());
     }
     if *holder_mint.key != mint_pda_data.holder_mint {
-        msg!("INCORRECT HOLDER MINT ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectHolderMint.into());
     }
     if *mint_pda.key != mint_pdak {
-        msg!("INCORRECT MINT PDA ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectMintPda.into());
     }
-
-    msg!("transferring writer_temp ownership to PDA...");
-    let transfer_temp = spl_token::instruction::set_authority(
-        token_program.key,
-        writer_temp.key,
-        Some(data_pda.key),
-        spl_token::instruction::AuthorityType::AccountOwner,
+    check_writer_mint(
+        program_id,
+        &contract_pda,
+        data_pda.key,
+        writer_mint,
+        writer_ata,
         writer.key,
-        &[writer.key],
     )?;
+    if !pooled && writer_receive.key == receive_ata.key {
+        return Err(OptionsError::IncorrectWriterReceive.into());
+    }
 
-    invoke(
-        &transfer_temp,
-        &[writer_temp.clone(), writer.clone(), token_program.clone()],
-    )?;
+    if let Some(collateral_dest) = collateral_dest {
+        if pooled {
+            check_series_vault(
+                program_id,
+                contract_pda.contract_type,
+                &contract_pda.series_seed,
+                collateral_dest,
+                b"vault",
+            )?;
+        }
+
+        msg!("transferring collateral to escrow...");
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            writer_temp.key,
+            collateral_dest.key,
+            writer.key,
+            &[writer.key],
+            collateral,
+        )?;
+        invoke(
+            &ix,
+            &[
+                writer_temp.clone(),
+                collateral_dest.clone(),
+                writer.clone(),
+                token_program.clone(),
+            ],
+        )?;
+    } else {
+        msg!("transferring writer_temp ownership to PDA...");
+        let transfer_temp = spl_token::instruction::set_authority(
+            token_program.key,
+            writer_temp.key,
+            Some(data_pda.key),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            writer.key,
+            &[writer.key],
+        )?;
+
+        invoke(
+            &transfer_temp,
+            &[writer_temp.clone(), writer.clone(), token_program.clone()],
+        )?;
+
+        msg!("transferring writer_receive ownership to PDA...");
+        let transfer_receive = spl_token::instruction::set_authority(
+            token_program.key,
+            writer_receive.key,
+            Some(data_pda.key),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            writer.key,
+            &[writer.key],
+        )?;
+        invoke(
+            &transfer_receive,
+            &[
+                writer_receive.clone(),
+                writer.clone(),
+                token_program.clone(),
+            ],
+        )?;
+    }
+
+    // the last fill sweeps premium_temp so it can be closed
+    let premium = match contract_pda.unfilled_lots {
+        0 => premium_temp_info.amount,
+        _ => lot_qty(contract_pda.contract_data.premium_qty, lots)?,
+    };
 
     msg!("transferring premium to writer...");
     let transfer_prem = spl_token::instruction::transfer(
@@ -346,7 +525,7 @@ pub fn accept_bid(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
         receive_ata.key,
         data_pda.key,
         &[data_pda.key],
-        premium_temp_info.amount,
+        premium,
     )?;
 
     invoke_signed(
@@ -364,38 +543,40 @@ pub fn accept_bid(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
         ]],
     )?;
 
-    msg!("closing premium temp account...");
-    let close_prem_temp = spl_token::instruction::close_account(
-        token_program.key,
-        premium_temp.key,
-        buyer.key,
-        data_pda.key,
-        &[data_pda.key],
-    )?;
-
-    invoke_signed(
-        &close_prem_temp,
-        &[
-            premium_temp.clone(),
-            buyer.clone(),
-            data_pda.clone(),
-            token_program.clone(),
-        ],
-        &[&[
-            &contract_pda.seed,
-            &contract_pda.index_seed,
-            &[contract_pda.bump],
-        ]],
-    )?;
+    if contract_pda.unfilled_lots == 0 {
+        msg!("closing premium temp account...");
+        let close_prem_temp = spl_token::instruction::close_account(
+            token_program.key,
+            premium_temp.key,
+            buyer.key,
+            data_pda.key,
+            &[data_pda.key],
+        )?;
+
+        invoke_signed(
+            &close_prem_temp,
+            &[
+                premium_temp.clone(),
+                buyer.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[&[
+                &contract_pda.seed,
+                &contract_pda.index_seed,
+                &[contract_pda.bump],
+            ]],
+        )?;
+    }
 
-    msg!("minting holder_mint token");
+    msg!("minting holder_mint tokens");
     let ix = spl_token::instruction::mint_to(
         token_program.key,
         holder_mint.key,
         buyer_holder_ata.key,
         mint_pda.key,
         &[mint_pda.key],
-        1,
+        lots,
     )?;
     invoke_signed(
         &ix,
@@ -407,21 +588,50 @@ pub fn accept_bid(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
         &[&[s1, &mint_seed, &[mint_bump]]],
     )?;
 
+    msg!("minting writer_mint tokens");
+    let ix = spl_token::instruction::mint_to(
+        token_program.key,
+        writer_mint.key,
+        writer_ata.key,
+        mint_pda.key,
+        &[mint_pda.key],
+        lots,
+    )?;
+    invoke_signed(
+        &ix,
+        &[writer_mint.clone(), writer_ata.clone(), mint_pda.clone()],
+        &[&[s1, &mint_seed, &[mint_bump]]],
+    )?;
+    contract_pda.writer_lots += lots;
+    mint_pda_data.record_fill(lots)?;
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+
+    // nothing of a filled pooled bid is left in escrow
+    if pooled && contract_pda.unfilled_lots == 0 {
+        return close_data_pda(data_pda, buyer);
+    }
+
     msg!("updating PDA data...");
-    contract_pda.contract_state = ContractState::FINAL;
-    contract_pda.writer_data = Some(PartyData {
-        party_pub: writer.key.clone(),
-        temp_pub: writer_temp.key.clone(),
-        receive_pub: writer_receive.key.clone(),
-        receive_ata: receive_ata.key.clone(),
-    });
+    if first_fill || pooled {
+        contract_pda.writer_data = Some(PartyData {
+            party_pub: writer.key.clone(),
+            temp_pub: writer_temp.key.clone(),
+            receive_pub: writer_receive.key.clone(),
+            receive_ata: receive_ata.key.clone(),
+        });
+    }
     contract_pda.buyer_data = Some(bd);
 
-    contract_pda.pack_into_slice(*data_pda.data.borrow_mut());
+    contract_pda.pack_into_slice(*data_pda.data.borrow_mut())?;
     Ok(())
 }
 
-pub fn accept_ask(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+pub fn accept_ask(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lots: u64,
+    offer_seq: Option<u64>,
+) -> Result<(), ProgramError> {
     let accounts = &mut accounts.iter();
     let buyer = next_account_info(accounts)?;
     let premium_temp = next_account_info(accounts)?;
@@ -433,29 +643,37 @@ pub fn accept_ask(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
     let seller_prem_acc = next_account_info(accounts)?;
     let sys_program = next_account_info(accounts)?;
     let token_program = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let writer_ata = next_account_info(accounts)?;
 
-    let mut contract_pda = ContractPDA::unpack_from_slice(*data_pda.try_borrow_data()?)?;
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
 
-    let prem_temp_info =
-        spl_token::state::Account::unpack_from_slice(*premium_temp.try_borrow_data()?)?;
-    let buyer_receive_info =
-        spl_token::state::Account::unpack_from_slice(*buyer_receive.try_borrow_data()?)?;
-    let mint_pda_data = MintPDA::unpack_from_slice(*mint_pda.try_borrow_data()?)?;
+    let prem_temp_info = unpack_token_account(premium_temp)?;
+    let buyer_receive_info = unpack_token_account(buyer_receive)?;
+    check_owner(holder_ata, &spl_token::id())?;
+    check_owner(seller_prem_acc, &spl_token::id())?;
+    check_owner(holder_mint, &spl_token::id())?;
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
 
-    let clock = Clock::get()?;
-    let time = (clock.slot * SLOT_MS) as i64 + (clock.unix_timestamp * 1000);
+    let time = expiry_clock(&contract_pda.contract_data)?;
 
     msg!("asserting validity");
     match contract_pda.contract_state {
         ContractState::ASK => (),
+        ContractState::PARTIALLY_FILLED
+            if contract_pda.init_party == instruction::InitParty::WRITER => {}
         _ => {
-            msg!("INVALID CONTRACT STATE");
-            return Err(ProgramError::InvalidArgument);
+            return Err(OptionsError::InvalidContractState.into());
         }
     };
     if time > contract_pda.contract_data.expiry_date {
-        msg!("CONTRACT EXPIRED");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::ContractExpired.into());
+    }
+    if contract_pda.offer_expired(time) {
+        return Err(OptionsError::OfferExpired.into());
+    }
+    if matches!(offer_seq, Some(seq) if seq != contract_pda.offer_seq) {
+        return Err(OptionsError::OfferAmended.into());
     }
     if !system_program::check_id(sys_program.key) {
         return Err(ProgramError::IncorrectProgramId);
@@ -464,44 +682,81 @@ pub fn accept_ask(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
         return Err(ProgramError::IncorrectProgramId);
     }
     if prem_temp_info.mint != contract_pda.contract_data.premium_type {
-        msg!("INCORRECT PREMIUM TYPE");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectTempType.into());
     }
-    if prem_temp_info.amount != contract_pda.contract_data.premium_qty {
-        msg!("INCORRECT PREMIUM TEMP BALANCE");
-        return Err(ProgramError::InvalidArgument);
+    let premium = lot_qty(contract_pda.contract_data.premium_qty, lots)?;
+    // a pooled buyer may pay from any account it holds, as a pooled writer escrows from one
+    let pooled = contract_pda.contract_data.pooled;
+    if (pooled && prem_temp_info.amount < premium) || (!pooled && prem_temp_info.amount != premium)
+    {
+        return Err(OptionsError::IncorrectTempBalance.into());
     }
-    let rec_type = match contract_pda.contract_type {
-        CALL => contract_pda.contract_data.token_type,
-        PUT => contract_pda.contract_data.strike_type,
-    };
+    let (rec_type, _) = contract_pda
+        .contract_data
+        .receive_types(contract_pda.contract_type);
     if buyer_receive_info.mint != rec_type {
-        msg!("INCORRECT BUYER RECEIVE ACCOUNT TYPE");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectReceiveType.into());
     }
     if !buyer.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
+    check_counterparty(&contract_pda, buyer)?;
+    let wd = contract_pda
+        .writer_data
+        .as_ref()
+        .ok_or(OptionsError::InvalidContractState)?;
+    if *seller_prem_acc.key != wd.receive_ata {
+        return Err(OptionsError::IncorrectWriterReceive.into());
+    }
 
     let s1 = match contract_pda.contract_type {
         CALL => &[0],
         PUT => &[1],
     };
-    let mint_seed = contract_pda.contract_data.get_seed();
+    let mint_seed = contract_pda.series_seed;
     let (mint_pdak, mint_bump) = Pubkey::find_program_address(&[s1, &mint_seed], program_id);
     if mint_pdak != *mint_pda.key {
-        msg!("INCORRECT MINT PDA ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectMintPda.into());
     }
     if *holder_mint.key != mint_pda_data.holder_mint {
-        msg!("INCORRECT HOLDER MINT ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectHolderMint.into());
     }
     let x_ata = get_associated_token_address(buyer.key, holder_mint.key);
     if x_ata != *holder_ata.key {
-        msg!("INCORRECT HOLDER MINT ATA");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectHolderAta.into());
     }
+    check_writer_mint(
+        program_id,
+        &contract_pda,
+        data_pda.key,
+        writer_mint,
+        writer_ata,
+        &wd.party_pub,
+    )?;
+    let pooled_accounts = match contract_pda.contract_data.pooled {
+        true => {
+            let writer_temp = next_account_info(accounts)?;
+            let collateral_vault = next_account_info(accounts)?;
+            let writer = next_account_info(accounts)?;
+            check_owner(writer_temp, &spl_token::id())?;
+            if *writer_temp.key != wd.temp_pub {
+                return Err(OptionsError::IncorrectTempAccount.into());
+            }
+            if *writer.key != wd.party_pub {
+                return Err(OptionsError::IncorrectWriterAccount.into());
+            }
+            check_series_vault(
+                program_id,
+                contract_pda.contract_type,
+                &contract_pda.series_seed,
+                collateral_vault,
+                b"vault",
+            )?;
+            Some((writer_temp, collateral_vault, writer))
+        }
+        false => None,
+    };
+    fill_lots(&mut contract_pda, lots)?;
 
     msg!("transferring premium to writer...");
     let transfer_premium = spl_token::instruction::transfer(
@@ -510,7 +765,7 @@ pub fn accept_ask(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
         seller_prem_acc.key,
         buyer.key,
         &[buyer.key],
-        contract_pda.contract_data.premium_qty,
+        premium,
     )?;
 
     invoke(
@@ -518,14 +773,14 @@ pub fn accept_ask(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
         &[premium_temp.clone(), seller_prem_acc.clone(), buyer.clone()],
     )?;
 
-    msg!("minting holder token");
+    msg!("minting holder tokens");
     let ix = spl_token::instruction::mint_to(
         token_program.key,
         holder_mint.key,
         holder_ata.key,
         mint_pda.key,
         &[mint_pda.key],
-        1,
+        lots,
     )?;
     invoke_signed(
         &ix,
@@ -533,8 +788,80 @@ pub fn accept_ask(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
         &[&[s1, &mint_seed, &[mint_bump]]],
     )?;
 
+    msg!("minting writer tokens");
+    let ix = spl_token::instruction::mint_to(
+        token_program.key,
+        writer_mint.key,
+        writer_ata.key,
+        mint_pda.key,
+        &[mint_pda.key],
+        lots,
+    )?;
+    invoke_signed(
+        &ix,
+        &[writer_mint.clone(), writer_ata.clone(), mint_pda.clone()],
+        &[&[s1, &mint_seed, &[mint_bump]]],
+    )?;
+    contract_pda.writer_lots += lots;
+    mint_pda_data.record_fill(lots)?;
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+
+    if let Some((writer_temp, collateral_vault, writer)) = pooled_accounts {
+        let pda_seeds: &[&[u8]] = &[
+            &contract_pda.seed,
+            &contract_pda.index_seed,
+            &[contract_pda.bump],
+        ];
+        let (_, collateral_qty) = contract_pda
+            .contract_data
+            .collateral(contract_pda.contract_type);
+
+        msg!("transferring collateral to series vault...");
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            writer_temp.key,
+            collateral_vault.key,
+            data_pda.key,
+            &[data_pda.key],
+            lot_qty(collateral_qty, lots)?,
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                writer_temp.clone(),
+                collateral_vault.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[pda_seeds],
+        )?;
+
+        if contract_pda.unfilled_lots == 0 {
+            msg!("transferring asset_temp back to writer...");
+            let ix = spl_token::instruction::set_authority(
+                token_program.key,
+                writer_temp.key,
+                Some(writer.key),
+                spl_token::instruction::AuthorityType::AccountOwner,
+                data_pda.key,
+                &[data_pda.key],
+            )?;
+            invoke_signed(
+                &ix,
+                &[
+                    writer_temp.clone(),
+                    writer.clone(),
+                    data_pda.clone(),
+                    token_program.clone(),
+                ],
+                &[pda_seeds],
+            )?;
+            return close_data_pda(data_pda, writer);
+        }
+    }
+
+    // every partial fill may come from a different buyer, the last one is kept
     msg!("updating PDA data...");
-    contract_pda.contract_state = ContractState::FINAL;
     contract_pda.buyer_data = Some(PartyData {
         party_pub: buyer.key.clone(),
         temp_pub: premium_temp.key.clone(),
@@ -542,54 +869,70 @@ pub fn accept_ask(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), P
         receive_ata: holder_ata.key.clone(),
     });
 
-    contract_pda.pack_into_slice(*data_pda.data.borrow_mut());
+    contract_pda.pack_into_slice(*data_pda.data.borrow_mut())?;
 
     Ok(())
 }
 
-pub fn execute_contract(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+pub fn execute_contract(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lots: u64,
+) -> Result<(), ProgramError> {
     let accounts = &mut accounts.iter();
 
-    let buyer = next_account_info(accounts)?;
-    let buyer_temp = next_account_info(accounts)?;
-    let buyer_receive = next_account_info(accounts)?;
-    let buyer_holder_ata = next_account_info(accounts)?;
+    let holder = next_account_info(accounts)?;
+    let holder_temp = next_account_info(accounts)?;
+    let holder_receive = next_account_info(accounts)?;
+    let holder_ata = next_account_info(accounts)?;
     let mint_pda = next_account_info(accounts)?;
-    let buyer_holder_mint = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
     let writer_temp = next_account_info(accounts)?;
     let data_pda = next_account_info(accounts)?;
     let writer = next_account_info(accounts)?;
     let writer_receive = next_account_info(accounts)?;
+    let buyer = next_account_info(accounts)?;
     let sys_program = next_account_info(accounts)?;
     let token_program = next_account_info(accounts)?;
-    let clock = Clock::get()?;
-    let time = (clock.slot * SLOT_MS) as i64 + (clock.unix_timestamp * 1000);
 
-    let contract_pda = ContractPDA::unpack_from_slice(*data_pda.data.borrow())?;
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
+    if contract_pda.contract_data.pooled {
+        return Err(OptionsError::ContractPooled.into());
+    }
+    let time = expiry_clock(&contract_pda.contract_data)?;
     let ct = contract_pda.contract_type;
 
-    let wd = contract_pda.writer_data.unwrap();
-
-    let mint_pda_data = MintPDA::unpack_from_slice(*mint_pda.try_borrow_data()?)?;
-    let buyer_ata_info =
-        spl_token::state::Account::unpack_from_slice(*buyer_holder_ata.try_borrow_data()?)?;
+    let wd = contract_pda
+        .writer_data
+        .as_ref()
+        .ok_or(OptionsError::ContractNotFinalised)?;
+    let bd = contract_pda
+        .buyer_data
+        .as_ref()
+        .ok_or(OptionsError::ContractNotFinalised)?;
+
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
+    let holder_ata_info = unpack_token_account(holder_ata)?;
+    check_owner(holder_mint, &spl_token::id())?;
+    check_owner(writer_temp, &spl_token::id())?;
+    check_owner(writer_receive, &spl_token::id())?;
 
     let s1 = match contract_pda.contract_type {
         CALL => &[0],
         PUT => &[1],
     };
-    let mint_seed = contract_pda.contract_data.get_seed();
+    let mint_seed = contract_pda.series_seed;
     let (mint_pda_k, _mint_bump) = Pubkey::find_program_address(&[s1, &mint_seed], program_id);
 
-    let buyer_temp_info =
-        spl_token::state::Account::unpack_from_slice(*buyer_temp.try_borrow_data()?)?;
-    let buyer_receive_info =
-        spl_token::state::Account::unpack_from_slice(*buyer_receive.try_borrow_data()?)?;
+    let holder_temp_info = unpack_token_account(holder_temp)?;
+    let holder_receive_info = unpack_token_account(holder_receive)?;
 
     msg!("asserting validity");
     if time > contract_pda.contract_data.expiry_date {
-        msg!("CONTRACT EXPIRED");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::ContractExpired.into());
+    }
+    if !exercise_window_open(&contract_pda.contract_data, time) {
+        return Err(OptionsError::ExerciseWindowNotOpen.into());
     }
     if !system_program::check_id(sys_program.key) {
         return Err(ProgramError::IncorrectProgramId);
@@ -598,80 +941,62 @@ pub fn execute_contract(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result
         return Err(ProgramError::IncorrectProgramId);
     }
     match contract_pda.contract_state {
-        ContractState::FINAL => (),
+        ContractState::FINAL | ContractState::PARTIALLY_FILLED => (),
         _ => {
-            msg!("CONTRACT NOT FINALISED");
-            return Err(ProgramError::InvalidArgument);
+            return Err(OptionsError::ContractNotFinalised.into());
         }
     };
-    if !buyer.is_signer {
+    if !holder.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
-    let (temp_type, temp_qty, rec_type) = match ct {
-        CALL => (
-            contract_pda.contract_data.strike_type,
-            contract_pda.contract_data.strike_qty,
-            contract_pda.contract_data.token_type,
-        ),
-        PUT => (
-            contract_pda.contract_data.token_type,
-            contract_pda.contract_data.token_qty,
-            contract_pda.contract_data.strike_type,
-        ),
-    };
-    if buyer_temp_info.mint != temp_type {
-        msg!("WRONG STRIKE TYPE");
-        return Err(ProgramError::InvalidArgument);
+    if lots == 0 || lots > contract_pda.open_lots {
+        return Err(OptionsError::InvalidLotCount.into());
     }
-    if buyer_temp_info.amount < temp_qty {
-        msg!("WRONG STRIKE TEMP BALANCE");
-        return Err(ProgramError::InvalidArgument);
+    let cd = &contract_pda.contract_data;
+    let (rec_type, _) = cd.receive_types(ct);
+    let (temp_qty, refund_qty, rec_qty) =
+        exercise_amounts(program_id, cd, ct, lots, &holder_temp_info, accounts)?;
+    if *writer.key != wd.party_pub {
+        return Err(OptionsError::IncorrectWriterAccount.into());
     }
     if *writer_receive.key != wd.receive_pub {
-        msg!("WRONG WRITER RECEIVE ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectWriterReceive.into());
     }
     if *writer_temp.key != wd.temp_pub {
-        msg!("WRONG ASSET TEMP ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectTempAccount.into());
     }
-    if buyer_receive_info.mint != rec_type {
-        msg!("WRONG BUYER RECEIVE ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+    // the original buyer only matters for the rent refund, payouts follow the holder token
+    if *buyer.key != bd.party_pub {
+        return Err(OptionsError::IncorrectBuyerAccount.into());
+    }
+    if holder_receive_info.mint != rec_type {
+        return Err(OptionsError::IncorrectReceiveType.into());
     }
     if mint_pda_k != *mint_pda.key {
-        msg!("INVALID MINT PDA ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectMintPda.into());
     }
-    if mint_pda_data.holder_mint != *buyer_holder_mint.key {
-        msg!("INVALID BUYER HOLDER MINT ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+    if mint_pda_data.holder_mint != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderMint.into());
     }
-    if buyer_ata_info.mint != *buyer_holder_mint.key {
-        msg!("INVALID BUYER HOLDER ATA ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+    if holder_ata_info.mint != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderAta.into());
     }
-    if buyer_ata_info.owner != *buyer.key {
-        msg!("BUYER HOLDER ATA ACCOUNT NOT OWNED BY BUYER");
-        return Err(ProgramError::InvalidArgument);
+    if holder_ata_info.owner != *holder.key {
+        return Err(OptionsError::HolderAtaNotOwned.into());
     }
 
-    msg!("burning holder_mint token...");
+    msg!("burning holder_mint tokens...");
     let ix1 = spl_token::instruction::burn(
         token_program.key,
-        buyer_holder_ata.key,
-        buyer_holder_mint.key,
-        buyer.key,
-        &[buyer.key],
-        1,
+        holder_ata.key,
+        holder_mint.key,
+        holder.key,
+        &[holder.key],
+        lots,
     )?;
     invoke(
         &ix1,
-        &[
-            buyer_holder_ata.clone(),
-            buyer_holder_mint.clone(),
-            buyer.clone(),
-        ],
+        &[holder_ata.clone(), holder_mint.clone(), holder.clone()],
     )?;
 
     let is_call = match ct {
@@ -679,47 +1004,80 @@ pub fn execute_contract(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result
         PUT => false,
     };
 
-    msg!(
-        "transferring {} to writer...",
-        if is_call { "strike" } else { "asset" }
-    );
-    let tx1 = spl_token::instruction::transfer(
-        token_program.key,
-        buyer_temp.key,
-        writer_receive.key,
-        buyer.key,
-        &[buyer.key],
-        contract_pda.contract_data.strike_qty,
-    )?;
+    if temp_qty > 0 {
+        msg!(
+            "transferring {} to writer...",
+            if is_call { "strike" } else { "asset" }
+        );
+        let tx1 = spl_token::instruction::transfer(
+            token_program.key,
+            holder_temp.key,
+            writer_receive.key,
+            holder.key,
+            &[holder.key],
+            temp_qty,
+        )?;
+
+        invoke(
+            &tx1,
+            &[
+                holder_temp.clone(),
+                writer_receive.clone(),
+                holder.clone(),
+                token_program.clone(),
+            ],
+        )?;
+    }
 
-    invoke(
-        &tx1,
-        &[
-            buyer_temp.clone(),
-            writer_receive.clone(),
-            buyer.clone(),
-            token_program.clone(),
-        ],
-    )?;
+    if refund_qty > 0 {
+        msg!("returning collateral not paid out to writer...");
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            writer_temp.key,
+            writer_receive.key,
+            data_pda.key,
+            &[data_pda.key],
+            refund_qty,
+        )?;
+
+        invoke_signed(
+            &ix,
+            &[
+                writer_temp.clone(),
+                writer_receive.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[&[
+                &contract_pda.seed,
+                &contract_pda.index_seed,
+                &[contract_pda.bump],
+            ]],
+        )?;
+    }
 
     msg!(
-        "transferring {} to buyer...",
-        if is_call { "asset" } else { "strike" }
+        "transferring {} to holder...",
+        match (cd.settlement, is_call) {
+            (SettlementType::CASH, _) => "payout",
+            (SettlementType::PHYSICAL, true) => "asset",
+            (SettlementType::PHYSICAL, false) => "strike",
+        }
     );
     let tx2 = spl_token::instruction::transfer(
         token_program.key,
         writer_temp.key,
-        buyer_receive.key,
+        holder_receive.key,
         data_pda.key,
         &[data_pda.key],
-        contract_pda.contract_data.token_qty,
+        rec_qty,
     )?;
 
     invoke_signed(
         &tx2,
         &[
             writer_temp.clone(),
-            buyer_receive.clone(),
+            holder_receive.clone(),
             data_pda.clone(),
             token_program.clone(),
         ],
@@ -730,6 +1088,25 @@ pub fn execute_contract(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result
         ]],
     )?;
 
+    contract_pda.open_lots -= lots;
+    mint_pda_data.record_exercise(lots)?;
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+    if contract_pda.open_lots > 0 || contract_pda.unfilled_lots > 0 {
+        msg!(
+            "{} lots open, {} unfilled, updating PDA data...",
+            contract_pda.open_lots,
+            contract_pda.unfilled_lots
+        );
+        contract_pda.pack_into_slice(*data_pda.data.borrow_mut())?;
+        return Ok(());
+    }
+    if contract_pda.writer_lots > 0 {
+        msg!("all lots exercised, writer tokens left to redeem...");
+        contract_pda.contract_state = ContractState::SETTLED;
+        contract_pda.pack_into_slice(*data_pda.data.borrow_mut())?;
+        return Ok(());
+    }
+
     msg!(
         "closing {} account...",
         if is_call { "asset_temp" } else { "strike_temp" }
@@ -762,17 +1139,40 @@ pub fn execute_contract(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result
         instruction::InitParty::WRITER => writer,
     };
 
-    msg!("zeroing PDA account data...");
-    *data_pda.data.borrow_mut() = &mut [];
-    msg!("transferring rent from PDA to initialiser...");
-    **send_to.try_borrow_mut_lamports()? += data_pda.try_lamports()?;
-    **data_pda.try_borrow_mut_lamports()? = 0;
-    msg!("PDA account closed");
+    close_data_pda(data_pda, send_to)
+}
 
-    Ok(())
+pub fn expire_contract(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    close_expired(program_id, accounts, instruction::InstructionType::Expire)
+}
+
+// permissionless: anyone may return the collateral to the stored writer once expired
+pub fn crank_expire(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    close_expired(
+        program_id,
+        accounts,
+        instruction::InstructionType::CrankExpire,
+    )
 }
 
-pub fn expire_contract(accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+// permissionless: the only way an auto-exercised contract expires, so keepers can settle
+// every expired contract without the holder calling Execute
+pub fn settle_at_expiry(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    close_expired(
+        program_id,
+        accounts,
+        instruction::InstructionType::SettleAtExpiry,
+    )
+}
+
+fn close_expired(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mode: instruction::InstructionType,
+) -> Result<(), ProgramError> {
+    let writer_signed = mode == instruction::InstructionType::Expire;
+    let settle = mode == instruction::InstructionType::SettleAtExpiry;
+
     let accounts = &mut accounts.iter();
     let writer = next_account_info(accounts)?;
     let writer_temp = next_account_info(accounts)?;
@@ -780,20 +1180,29 @@ pub fn expire_contract(accounts: &[AccountInfo]) -> Result<(), ProgramError> {
     let buyer = next_account_info(accounts)?;
     let sys_program = next_account_info(accounts)?;
     let token_program = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let oracle_acc = match settle {
+        true => Some(next_account_info(accounts)?),
+        false => None,
+    };
 
-    let contract_pda = ContractPDA::unpack_from_slice(*data_pda.data.borrow())?;
-    let clock = Clock::get()?;
-    let time = (clock.slot * SLOT_MS) as i64 + (clock.unix_timestamp * 1000);
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
+    check_data_pda(program_id, &contract_pda, data_pda)?;
+    if contract_pda.contract_data.pooled {
+        return Err(OptionsError::ContractPooled.into());
+    }
+    check_owner(writer_temp, &spl_token::id())?;
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
+    let time = expiry_clock(&contract_pda.contract_data)?;
 
     msg!("asserting validity...");
     if time < contract_pda.contract_data.expiry_date {
         msg!(
-            "timestamp: {}    expiry: {}",
+            "time: {}    expiry: {}",
             time,
             contract_pda.contract_data.expiry_date
         );
-        msg!("CONTRACT NOT EXPIRED");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::ContractNotExpired.into());
     }
     if !system_program::check_id(sys_program.key) {
         return Err(ProgramError::IncorrectProgramId);
@@ -802,26 +1211,178 @@ pub fn expire_contract(accounts: &[AccountInfo]) -> Result<(), ProgramError> {
         return Err(ProgramError::IncorrectProgramId);
     }
     match contract_pda.contract_state {
-        ContractState::FINAL => (),
+        ContractState::FINAL | ContractState::PARTIALLY_FILLED => (),
         _ => {
-            msg!("CONTRACT NOT FINALISED");
-            return Err(ProgramError::InvalidArgument);
+            return Err(OptionsError::ContractNotFinalised.into());
         }
     };
-    let wd = contract_pda.writer_data.unwrap();
-    let bd = contract_pda.buyer_data.unwrap();
+    let wd = contract_pda
+        .writer_data
+        .as_ref()
+        .ok_or(OptionsError::ContractNotFinalised)?;
+    let bd = contract_pda
+        .buyer_data
+        .as_ref()
+        .ok_or(OptionsError::ContractNotFinalised)?;
 
     if *writer.key != wd.party_pub {
-        msg!("INCORRECT WRITER ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectWriterAccount.into());
     };
+    if writer_signed && !writer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
     if *writer_temp.key != wd.temp_pub {
-        msg!("INCORRECT ASSET TEMP ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectTempAccount.into());
     }
     if *buyer.key != bd.party_pub {
-        msg!("INCORRECT BUYER ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectBuyerAccount.into());
+    }
+    let (mint_pdak, _mint_bump) = instruction::get_mint_pda(
+        program_id,
+        contract_pda.contract_type,
+        &contract_pda.series_seed,
+    );
+    if mint_pdak != *mint_pda.key {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    // expiring an auto-exercised contract any other way would hand the holder's
+    // payout to the writer
+    if contract_pda.contract_data.auto_exercise != settle {
+        return Err(OptionsError::InvalidSettlement.into());
+    }
+
+    let mut payout = 0;
+    if let Some(oracle_acc) = oracle_acc {
+        let cd = &contract_pda.contract_data;
+        if cd.oracle != Some(*oracle_acc.key) {
+            return Err(OptionsError::InvalidOracle.into());
+        }
+        if cd.expiry_type != ExpiryType::TIMESTAMP {
+            return Err(OptionsError::InvalidSettlement.into());
+        }
+        let window_end = cd.expiry_date.saturating_add(oracle::SETTLE_WINDOW * 1000);
+        // once no price from the window can be loaded any more, the lots expire unexercised
+        if time > window_end.saturating_add(oracle::MAX_PRICE_AGE * 1000) {
+            msg!("settlement window closed, expiring lots...");
+        } else {
+            let price = oracle::load_price(program_id, oracle_acc, Clock::get()?.unix_timestamp)?;
+            // a price from before expiry would settle at a level the contract never reached
+            let published = price.publish_time.saturating_mul(1000);
+            if published < cd.expiry_date || published > window_end {
+                return Err(OptionsError::StaleOraclePrice.into());
+            }
+            msg!("oracle price: {} / 10^{}", price.price, price.decimals);
+            payout = oracle::intrinsic_value(cd, contract_pda.contract_type, &price)?;
+        }
+    }
+    // the holder tokens of the open lots may have changed hands since, so their
+    // payout waits in writer_temp for whoever burns them; contracts from before the
+    // writer mint cannot be auto-exercised, so writer tokens always redeem the rest
+    let exercised = payout > 0 && contract_pda.open_lots > 0;
+    if exercised && contract_pda.writer_lots == 0 {
+        return Err(OptionsError::InvalidSettlement.into());
+    }
+
+    match exercised {
+        true => mint_pda_data.record_exercise(contract_pda.open_lots)?,
+        false => mint_pda_data.record_expiry(contract_pda.open_lots)?,
+    };
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+
+    // the premium of a bid's unfilled lots is still escrowed
+    if contract_pda.contract_state == ContractState::PARTIALLY_FILLED
+        && contract_pda.init_party == instruction::InitParty::BUYER
+    {
+        let premium_temp = next_account_info(accounts)?;
+        check_owner(premium_temp, &spl_token::id())?;
+        if *premium_temp.key != bd.temp_pub {
+            return Err(OptionsError::IncorrectTempAccount.into());
+        }
+
+        msg!("transferring premium_temp back to buyer...");
+        let ix = spl_token::instruction::set_authority(
+            token_program.key,
+            premium_temp.key,
+            Some(buyer.key),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            data_pda.key,
+            &[data_pda.key],
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                premium_temp.clone(),
+                buyer.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[&[
+                &contract_pda.seed,
+                &contract_pda.index_seed,
+                &[contract_pda.bump],
+            ]],
+        )?;
+    }
+
+    // the collateral belongs to the writer token holders
+    if contract_pda.writer_lots > 0 {
+        // except that of an ask's unfilled lots, which never backed a writer token
+        if contract_pda.contract_state == ContractState::PARTIALLY_FILLED
+            && contract_pda.init_party == instruction::InitParty::WRITER
+        {
+            let refund_acc = next_account_info(accounts)?;
+            check_owner(refund_acc, &spl_token::id())?;
+            let (collateral_type, collateral_qty) = contract_pda
+                .contract_data
+                .collateral(contract_pda.contract_type);
+            if unpack_token_account(refund_acc)?.mint != collateral_type {
+                return Err(OptionsError::IncorrectReceiveType.into());
+            }
+            if !writer_signed
+                && *refund_acc.key != get_associated_token_address(writer.key, &collateral_type)
+            {
+                return Err(OptionsError::IncorrectWriterReceive.into());
+            }
+
+            msg!("refunding unfilled collateral to writer...");
+            let ix = spl_token::instruction::transfer(
+                token_program.key,
+                writer_temp.key,
+                refund_acc.key,
+                data_pda.key,
+                &[data_pda.key],
+                lot_qty(collateral_qty, contract_pda.unfilled_lots)?,
+            )?;
+            invoke_signed(
+                &ix,
+                &[
+                    writer_temp.clone(),
+                    refund_acc.clone(),
+                    data_pda.clone(),
+                    token_program.clone(),
+                ],
+                &[&[
+                    &contract_pda.seed,
+                    &contract_pda.index_seed,
+                    &[contract_pda.bump],
+                ]],
+            )?;
+        }
+
+        if exercised {
+            msg!(
+                "setting {} aside for {} holder lots to claim...",
+                lot_qty(payout, contract_pda.open_lots)?,
+                contract_pda.open_lots
+            );
+            contract_pda.settle_payout = payout;
+        } else {
+            msg!("lots expired, writer tokens left to redeem...");
+            contract_pda.open_lots = 0;
+        }
+        contract_pda.unfilled_lots = 0;
+        contract_pda.contract_state = ContractState::SETTLED;
+        return contract_pda.pack_into_slice(&mut data_pda.try_borrow_mut_data()?);
     }
 
     msg!("transferring writer_temp back to writer...");
@@ -830,8 +1391,8 @@ pub fn expire_contract(accounts: &[AccountInfo]) -> Result<(), ProgramError> {
         writer_temp.key,
         Some(writer.key),
         spl_token::instruction::AuthorityType::AccountOwner,
-        &data_pda.key,
-        &[&data_pda.key],
+        data_pda.key,
+        &[data_pda.key],
     )?;
     invoke_signed(
         &ix,
@@ -853,24 +1414,42 @@ pub fn expire_contract(accounts: &[AccountInfo]) -> Result<(), ProgramError> {
         instruction::InitParty::WRITER => writer,
     };
 
-    msg!("zeroing PDA account data...");
-    *data_pda.data.borrow_mut() = &mut [];
-    msg!("transferring rent from PDA to initialiser...");
-    **send_to.try_borrow_mut_lamports()? += data_pda.try_lamports()?;
-    **data_pda.try_borrow_mut_lamports()? = 0;
-    msg!("PDA account closed");
-    Ok(())
+    close_data_pda(data_pda, send_to)
 }
 
-pub fn cancel_offer(accounts: &[AccountInfo]) -> Result<(), ProgramError> {
-    let accounts = &mut accounts.iter();
-    let initialiser = next_account_info(accounts)?;
+pub fn cancel_offer(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    close_offer(
+        program_id,
+        accounts,
+        instruction::InstructionType::CancelOffer,
+    )
+}
+
+// permissionless: anyone may return a stale offer's escrow and rent to its initialiser
+pub fn crank_cancel(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    close_offer(
+        program_id,
+        accounts,
+        instruction::InstructionType::CrankCancel,
+    )
+}
+
+fn close_offer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mode: instruction::InstructionType,
+) -> Result<(), ProgramError> {
+    let initialiser_signed = mode == instruction::InstructionType::CancelOffer;
+
+    let accounts = &mut accounts.iter();
+    let initialiser = next_account_info(accounts)?;
     let token_temp = next_account_info(accounts)?;
     let data_pda = next_account_info(accounts)?;
     let sys_program = next_account_info(accounts)?;
     let token_program = next_account_info(accounts)?;
 
-    let contract_pda = ContractPDA::unpack_from_slice(*data_pda.data.borrow())?;
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
+    check_owner(token_temp, &spl_token::id())?;
 
     msg!("asserting validity...");
     if !system_program::check_id(sys_program.key) {
@@ -879,64 +1458,295 @@ pub fn cancel_offer(accounts: &[AccountInfo]) -> Result<(), ProgramError> {
     if !spl_token::check_id(token_program.key) {
         return Err(ProgramError::IncorrectProgramId);
     }
-    if !initialiser.is_signer {
+    if initialiser_signed && !initialiser.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
 
     let pd = match contract_pda.contract_state {
-        ContractState::ASK => contract_pda.writer_data.unwrap(),
-        ContractState::BID => contract_pda.buyer_data.unwrap(),
+        ContractState::ASK => contract_pda.writer_data.as_ref(),
+        ContractState::BID => contract_pda.buyer_data.as_ref(),
+        ContractState::PARTIALLY_FILLED => match contract_pda.init_party {
+            instruction::InitParty::BUYER => contract_pda.buyer_data.as_ref(),
+            instruction::InitParty::WRITER => contract_pda.writer_data.as_ref(),
+        },
         _ => {
-            msg!("INVALID CONTRACT STATE");
-            return Err(ProgramError::InvalidArgument);
+            return Err(OptionsError::InvalidContractState.into());
         }
-    };
+    }
+    .ok_or(OptionsError::InvalidContractState)?;
     if *initialiser.key != pd.party_pub {
-        msg!("INCORRECT INITIALISER ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectInitialiserAccount.into());
     }
     if *token_temp.key != pd.temp_pub {
-        msg!("INCORRECT ASSET_TEMP ACCOUNT");
-        return Err(ProgramError::InvalidArgument);
+        return Err(OptionsError::IncorrectTempAccount.into());
     }
+    // an offer is stale once its TTL has run out, or the option has expired
+    let time = expiry_clock(&contract_pda.contract_data)?;
+    if !initialiser_signed
+        && !contract_pda.offer_expired(time)
+        && time <= contract_pda.contract_data.expiry_date
+    {
+        return Err(OptionsError::OfferNotExpired.into());
+    }
+    // the collateral of a pooled contract's filled lots is already in the series vault
+    let pooled = contract_pda.contract_data.pooled;
+    let partially_filled =
+        contract_pda.contract_state == ContractState::PARTIALLY_FILLED && !pooled;
+
+    // the writer's temp still backs the filled lots, so only the unfilled collateral leaves it
+    if partially_filled && contract_pda.init_party == instruction::InitParty::WRITER {
+        let refund_acc = next_account_info(accounts)?;
+        check_owner(refund_acc, &spl_token::id())?;
+        let (collateral_type, collateral_qty) = contract_pda
+            .contract_data
+            .collateral(contract_pda.contract_type);
+        if unpack_token_account(refund_acc)?.mint != collateral_type {
+            return Err(OptionsError::IncorrectReceiveType.into());
+        }
+        if !initialiser_signed
+            && *refund_acc.key != get_associated_token_address(initialiser.key, &collateral_type)
+        {
+            return Err(OptionsError::IncorrectWriterReceive.into());
+        }
 
-    msg!("transferring temp back to initialiser...");
-    let ix = spl_token::instruction::set_authority(
-        token_program.key,
-        token_temp.key,
-        Some(initialiser.key),
-        spl_token::instruction::AuthorityType::AccountOwner,
-        &data_pda.key,
-        &[&data_pda.key],
-    )?;
-    invoke_signed(
-        &ix,
-        &[
-            token_temp.clone(),
-            initialiser.clone(),
-            data_pda.clone(),
-            token_program.clone(),
-        ],
-        &[&[
-            &contract_pda.seed,
-            &contract_pda.index_seed,
-            &[contract_pda.bump],
-        ]],
-    )?;
+        msg!("refunding unfilled collateral to writer...");
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            token_temp.key,
+            refund_acc.key,
+            data_pda.key,
+            &[data_pda.key],
+            lot_qty(collateral_qty, contract_pda.unfilled_lots)?,
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                token_temp.clone(),
+                refund_acc.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[&[
+                &contract_pda.seed,
+                &contract_pda.index_seed,
+                &[contract_pda.bump],
+            ]],
+        )?;
+    } else {
+        if contract_pda.contract_state == ContractState::ASK && !pooled {
+            let receive_acc = next_account_info(accounts)?;
+            if *receive_acc.key != pd.receive_pub {
+                return Err(OptionsError::IncorrectWriterReceive.into());
+            }
 
-    msg!("zeroing PDA account data...");
-    *data_pda.data.borrow_mut() = &mut [];
-    msg!("transferring rent from PDA to initialiser...");
-    **initialiser.try_borrow_mut_lamports()? += data_pda.try_lamports()?;
-    **data_pda.try_borrow_mut_lamports()? = 0;
-    msg!("PDA account closed");
-    Ok(())
+            msg!("transferring receive_acc back to initialiser...");
+            let ix = spl_token::instruction::set_authority(
+                token_program.key,
+                receive_acc.key,
+                Some(initialiser.key),
+                spl_token::instruction::AuthorityType::AccountOwner,
+                data_pda.key,
+                &[data_pda.key],
+            )?;
+            invoke_signed(
+                &ix,
+                &[
+                    receive_acc.clone(),
+                    initialiser.clone(),
+                    data_pda.clone(),
+                    token_program.clone(),
+                ],
+                &[&[
+                    &contract_pda.seed,
+                    &contract_pda.index_seed,
+                    &[contract_pda.bump],
+                ]],
+            )?;
+        }
+
+        msg!("transferring temp back to initialiser...");
+        let ix = spl_token::instruction::set_authority(
+            token_program.key,
+            token_temp.key,
+            Some(initialiser.key),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            data_pda.key,
+            &[data_pda.key],
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                token_temp.clone(),
+                initialiser.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[&[
+                &contract_pda.seed,
+                &contract_pda.index_seed,
+                &[contract_pda.bump],
+            ]],
+        )?;
+    }
+
+    if partially_filled {
+        msg!(
+            "cancelled {} unfilled lots, updating PDA data...",
+            contract_pda.unfilled_lots
+        );
+        contract_pda.unfilled_lots = 0;
+        contract_pda.contract_state = ContractState::FINAL;
+        return contract_pda.pack_into_slice(&mut data_pda.try_borrow_mut_data()?);
+    }
+
+    close_data_pda(data_pda, initialiser)
+}
+
+pub fn amend_offer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    premium_qty: u64,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+    let initialiser = next_account_info(accounts)?;
+    let data_pda = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
+
+    msg!("asserting validity...");
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !initialiser.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // once a lot is filled its premium is settled, so only untouched offers are repriced
+    let pd = match contract_pda.contract_state {
+        ContractState::ASK => contract_pda.writer_data.as_ref(),
+        ContractState::BID => contract_pda.buyer_data.as_ref(),
+        _ => {
+            return Err(OptionsError::InvalidContractState.into());
+        }
+    }
+    .ok_or(OptionsError::InvalidContractState)?;
+    if *initialiser.key != pd.party_pub {
+        return Err(OptionsError::IncorrectInitialiserAccount.into());
+    }
+    let time = expiry_clock(&contract_pda.contract_data)?;
+    if time > contract_pda.contract_data.expiry_date {
+        return Err(OptionsError::ContractExpired.into());
+    }
+    if contract_pda.offer_expired(time) {
+        return Err(OptionsError::OfferExpired.into());
+    }
+    let premium = lot_qty(premium_qty, contract_pda.unfilled_lots)?;
+
+    // the bid's escrow must hold exactly the new premium, as when it was offered
+    if contract_pda.contract_state == ContractState::BID {
+        let token_temp = next_account_info(accounts)?;
+        let premium_acc = next_account_info(accounts)?;
+        if *token_temp.key != pd.temp_pub {
+            return Err(OptionsError::IncorrectTempAccount.into());
+        }
+        let escrowed = unpack_token_account(token_temp)?.amount;
+        if unpack_token_account(premium_acc)?.mint != contract_pda.contract_data.premium_type {
+            return Err(OptionsError::IncorrectPremiumReceiveType.into());
+        }
+
+        if premium > escrowed {
+            msg!("topping up premium_temp...");
+            let ix = spl_token::instruction::transfer(
+                token_program.key,
+                premium_acc.key,
+                token_temp.key,
+                initialiser.key,
+                &[initialiser.key],
+                premium - escrowed,
+            )?;
+            invoke(
+                &ix,
+                &[
+                    premium_acc.clone(),
+                    token_temp.clone(),
+                    initialiser.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        } else if premium < escrowed {
+            msg!("refunding premium_temp surplus...");
+            let ix = spl_token::instruction::transfer(
+                token_program.key,
+                token_temp.key,
+                premium_acc.key,
+                data_pda.key,
+                &[data_pda.key],
+                escrowed - premium,
+            )?;
+            invoke_signed(
+                &ix,
+                &[
+                    token_temp.clone(),
+                    premium_acc.clone(),
+                    data_pda.clone(),
+                    token_program.clone(),
+                ],
+                &[&[
+                    &contract_pda.seed,
+                    &contract_pda.index_seed,
+                    &[contract_pda.bump],
+                ]],
+            )?;
+        }
+    }
+
+    // a booked order moves to the back of its new price level
+    if contract_pda.contract_data.pooled {
+        let order_book = next_account_info(accounts)?;
+        let (bookk, _bump) = instruction::get_order_book(
+            program_id,
+            contract_pda.contract_type,
+            &contract_pda.series_seed,
+            &contract_pda.contract_data.premium_type,
+        );
+        if bookk != *order_book.key {
+            return Err(OptionsError::IncorrectOrderBook.into());
+        }
+        if !order_book.data_is_empty() {
+            check_owner(order_book, program_id)?;
+            let mut book = OrderBook::unpack_from_slice(*order_book.try_borrow_data()?)?;
+            if let Some(order) = book.remove(data_pda.key) {
+                msg!("repricing booked order...");
+                book.insert(
+                    &contract_pda.init_party,
+                    Order {
+                        premium_qty,
+                        ..order
+                    },
+                )?;
+                book.pack_into_slice(*order_book.try_borrow_mut_data()?);
+            }
+        }
+    }
+
+    msg!(
+        "amending premium from {} to {}...",
+        contract_pda.contract_data.premium_qty,
+        premium_qty
+    );
+    contract_pda.contract_data.premium_qty = premium_qty;
+    contract_pda.offer_seq = contract_pda
+        .offer_seq
+        .checked_add(1)
+        .ok_or(OptionsError::Overflow)?;
+    contract_pda.pack_into_slice(*data_pda.data.borrow_mut())
 }
 
 pub fn create_mint(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    seeds: (u8, [u8; 32]),
+    contract_type: ContractType,
+    contract_data: ContractData,
 ) -> Result<(), ProgramError> {
     let accounts = &mut accounts.iter();
 
@@ -946,6 +1756,7 @@ pub fn create_mint(
     let sys_program = next_account_info(accounts)?;
     let token_program = next_account_info(accounts)?;
     let rent_program = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
 
     msg!("asserting validity...");
     if !system_program::check_id(sys_program.key) {
@@ -960,12 +1771,22 @@ pub fn create_mint(
     if !holder_mint.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
+    if !rent::check_id(rent_program.key) {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    let pda_data = MintPDA {
-        holder_mint: holder_mint.key.clone(),
-    };
+    let pda_data = MintPDA::new(
+        *holder_mint.key,
+        contract_type,
+        &contract_data,
+        Clock::get()?.unix_timestamp,
+    );
 
-    let (_pda, bump) = Pubkey::find_program_address(&[&[seeds.0], &seeds.1], program_id);
+    let seeds = (contract_type as u8, contract_data.series_seed());
+    let (pda, bump) = Pubkey::find_program_address(&[&[seeds.0], &seeds.1], program_id);
+    if pda != *mint_pda.key {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
     let min_rent = rent::Rent::get()?.minimum_balance(MintPDA::LEN);
 
     msg!("creating mint PDA");
@@ -1004,5 +1825,2262 @@ pub fn create_mint(
         0,
     )?;
     invoke(&ix, &[holder_mint.clone(), rent_program.clone()])?;
+
+    // other contracts mint their own writer tokens
+    if !contract_data.pooled {
+        return Ok(());
+    }
+    create_writer_mint(
+        program_id,
+        sender,
+        writer_mint,
+        mint_pda,
+        sys_program,
+        &[&[seeds.0], &seeds.1, b"writer"],
+    )
+}
+
+pub fn migrate_contract(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let payer = next_account_info(accounts)?;
+    let data_pda = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let sys_program = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
+
+    msg!("asserting validity...");
+    if !system_program::check_id(sys_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_owner(data_pda, program_id)?;
+    check_owner(mint_pda, program_id)?;
+
+    let legacy_contract = data_pda.data_len() == ContractPDA::LEGACY_LEN;
+    let mut contract_pda = if legacy_contract {
+        ContractPDA::unpack_legacy(*data_pda.try_borrow_data()?)?
+    } else {
+        ContractPDA::unpack_from_slice(*data_pda.try_borrow_data()?)?
+    };
+
+    // legacy accounts carry no discriminator, so the address is the only proof of origin
+    check_data_pda(program_id, &contract_pda, data_pda)?;
+    let ct = contract_pda.contract_type;
+    let series_seed = contract_pda.contract_data.series_seed();
+    let merged = contract_pda.series_seed != series_seed
+        && *mint_pda.key == instruction::get_mint_pda(program_id, ct, &series_seed).0;
+    if merged {
+        let legacy_mint_pda = next_account_info(accounts)?;
+        let (legacy_mint_pdak, _bump) =
+            instruction::get_mint_pda(program_id, ct, &contract_pda.series_seed);
+        // MigrateSeries closes the legacy series account once its holder mint has moved
+        if *legacy_mint_pda.key != legacy_mint_pdak || legacy_mint_pda.owner == program_id {
+            return Err(OptionsError::IncorrectMintPda.into());
+        }
+        msg!("moving contract to its merged series...");
+        contract_pda.series_seed = series_seed;
+    }
+    let (mint_pdak, _mint_bump) =
+        instruction::get_mint_pda(program_id, ct, &contract_pda.series_seed);
+    if mint_pdak != *mint_pda.key {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+
+    if legacy_contract {
+        msg!("migrating contract PDA...");
+        resize_account(payer, data_pda, sys_program, ContractPDA::LEN)?;
+    }
+    if legacy_contract || merged {
+        contract_pda.pack_into_slice(*data_pda.try_borrow_mut_data()?)?;
+    }
+
+    if mint_pda.data_len() == MintPDA::LEGACY_LEN {
+        msg!("migrating mint PDA...");
+        let holder_mintk = MintPDA::unpack_legacy(*mint_pda.try_borrow_data()?)?;
+        if holder_mintk != *holder_mint.key {
+            return Err(OptionsError::IncorrectHolderMint.into());
+        }
+        check_owner(holder_mint, &spl_token::id())?;
+        let mut mint_pda_data = MintPDA::new(
+            holder_mintk,
+            contract_pda.contract_type,
+            &contract_pda.contract_data,
+            Clock::get()?.unix_timestamp,
+        );
+        // a series keyed by its premium type keeps it, so its terms still derive its address
+        if contract_pda.series_seed != contract_pda.contract_data.series_seed() {
+            mint_pda_data.contract_data.premium_type = contract_pda.contract_data.premium_type;
+        }
+        // every outstanding holder token of a legacy series is an open lot
+        mint_pda_data.open_interest =
+            spl_token::state::Mint::unpack(&holder_mint.try_borrow_data()?)?.supply;
+        resize_account(payer, mint_pda, sys_program, MintPDA::LEN)?;
+        mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+    } else {
+        MintPDA::unpack_from_slice(*mint_pda.try_borrow_data()?)?;
+    }
+
+    if writer_mint.try_data_is_empty()? {
+        msg!("contract predates writer tokens");
+        let s1 = [ct as u8];
+        let seeds: &[&[u8]] = match contract_pda.contract_data.pooled {
+            true => &[&s1, &contract_pda.series_seed, b"writer"],
+            false => &[data_pda.key.as_ref(), b"writer"],
+        };
+        create_writer_mint(program_id, payer, writer_mint, mint_pda, sys_program, seeds)?;
+    }
     Ok(())
 }
+
+pub fn migrate_series(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_type: ContractType,
+    contract_data: ContractData,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let payer = next_account_info(accounts)?;
+    let legacy_mint_pda = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
+    let sys_program = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    msg!("asserting validity...");
+    if !system_program::check_id(sys_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_owner(legacy_mint_pda, program_id)?;
+
+    let ct = contract_type;
+    let legacy_seed = contract_data.get_seed();
+    let series_seed = contract_data.series_seed();
+    let (legacy_mint_pdak, legacy_bump) = instruction::get_mint_pda(program_id, ct, &legacy_seed);
+    let (mint_pdak, bump) = instruction::get_mint_pda(program_id, ct, &series_seed);
+    if *legacy_mint_pda.key != legacy_mint_pdak || *mint_pda.key != mint_pdak {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    // two holder mints cannot be merged, so the series must not have one of its own yet
+    if !mint_pda.try_data_is_empty()? {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let legacy = match legacy_mint_pda.data_len() == MintPDA::LEGACY_LEN {
+        true => None,
+        false => Some(MintPDA::unpack_from_slice(
+            *legacy_mint_pda.try_borrow_data()?,
+        )?),
+    };
+    let holder_mintk = match &legacy {
+        Some(legacy) => legacy.holder_mint,
+        None => MintPDA::unpack_legacy(*legacy_mint_pda.try_borrow_data()?)?,
+    };
+    if holder_mintk != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderMint.into());
+    }
+    check_owner(holder_mint, &spl_token::id())?;
+    let holder_mint_info = spl_token::state::Mint::unpack(&holder_mint.try_borrow_data()?)?;
+
+    // the series account drops the premium type from its terms, like any series since
+    let mut mint_pda_data = MintPDA::new(
+        holder_mintk,
+        ct,
+        &contract_data,
+        Clock::get()?.unix_timestamp,
+    );
+    match legacy {
+        Some(legacy) => {
+            mint_pda_data.created_at = legacy.created_at;
+            mint_pda_data.open_interest = legacy.open_interest;
+            mint_pda_data.total_exercised = legacy.total_exercised;
+            mint_pda_data.total_expired = legacy.total_expired;
+        }
+        // every outstanding holder token of a legacy series is an open lot
+        None => mint_pda_data.open_interest = holder_mint_info.supply,
+    }
+
+    msg!("creating series account...");
+    let s1 = [ct as u8];
+    create_pda_account(
+        program_id,
+        payer,
+        mint_pda,
+        MintPDA::LEN,
+        &[&s1, &series_seed, &[bump]],
+        sys_program,
+    )?;
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+
+    msg!("handing the holder mint over...");
+    let mut authorities = vec![spl_token::instruction::AuthorityType::MintTokens];
+    if holder_mint_info.freeze_authority.is_some() {
+        authorities.push(spl_token::instruction::AuthorityType::FreezeAccount);
+    }
+    for authority_type in authorities {
+        let ix = spl_token::instruction::set_authority(
+            &spl_token::id(),
+            holder_mint.key,
+            Some(mint_pda.key),
+            authority_type,
+            legacy_mint_pda.key,
+            &[],
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                holder_mint.clone(),
+                legacy_mint_pda.clone(),
+                token_program.clone(),
+            ],
+            &[&[&s1, &legacy_seed, &[legacy_bump]]],
+        )?;
+    }
+
+    close_data_pda(legacy_mint_pda, payer)
+}
+
+pub fn redeem_writer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lots: u64,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let redeemer = next_account_info(accounts)?;
+    let writer_ata = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let writer_temp = next_account_info(accounts)?;
+    let writer_receive = next_account_info(accounts)?;
+    let collateral_dest = next_account_info(accounts)?;
+    let proceeds_dest = next_account_info(accounts)?;
+    let data_pda = next_account_info(accounts)?;
+    let writer = next_account_info(accounts)?;
+    let buyer = next_account_info(accounts)?;
+    let sys_program = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
+    check_data_pda(program_id, &contract_pda, data_pda)?;
+    let writer_temp_info = unpack_token_account(writer_temp)?;
+    let writer_receive_info = unpack_token_account(writer_receive)?;
+    let collateral_dest_info = unpack_token_account(collateral_dest)?;
+    let proceeds_dest_info = unpack_token_account(proceeds_dest)?;
+    let writer_ata_info = unpack_token_account(writer_ata)?;
+    check_owner(writer_mint, &spl_token::id())?;
+
+    msg!("asserting validity...");
+    if !system_program::check_id(sys_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if contract_pda.contract_state != ContractState::SETTLED {
+        return Err(OptionsError::ContractNotSettled.into());
+    }
+    if !redeemer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if lots == 0 || lots > contract_pda.writer_lots {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    let (wd, bd) = match (&contract_pda.writer_data, &contract_pda.buyer_data) {
+        (Some(wd), Some(bd)) => (wd, bd),
+        _ => return Err(OptionsError::ContractNotFinalised.into()),
+    };
+    let (mint_pdak, _mint_bump) = instruction::get_mint_pda(
+        program_id,
+        contract_pda.contract_type,
+        &contract_pda.series_seed,
+    );
+    if *mint_pda.key != mint_pdak {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    let (writer_mintk, _bump) =
+        instruction::get_writer_mint_of(program_id, &contract_pda, data_pda.key);
+    if *writer_mint.key != writer_mintk {
+        return Err(OptionsError::IncorrectWriterMint.into());
+    }
+    if writer_ata_info.mint != writer_mintk {
+        return Err(OptionsError::IncorrectWriterAta.into());
+    }
+    if *writer_temp.key != wd.temp_pub {
+        return Err(OptionsError::IncorrectTempAccount.into());
+    }
+    if *writer_receive.key != wd.receive_pub {
+        return Err(OptionsError::IncorrectWriterReceive.into());
+    }
+    if collateral_dest_info.mint != writer_temp_info.mint
+        || proceeds_dest_info.mint != writer_receive_info.mint
+    {
+        return Err(OptionsError::IncorrectReceiveType.into());
+    }
+    if *writer.key != wd.party_pub {
+        return Err(OptionsError::IncorrectWriterAccount.into());
+    }
+    if *buyer.key != bd.party_pub {
+        return Err(OptionsError::IncorrectBuyerAccount.into());
+    }
+
+    msg!("burning writer tokens...");
+    let ix = spl_token::instruction::burn(
+        token_program.key,
+        writer_ata.key,
+        writer_mint.key,
+        redeemer.key,
+        &[redeemer.key],
+        lots,
+    )?;
+    invoke(
+        &ix,
+        &[writer_ata.clone(), writer_mint.clone(), redeemer.clone()],
+    )?;
+
+    // pro rata, the last tokens take whatever rounding left behind; the payout still
+    // owed to holder lots is not the writers' to redeem
+    let pda_seeds: &[&[u8]] = &[
+        &contract_pda.seed,
+        &contract_pda.index_seed,
+        &[contract_pda.bump],
+    ];
+    let collateral = writer_temp_info
+        .amount
+        .checked_sub(lot_qty(contract_pda.settle_payout, contract_pda.open_lots)?)
+        .ok_or(OptionsError::IncorrectTempBalance)?;
+    for (escrow, amount, dest) in [
+        (writer_temp, collateral, collateral_dest),
+        (writer_receive, writer_receive_info.amount, proceeds_dest),
+    ] {
+        let share = (amount as u128 * lots as u128 / contract_pda.writer_lots as u128) as u64;
+        if share == 0 {
+            continue;
+        }
+        msg!("transferring {} to redeemer...", share);
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            escrow.key,
+            dest.key,
+            data_pda.key,
+            &[data_pda.key],
+            share,
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                escrow.clone(),
+                dest.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[pda_seeds],
+        )?;
+    }
+
+    contract_pda.writer_lots -= lots;
+    if contract_pda.writer_lots > 0 || contract_pda.open_lots > 0 {
+        msg!(
+            "{} writer lots, {} holder lots left, updating PDA data...",
+            contract_pda.writer_lots,
+            contract_pda.open_lots
+        );
+        return contract_pda.pack_into_slice(&mut data_pda.try_borrow_mut_data()?);
+    }
+
+    close_settled(
+        &contract_pda,
+        data_pda,
+        [writer_temp, writer_receive],
+        writer,
+        buyer,
+        token_program,
+    )
+}
+
+// the escrow rent goes to the writer, the contract's to whoever initialised it
+fn close_settled<'a>(
+    contract_pda: &ContractPDA,
+    data_pda: &AccountInfo<'a>,
+    escrows: [&AccountInfo<'a>; 2],
+    writer: &AccountInfo<'a>,
+    buyer: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+) -> Result<(), ProgramError> {
+    let pda_seeds: &[&[u8]] = &[
+        &contract_pda.seed,
+        &contract_pda.index_seed,
+        &[contract_pda.bump],
+    ];
+    msg!("closing escrow accounts...");
+    for escrow in escrows {
+        let ix = spl_token::instruction::close_account(
+            token_program.key,
+            escrow.key,
+            writer.key,
+            data_pda.key,
+            &[data_pda.key],
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                escrow.clone(),
+                writer.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[pda_seeds],
+        )?;
+    }
+
+    let send_to = match contract_pda.init_party {
+        instruction::InitParty::BUYER => buyer,
+        instruction::InitParty::WRITER => writer,
+    };
+
+    close_data_pda(data_pda, send_to)
+}
+
+pub fn claim_settlement(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lots: u64,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let holder = next_account_info(accounts)?;
+    let holder_ata = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let writer_temp = next_account_info(accounts)?;
+    let writer_receive = next_account_info(accounts)?;
+    let payout_dest = next_account_info(accounts)?;
+    let data_pda = next_account_info(accounts)?;
+    let writer = next_account_info(accounts)?;
+    let buyer = next_account_info(accounts)?;
+    let sys_program = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
+    check_data_pda(program_id, &contract_pda, data_pda)?;
+    let mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
+    let holder_ata_info = unpack_token_account(holder_ata)?;
+    let writer_temp_info = unpack_token_account(writer_temp)?;
+    let payout_dest_info = unpack_token_account(payout_dest)?;
+    check_owner(holder_mint, &spl_token::id())?;
+    check_owner(writer_receive, &spl_token::id())?;
+
+    msg!("asserting validity...");
+    if !system_program::check_id(sys_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if contract_pda.contract_state != ContractState::SETTLED {
+        return Err(OptionsError::ContractNotSettled.into());
+    }
+    if !holder.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if lots == 0 || lots > contract_pda.open_lots {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    let (wd, bd) = match (&contract_pda.writer_data, &contract_pda.buyer_data) {
+        (Some(wd), Some(bd)) => (wd, bd),
+        _ => return Err(OptionsError::ContractNotFinalised.into()),
+    };
+    let (mint_pdak, _mint_bump) = instruction::get_mint_pda(
+        program_id,
+        contract_pda.contract_type,
+        &contract_pda.series_seed,
+    );
+    if *mint_pda.key != mint_pdak {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    if mint_pda_data.holder_mint != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderMint.into());
+    }
+    if holder_ata_info.mint != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderAta.into());
+    }
+    if *writer_temp.key != wd.temp_pub {
+        return Err(OptionsError::IncorrectTempAccount.into());
+    }
+    if *writer_receive.key != wd.receive_pub {
+        return Err(OptionsError::IncorrectWriterReceive.into());
+    }
+    if payout_dest_info.mint != writer_temp_info.mint {
+        return Err(OptionsError::IncorrectReceiveType.into());
+    }
+    if *writer.key != wd.party_pub {
+        return Err(OptionsError::IncorrectWriterAccount.into());
+    }
+    if *buyer.key != bd.party_pub {
+        return Err(OptionsError::IncorrectBuyerAccount.into());
+    }
+
+    msg!("burning holder_mint tokens...");
+    let ix = spl_token::instruction::burn(
+        token_program.key,
+        holder_ata.key,
+        holder_mint.key,
+        holder.key,
+        &[holder.key],
+        lots,
+    )?;
+    invoke(
+        &ix,
+        &[holder_ata.clone(), holder_mint.clone(), holder.clone()],
+    )?;
+
+    let payout = lot_qty(contract_pda.settle_payout, lots)?;
+    msg!("transferring {} to holder...", payout);
+    let ix = spl_token::instruction::transfer(
+        token_program.key,
+        writer_temp.key,
+        payout_dest.key,
+        data_pda.key,
+        &[data_pda.key],
+        payout,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            writer_temp.clone(),
+            payout_dest.clone(),
+            data_pda.clone(),
+            token_program.clone(),
+        ],
+        &[&[
+            &contract_pda.seed,
+            &contract_pda.index_seed,
+            &[contract_pda.bump],
+        ]],
+    )?;
+
+    contract_pda.open_lots -= lots;
+    if contract_pda.writer_lots > 0 || contract_pda.open_lots > 0 {
+        msg!(
+            "{} writer lots, {} holder lots left, updating PDA data...",
+            contract_pda.writer_lots,
+            contract_pda.open_lots
+        );
+        return contract_pda.pack_into_slice(&mut data_pda.try_borrow_mut_data()?);
+    }
+
+    close_settled(
+        &contract_pda,
+        data_pda,
+        [writer_temp, writer_receive],
+        writer,
+        buyer,
+        token_program,
+    )
+}
+
+// holder and writer tokens of the same lots cancel out, so whoever holds both takes
+// the collateral back without waiting for expiry
+pub fn close_out(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lots: u64,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let closer = next_account_info(accounts)?;
+    let holder_ata = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let writer_ata = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let writer_temp = next_account_info(accounts)?;
+    let writer_receive = next_account_info(accounts)?;
+    let collateral_dest = next_account_info(accounts)?;
+    let data_pda = next_account_info(accounts)?;
+    let writer = next_account_info(accounts)?;
+    let buyer = next_account_info(accounts)?;
+    let sys_program = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    let mut contract_pda = unpack_contract_pda(program_id, data_pda)?;
+    check_data_pda(program_id, &contract_pda, data_pda)?;
+    if contract_pda.contract_data.pooled {
+        return Err(OptionsError::ContractPooled.into());
+    }
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
+    let writer_temp_info = unpack_token_account(writer_temp)?;
+    let collateral_dest_info = unpack_token_account(collateral_dest)?;
+    check_owner(holder_mint, &spl_token::id())?;
+    check_owner(writer_receive, &spl_token::id())?;
+    // no writer tokens were minted against contracts written before the writer mint
+    let legacy = contract_pda.writer_lots == 0;
+
+    msg!("asserting validity...");
+    if !system_program::check_id(sys_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    match contract_pda.contract_state {
+        ContractState::FINAL | ContractState::PARTIALLY_FILLED => (),
+        _ => {
+            return Err(OptionsError::ContractNotFinalised.into());
+        }
+    };
+    // after expiry the lots are settled by Expire or SettleAtExpiry instead
+    if expiry_clock(&contract_pda.contract_data)? > contract_pda.contract_data.expiry_date {
+        return Err(OptionsError::ContractExpired.into());
+    }
+    if !closer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if lots == 0 || lots > contract_pda.open_lots {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    let (wd, bd) = match (&contract_pda.writer_data, &contract_pda.buyer_data) {
+        (Some(wd), Some(bd)) => (wd, bd),
+        _ => return Err(OptionsError::ContractNotFinalised.into()),
+    };
+    let (mint_pdak, _mint_bump) = instruction::get_mint_pda(
+        program_id,
+        contract_pda.contract_type,
+        &contract_pda.series_seed,
+    );
+    if *mint_pda.key != mint_pdak {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    if mint_pda_data.holder_mint != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderMint.into());
+    }
+    if *writer_temp.key != wd.temp_pub {
+        return Err(OptionsError::IncorrectTempAccount.into());
+    }
+    if *writer_receive.key != wd.receive_pub {
+        return Err(OptionsError::IncorrectWriterReceive.into());
+    }
+    if collateral_dest_info.mint != writer_temp_info.mint {
+        return Err(OptionsError::IncorrectReceiveType.into());
+    }
+    if *writer.key != wd.party_pub {
+        return Err(OptionsError::IncorrectWriterAccount.into());
+    }
+    if *buyer.key != bd.party_pub {
+        return Err(OptionsError::IncorrectBuyerAccount.into());
+    }
+    if legacy {
+        // the collateral is the writer's alone
+        if *closer.key != wd.party_pub {
+            return Err(OptionsError::IncorrectWriterAccount.into());
+        }
+    } else {
+        let (writer_mintk, _bump) =
+            instruction::get_writer_mint_of(program_id, &contract_pda, data_pda.key);
+        if *writer_mint.key != writer_mintk {
+            return Err(OptionsError::IncorrectWriterMint.into());
+        }
+        check_owner(writer_ata, &spl_token::id())?;
+    }
+
+    msg!("burning holder_mint tokens...");
+    let ix = spl_token::instruction::burn(
+        token_program.key,
+        holder_ata.key,
+        holder_mint.key,
+        closer.key,
+        &[closer.key],
+        lots,
+    )?;
+    invoke(
+        &ix,
+        &[holder_ata.clone(), holder_mint.clone(), closer.clone()],
+    )?;
+
+    if !legacy {
+        msg!("burning writer tokens...");
+        let ix = spl_token::instruction::burn(
+            token_program.key,
+            writer_ata.key,
+            writer_mint.key,
+            closer.key,
+            &[closer.key],
+            lots,
+        )?;
+        invoke(
+            &ix,
+            &[writer_ata.clone(), writer_mint.clone(), closer.clone()],
+        )?;
+    }
+
+    let pda_seeds: &[&[u8]] = &[
+        &contract_pda.seed,
+        &contract_pda.index_seed,
+        &[contract_pda.bump],
+    ];
+    let (_, collateral_qty) = contract_pda
+        .contract_data
+        .collateral(contract_pda.contract_type);
+    msg!("releasing collateral to closer...");
+    let ix = spl_token::instruction::transfer(
+        token_program.key,
+        writer_temp.key,
+        collateral_dest.key,
+        data_pda.key,
+        &[data_pda.key],
+        lot_qty(collateral_qty, lots)?,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            writer_temp.clone(),
+            collateral_dest.clone(),
+            data_pda.clone(),
+            token_program.clone(),
+        ],
+        &[pda_seeds],
+    )?;
+
+    contract_pda.open_lots -= lots;
+    mint_pda_data.record_close_out(lots);
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+    if !legacy {
+        contract_pda.writer_lots -= lots;
+    }
+    if contract_pda.open_lots > 0 || contract_pda.unfilled_lots > 0 {
+        msg!(
+            "{} lots open, {} unfilled, updating PDA data...",
+            contract_pda.open_lots,
+            contract_pda.unfilled_lots
+        );
+        return contract_pda.pack_into_slice(&mut data_pda.try_borrow_mut_data()?);
+    }
+    if contract_pda.writer_lots > 0 {
+        msg!("all lots closed, writer tokens left to redeem...");
+        contract_pda.contract_state = ContractState::SETTLED;
+        return contract_pda.pack_into_slice(&mut data_pda.try_borrow_mut_data()?);
+    }
+
+    // a legacy writer never handed over its receive account
+    let escrows = match legacy {
+        true => vec![writer_temp],
+        false => vec![writer_temp, writer_receive],
+    };
+    for escrow in escrows {
+        msg!("returning escrow {} to writer...", escrow.key);
+        let ix = spl_token::instruction::set_authority(
+            token_program.key,
+            escrow.key,
+            Some(writer.key),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            data_pda.key,
+            &[data_pda.key],
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                escrow.clone(),
+                writer.clone(),
+                data_pda.clone(),
+                token_program.clone(),
+            ],
+            &[pda_seeds],
+        )?;
+    }
+
+    let send_to = match contract_pda.init_party {
+        instruction::InitParty::BUYER => buyer,
+        instruction::InitParty::WRITER => writer,
+    };
+
+    close_data_pda(data_pda, send_to)
+}
+
+pub fn exercise_pooled(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_type: ContractType,
+    contract_data: ContractData,
+    lots: u64,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let holder = next_account_info(accounts)?;
+    let holder_temp = next_account_info(accounts)?;
+    let holder_receive = next_account_info(accounts)?;
+    let holder_ata = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
+    let collateral_vault = next_account_info(accounts)?;
+    let proceeds_vault = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    let ct = contract_type;
+    let cd = &contract_data;
+    let seed = &cd.series_seed();
+    let time = expiry_clock(cd)?;
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
+    let holder_ata_info = unpack_token_account(holder_ata)?;
+    let holder_temp_info = unpack_token_account(holder_temp)?;
+    let holder_receive_info = unpack_token_account(holder_receive)?;
+    check_owner(holder_mint, &spl_token::id())?;
+
+    msg!("asserting validity");
+    if !cd.pooled {
+        return Err(OptionsError::ContractNotPooled.into());
+    }
+    if time > cd.expiry_date {
+        return Err(OptionsError::ContractExpired.into());
+    }
+    if !exercise_window_open(cd, time) {
+        return Err(OptionsError::ExerciseWindowNotOpen.into());
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !holder.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if lots == 0 {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    let (mint_pdak, mint_bump) = instruction::get_mint_pda(program_id, ct, seed);
+    if mint_pdak != *mint_pda.key {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    if mint_pda_data.holder_mint != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderMint.into());
+    }
+    if holder_ata_info.mint != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderAta.into());
+    }
+    if holder_ata_info.owner != *holder.key {
+        return Err(OptionsError::HolderAtaNotOwned.into());
+    }
+    check_series_vault(program_id, ct, seed, collateral_vault, b"vault")?;
+    check_series_vault(program_id, ct, seed, proceeds_vault, b"proceeds")?;
+    let (rec_type, _) = cd.receive_types(ct);
+    if holder_receive_info.mint != rec_type {
+        return Err(OptionsError::IncorrectReceiveType.into());
+    }
+    // the collateral not paid out stays in the vault for the writers
+    let (temp_qty, _, rec_qty) =
+        exercise_amounts(program_id, cd, ct, lots, &holder_temp_info, accounts)?;
+
+    msg!("burning holder_mint tokens...");
+    let ix = spl_token::instruction::burn(
+        token_program.key,
+        holder_ata.key,
+        holder_mint.key,
+        holder.key,
+        &[holder.key],
+        lots,
+    )?;
+    invoke(
+        &ix,
+        &[holder_ata.clone(), holder_mint.clone(), holder.clone()],
+    )?;
+    mint_pda_data.record_exercise(lots)?;
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+
+    if temp_qty > 0 {
+        msg!("transferring holder_temp to series proceeds...");
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            holder_temp.key,
+            proceeds_vault.key,
+            holder.key,
+            &[holder.key],
+            temp_qty,
+        )?;
+        invoke(
+            &ix,
+            &[
+                holder_temp.clone(),
+                proceeds_vault.clone(),
+                holder.clone(),
+                token_program.clone(),
+            ],
+        )?;
+    }
+
+    msg!("transferring collateral from series vault to holder...");
+    let s1 = [ct as u8];
+    let ix = spl_token::instruction::transfer(
+        token_program.key,
+        collateral_vault.key,
+        holder_receive.key,
+        mint_pda.key,
+        &[mint_pda.key],
+        rec_qty,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            collateral_vault.clone(),
+            holder_receive.clone(),
+            mint_pda.clone(),
+            token_program.clone(),
+        ],
+        &[&[&s1, seed, &[mint_bump]]],
+    )
+}
+
+pub fn redeem_pooled(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_type: ContractType,
+    contract_data: ContractData,
+    lots: u64,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let redeemer = next_account_info(accounts)?;
+    let writer_ata = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let collateral_vault = next_account_info(accounts)?;
+    let proceeds_vault = next_account_info(accounts)?;
+    let collateral_dest = next_account_info(accounts)?;
+    let proceeds_dest = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    let ct = contract_type;
+    let cd = &contract_data;
+    let seed = &cd.series_seed();
+    let time = expiry_clock(cd)?;
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
+    let collateral_info = unpack_token_account(collateral_vault)?;
+    let proceeds_info = unpack_token_account(proceeds_vault)?;
+    let collateral_dest_info = unpack_token_account(collateral_dest)?;
+    let proceeds_dest_info = unpack_token_account(proceeds_dest)?;
+    check_owner(writer_mint, &spl_token::id())?;
+
+    msg!("asserting validity");
+    if !cd.pooled {
+        return Err(OptionsError::ContractNotPooled.into());
+    }
+    // holders exercise until expiry, writers redeem what is left after it
+    if time <= cd.expiry_date {
+        return Err(OptionsError::ContractNotExpired.into());
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !redeemer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let (mint_pdak, mint_bump) = instruction::get_mint_pda(program_id, ct, seed);
+    if mint_pdak != *mint_pda.key {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    if instruction::get_writer_mint(program_id, ct, seed).0 != *writer_mint.key {
+        return Err(OptionsError::IncorrectWriterMint.into());
+    }
+    check_series_vault(program_id, ct, seed, collateral_vault, b"vault")?;
+    check_series_vault(program_id, ct, seed, proceeds_vault, b"proceeds")?;
+    if collateral_dest_info.mint != collateral_info.mint
+        || proceeds_dest_info.mint != proceeds_info.mint
+    {
+        return Err(OptionsError::IncorrectReceiveType.into());
+    }
+    let shares = spl_token::state::Mint::unpack(&writer_mint.try_borrow_data()?)?.supply;
+    if lots == 0 || lots > shares {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    let share = |amount: u64| (amount as u128 * lots as u128 / shares as u128) as u64;
+
+    msg!("burning writer_mint tokens...");
+    let ix = spl_token::instruction::burn(
+        token_program.key,
+        writer_ata.key,
+        writer_mint.key,
+        redeemer.key,
+        &[redeemer.key],
+        lots,
+    )?;
+    invoke(
+        &ix,
+        &[writer_ata.clone(), writer_mint.clone(), redeemer.clone()],
+    )?;
+    // no instruction expires a pooled series, the first redemption retires its open lots
+    if mint_pda_data.open_interest > 0 {
+        let open_interest = mint_pda_data.open_interest;
+        mint_pda_data.record_expiry(open_interest)?;
+        mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+    }
+
+    let s1 = [ct as u8];
+    for (vault, dest, amount) in [
+        (
+            collateral_vault,
+            collateral_dest,
+            share(collateral_info.amount),
+        ),
+        (proceeds_vault, proceeds_dest, share(proceeds_info.amount)),
+    ] {
+        if amount == 0 {
+            continue;
+        }
+        msg!("transferring {} from series vault...", amount);
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            vault.key,
+            dest.key,
+            mint_pda.key,
+            &[mint_pda.key],
+            amount,
+        )?;
+        invoke_signed(
+            &ix,
+            &[
+                vault.clone(),
+                dest.clone(),
+                mint_pda.clone(),
+                token_program.clone(),
+            ],
+            &[&[&s1, seed, &[mint_bump]]],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn close_out_pooled(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    contract_type: ContractType,
+    contract_data: ContractData,
+    lots: u64,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let closer = next_account_info(accounts)?;
+    let holder_ata = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let writer_ata = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let collateral_vault = next_account_info(accounts)?;
+    let collateral_dest = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    let ct = contract_type;
+    let cd = &contract_data;
+    let seed = &cd.series_seed();
+    let time = expiry_clock(cd)?;
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
+    let collateral_dest_info = unpack_token_account(collateral_dest)?;
+    check_owner(holder_mint, &spl_token::id())?;
+    check_owner(writer_mint, &spl_token::id())?;
+
+    msg!("asserting validity");
+    if !cd.pooled {
+        return Err(OptionsError::ContractNotPooled.into());
+    }
+    if time > cd.expiry_date {
+        return Err(OptionsError::ContractExpired.into());
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !closer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if lots == 0 {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    let (mint_pdak, mint_bump) = instruction::get_mint_pda(program_id, ct, seed);
+    if mint_pdak != *mint_pda.key {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    if mint_pda_data.holder_mint != *holder_mint.key {
+        return Err(OptionsError::IncorrectHolderMint.into());
+    }
+    if instruction::get_writer_mint(program_id, ct, seed).0 != *writer_mint.key {
+        return Err(OptionsError::IncorrectWriterMint.into());
+    }
+    check_series_vault(program_id, ct, seed, collateral_vault, b"vault")?;
+    let (collateral_type, collateral_qty) = cd.collateral(ct);
+    if collateral_dest_info.mint != collateral_type {
+        return Err(OptionsError::IncorrectReceiveType.into());
+    }
+
+    msg!("burning holder_mint tokens...");
+    let ix = spl_token::instruction::burn(
+        token_program.key,
+        holder_ata.key,
+        holder_mint.key,
+        closer.key,
+        &[closer.key],
+        lots,
+    )?;
+    invoke(
+        &ix,
+        &[holder_ata.clone(), holder_mint.clone(), closer.clone()],
+    )?;
+
+    msg!("burning writer_mint tokens...");
+    let ix = spl_token::instruction::burn(
+        token_program.key,
+        writer_ata.key,
+        writer_mint.key,
+        closer.key,
+        &[closer.key],
+        lots,
+    )?;
+    invoke(
+        &ix,
+        &[writer_ata.clone(), writer_mint.clone(), closer.clone()],
+    )?;
+    mint_pda_data.record_close_out(lots);
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+
+    msg!("transferring collateral from series vault...");
+    let s1 = [ct as u8];
+    let ix = spl_token::instruction::transfer(
+        token_program.key,
+        collateral_vault.key,
+        collateral_dest.key,
+        mint_pda.key,
+        &[mint_pda.key],
+        lot_qty(collateral_qty, lots)?,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            collateral_vault.clone(),
+            collateral_dest.clone(),
+            mint_pda.clone(),
+            token_program.clone(),
+        ],
+        &[&[&s1, seed, &[mint_bump]]],
+    )
+}
+
+pub fn place_order(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction: instruction::OfferData,
+) -> Result<(), ProgramError> {
+    // only a pooled series can fill against any number of counterparties
+    if !instruction.contract_data.pooled {
+        return Err(OptionsError::ContractNotPooled.into());
+    }
+    if instruction.counterparty.is_some() {
+        return Err(OptionsError::PrivateOrder.into());
+    }
+    let accounts = &mut accounts.iter();
+
+    let initialiser = next_account_info(accounts)?;
+    let source = next_account_info(accounts)?;
+    let token_temp = next_account_info(accounts)?;
+    let receive_acc = next_account_info(accounts)?;
+    let receive_ata = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
+    let data_pda = next_account_info(accounts)?;
+    let sys_program = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+    let collateral_vault = next_account_info(accounts)?;
+    let proceeds_vault = next_account_info(accounts)?;
+    let collateral_mint = next_account_info(accounts)?;
+    let proceeds_mint = next_account_info(accounts)?;
+    let order_book = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let party = instruction.party;
+    let writer_ata = match party {
+        instruction::InitParty::WRITER => Some(next_account_info(accounts)?),
+        instruction::InitParty::BUYER => None,
+    };
+
+    let ct = instruction.contract_type;
+    let cd = &instruction.contract_data;
+    let premium_qty = cd.premium_qty;
+    let escrow_qty = match party {
+        instruction::InitParty::BUYER => premium_qty,
+        instruction::InitParty::WRITER => cd.collateral(ct).1,
+    };
+
+    msg!("asserting validity...");
+    if cd.lots == 0 {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    let time = expiry_clock(cd)?;
+    if time > cd.expiry_date {
+        return Err(OptionsError::ContractExpired.into());
+    }
+    if !system_program::check_id(sys_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !initialiser.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if unpack_token_account(source)?.amount < lot_qty(escrow_qty, cd.lots)? {
+        return Err(OptionsError::IncorrectTempBalance.into());
+    }
+    let series_seed = cd.series_seed();
+    let (bookk, book_bump) =
+        instruction::get_order_book(program_id, ct, &series_seed, &cd.premium_type);
+    if bookk != *order_book.key {
+        return Err(OptionsError::IncorrectOrderBook.into());
+    }
+
+    let mut book = match order_book.try_data_is_empty()? {
+        true => {
+            msg!("creating order book");
+            let min_rent = rent::Rent::get()?.minimum_balance(OrderBook::LEN);
+            let ix = system_instruction::create_account(
+                initialiser.key,
+                order_book.key,
+                min_rent,
+                OrderBook::LEN as u64,
+                program_id,
+            );
+            invoke_signed(
+                &ix,
+                &[initialiser.clone(), order_book.clone(), sys_program.clone()],
+                &[&[
+                    &[ct as u8],
+                    &series_seed,
+                    b"book",
+                    cd.premium_type.as_ref(),
+                    &[book_bump],
+                ]],
+            )?;
+            OrderBook::new(ct, cd)
+        }
+        false => {
+            check_owner(order_book, program_id)?;
+            OrderBook::unpack_from_slice(*order_book.try_borrow_data()?)?
+        }
+    };
+
+    let mut lots = cd.lots;
+    while lots > 0 {
+        let best = match book.opposite(&party).first() {
+            Some(order) => order.clone(),
+            None => break,
+        };
+        let crosses = match party {
+            instruction::InitParty::BUYER => best.premium_qty <= premium_qty,
+            instruction::InitParty::WRITER => best.premium_qty >= premium_qty,
+        };
+        if !crosses {
+            break;
+        }
+
+        let maker_pda = next_account_info(accounts)?;
+        if *maker_pda.key != best.data_pda {
+            return Err(OptionsError::IncorrectDataPda.into());
+        }
+        // offers stay open to AcceptBid, AcceptAsk and CancelOffer outside the book
+        let unfilled = match maker_pda.try_data_is_empty()? {
+            true => 0,
+            false => {
+                let maker = unpack_contract_pda(program_id, maker_pda)?;
+                match maker.contract_data.premium_qty == best.premium_qty
+                    && !maker.offer_expired(time)
+                {
+                    true => maker.unfilled_lots,
+                    false => 0,
+                }
+            }
+        };
+        if unfilled == 0 {
+            msg!("dropping order gone from the chain");
+            book.opposite(&party).remove(0);
+            continue;
+        }
+
+        let fill = lots.min(unfilled);
+        msg!("crossing {} lots at {}", fill, best.premium_qty);
+        match writer_ata {
+            None => {
+                let premium_dest = next_account_info(accounts)?;
+                let maker_writer_ata = next_account_info(accounts)?;
+                let asset_temp = next_account_info(accounts)?;
+                let writer = next_account_info(accounts)?;
+                accept_ask(
+                    program_id,
+                    &[
+                        initialiser.clone(),
+                        source.clone(),
+                        receive_acc.clone(),
+                        receive_ata.clone(),
+                        mint_pda.clone(),
+                        holder_mint.clone(),
+                        maker_pda.clone(),
+                        premium_dest.clone(),
+                        sys_program.clone(),
+                        token_program.clone(),
+                        writer_mint.clone(),
+                        maker_writer_ata.clone(),
+                        asset_temp.clone(),
+                        collateral_vault.clone(),
+                        writer.clone(),
+                    ],
+                    fill,
+                    None,
+                )?;
+            }
+            Some(writer_ata) => {
+                let premium_temp = next_account_info(accounts)?;
+                let buyer = next_account_info(accounts)?;
+                let buyer_holder_ata = next_account_info(accounts)?;
+                accept_bid(
+                    program_id,
+                    &[
+                        initialiser.clone(),
+                        source.clone(),
+                        receive_acc.clone(),
+                        receive_ata.clone(),
+                        maker_pda.clone(),
+                        premium_temp.clone(),
+                        buyer.clone(),
+                        buyer_holder_ata.clone(),
+                        mint_pda.clone(),
+                        holder_mint.clone(),
+                        sys_program.clone(),
+                        token_program.clone(),
+                        writer_mint.clone(),
+                        writer_ata.clone(),
+                        collateral_vault.clone(),
+                    ],
+                    fill,
+                    None,
+                )?;
+            }
+        }
+        lots -= fill;
+        let resting = book.opposite(&party);
+        match unfilled - fill {
+            0 => {
+                resting.remove(0);
+            }
+            left => resting[0].lots = left,
+        }
+    }
+
+    if lots > 0 {
+        msg!("escrowing {} resting lots...", lots);
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            token_temp.key,
+            initialiser.key,
+            &[initialiser.key],
+            lot_qty(escrow_qty, lots)?,
+        )?;
+        invoke(
+            &ix,
+            &[
+                source.clone(),
+                token_temp.clone(),
+                initialiser.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        let mut offer = instruction;
+        offer.contract_data.lots = lots;
+        initialise_contract(
+            program_id,
+            &[
+                initialiser.clone(),
+                token_temp.clone(),
+                receive_acc.clone(),
+                receive_ata.clone(),
+                mint_pda.clone(),
+                holder_mint.clone(),
+                data_pda.clone(),
+                sys_program.clone(),
+                token_program.clone(),
+                collateral_vault.clone(),
+                proceeds_vault.clone(),
+                collateral_mint.clone(),
+                proceeds_mint.clone(),
+            ],
+            offer,
+        )?;
+        let evicted = book.insert(
+            &party,
+            Order {
+                premium_qty,
+                lots,
+                data_pda: *data_pda.key,
+            },
+        )?;
+        if let Some(evicted) = evicted {
+            msg!("evicting order {} from the full book", evicted.data_pda);
+        }
+    }
+
+    msg!("updating order book...");
+    book.pack_into_slice(*order_book.try_borrow_mut_data()?);
+    Ok(())
+}
+
+pub fn cancel_order(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ProgramError> {
+    let (order_book, offer_accounts) = accounts
+        .split_first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let data_pda = offer_accounts
+        .get(2)
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let contract_pda = unpack_contract_pda(program_id, data_pda)?;
+
+    let (bookk, _bump) = instruction::get_order_book(
+        program_id,
+        contract_pda.contract_type,
+        &contract_pda.series_seed,
+        &contract_pda.contract_data.premium_type,
+    );
+    if bookk != *order_book.key {
+        return Err(OptionsError::IncorrectOrderBook.into());
+    }
+    check_owner(order_book, program_id)?;
+    let mut book = OrderBook::unpack_from_slice(*order_book.try_borrow_data()?)?;
+    book.remove(data_pda.key)
+        .ok_or(OptionsError::OrderNotFound)?;
+
+    cancel_offer(program_id, offer_accounts)?;
+    msg!("updating order book...");
+    book.pack_into_slice(*order_book.try_borrow_mut_data()?);
+    Ok(())
+}
+
+pub fn accept_quote(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    quote: instruction::Quote,
+) -> Result<(), ProgramError> {
+    let accounts = &mut accounts.iter();
+
+    let taker = next_account_info(accounts)?;
+    let data_pda = next_account_info(accounts)?;
+    let quote_nonce = next_account_info(accounts)?;
+    let quote_authority = next_account_info(accounts)?;
+    let instructions_sysvar = next_account_info(accounts)?;
+    let mint_pda = next_account_info(accounts)?;
+    let holder_mint = next_account_info(accounts)?;
+    let writer_mint = next_account_info(accounts)?;
+    let buyer = next_account_info(accounts)?;
+    let premium_source = next_account_info(accounts)?;
+    let buyer_receive = next_account_info(accounts)?;
+    let holder_ata = next_account_info(accounts)?;
+    let writer = next_account_info(accounts)?;
+    let collateral_source = next_account_info(accounts)?;
+    let asset_temp = next_account_info(accounts)?;
+    let writer_receive = next_account_info(accounts)?;
+    let prem_receive_acc = next_account_info(accounts)?;
+    let writer_ata = next_account_info(accounts)?;
+    let sys_program = next_account_info(accounts)?;
+    let token_program = next_account_info(accounts)?;
+
+    let ct = quote.contract_type;
+    let cd = &quote.contract_data;
+    let lots = cd.lots;
+    let (vault_mints, ata_program) = match cd.pooled {
+        true => (
+            Some([next_account_info(accounts)?, next_account_info(accounts)?]),
+            None,
+        ),
+        false => (None, Some(next_account_info(accounts)?)),
+    };
+
+    check_exercise_window(cd)?;
+    check_settlement(cd)?;
+    if lots == 0 {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    let (collateral_type, collateral_qty) = cd.collateral(ct);
+    let (buyer_rec_type, writer_rec_type) = cd.receive_types(ct);
+    for qty in [cd.token_qty, cd.strike_qty] {
+        lot_qty(qty, lots)?;
+    }
+    let premium = lot_qty(cd.premium_qty, lots)?;
+    let collateral = lot_qty(collateral_qty, lots)?;
+
+    let premium_source_info = unpack_token_account(premium_source)?;
+    let collateral_source_info = unpack_token_account(collateral_source)?;
+    let buyer_receive_info = unpack_token_account(buyer_receive)?;
+    let prem_receive_info = unpack_token_account(prem_receive_acc)?;
+    check_owner(holder_ata, &spl_token::id())?;
+    check_owner(holder_mint, &spl_token::id())?;
+    let mut mint_pda_data = unpack_mint_pda(program_id, mint_pda)?;
+
+    msg!("asserting validity...");
+    if !system_program::check_id(sys_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !spl_token::check_id(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !taker.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if *taker.key != quote.taker {
+        return Err(OptionsError::CounterpartyNotAllowed.into());
+    }
+    if current_time_ms()? > quote.valid_until {
+        return Err(OptionsError::QuoteExpired.into());
+    }
+    if expiry_clock(cd)? > cd.expiry_date {
+        return Err(OptionsError::ContractExpired.into());
+    }
+    let maker_is_buyer = quote.party == instruction::InitParty::BUYER;
+    let (buyerk, writerk) = match maker_is_buyer {
+        true => (quote.maker, *taker.key),
+        false => (*taker.key, quote.maker),
+    };
+    if *buyer.key != buyerk {
+        return Err(OptionsError::IncorrectBuyerAccount.into());
+    }
+    if *writer.key != writerk {
+        return Err(OptionsError::IncorrectWriterAccount.into());
+    }
+    check_quote_signature(
+        instructions_sysvar,
+        &quote.maker,
+        &quote.message(program_id),
+    )?;
+
+    let (quote_noncek, nonce_bump) =
+        instruction::get_quote_nonce(program_id, &quote.maker, quote.nonce);
+    let (quote_authorityk, authority_bump) =
+        instruction::get_quote_authority(program_id, &quote.maker);
+    if *quote_nonce.key != quote_noncek || *quote_authority.key != quote_authorityk {
+        return Err(OptionsError::IncorrectQuoteAccount.into());
+    }
+    if quote_nonce.owner == program_id {
+        return Err(OptionsError::QuoteAlreadyAccepted.into());
+    }
+
+    // the maker's accounts only pay what it signed for, through its own quote authority
+    if premium_source_info.mint != cd.premium_type || collateral_source_info.mint != collateral_type
+    {
+        return Err(OptionsError::IncorrectTempType.into());
+    }
+    if premium_source_info.owner != buyerk || collateral_source_info.owner != writerk {
+        return Err(OptionsError::IncorrectTempAccount.into());
+    }
+    if premium_source_info.amount < premium || collateral_source_info.amount < collateral {
+        return Err(OptionsError::IncorrectTempBalance.into());
+    }
+    if buyer_receive_info.mint != buyer_rec_type {
+        return Err(OptionsError::IncorrectReceiveType.into());
+    }
+    if prem_receive_info.mint != cd.premium_type {
+        return Err(OptionsError::IncorrectPremiumReceiveType.into());
+    }
+
+    let series_seed = cd.series_seed();
+    let (mint_pdak, mint_bump) = instruction::get_mint_pda(program_id, ct, &series_seed);
+    if mint_pdak != *mint_pda.key {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    if *holder_mint.key != mint_pda_data.holder_mint {
+        return Err(OptionsError::IncorrectHolderMint.into());
+    }
+    if *holder_ata.key != get_associated_token_address(&buyerk, holder_mint.key) {
+        return Err(OptionsError::IncorrectHolderAta.into());
+    }
+
+    let seed = cd.get_seed();
+    let index_seed = get_seed(&quote.index_data());
+    let (pda, bump) = Pubkey::find_program_address(&[&seed, &index_seed], program_id);
+    if *data_pda.key != pda {
+        return Err(OptionsError::IncorrectDataPda.into());
+    }
+    if !data_pda.try_data_is_empty()? {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    msg!("building PDA data...");
+    let pda_data = ContractPDA {
+        contract_data: quote.contract_data.clone(),
+        contract_state: ContractState::FINAL,
+        buyer_data: Some(PartyData {
+            party_pub: buyerk,
+            temp_pub: *premium_source.key,
+            receive_pub: *buyer_receive.key,
+            receive_ata: *holder_ata.key,
+        }),
+        writer_data: Some(PartyData {
+            party_pub: writerk,
+            temp_pub: *asset_temp.key,
+            receive_pub: *writer_receive.key,
+            receive_ata: *prem_receive_acc.key,
+        }),
+        is_initialised: true,
+        bump,
+        seed,
+        init_party: quote.party,
+        contract_type: ct,
+        index_seed,
+        open_lots: lots,
+        unfilled_lots: 0,
+        writer_lots: lots,
+        series_seed,
+        offer_expiry: None,
+        counterparty: None,
+        offer_seq: 0,
+        settle_payout: 0,
+    };
+    if let Some(ata_program) = ata_program {
+        if !spl_associated_token_account::check_id(ata_program.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        create_contract_writer_mint(
+            program_id,
+            &pda_data,
+            &pda,
+            taker,
+            writer_mint,
+            mint_pda,
+            sys_program,
+        )?;
+        // the writer could not open an account for a mint that did not exist yet
+        let ix =
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                taker.key,
+                &writerk,
+                writer_mint.key,
+                &spl_token::id(),
+            );
+        invoke(
+            &ix,
+            &[
+                taker.clone(),
+                writer_ata.clone(),
+                writer.clone(),
+                writer_mint.clone(),
+                sys_program.clone(),
+                token_program.clone(),
+                ata_program.clone(),
+            ],
+        )?;
+    }
+    check_writer_mint(
+        program_id,
+        &pda_data,
+        &pda,
+        writer_mint,
+        writer_ata,
+        &writerk,
+    )?;
+
+    match vault_mints {
+        Some([collateral_mint, proceeds_mint]) => create_series_vaults(
+            program_id,
+            &pda_data,
+            taker,
+            [asset_temp, writer_receive],
+            [collateral_mint, proceeds_mint],
+            mint_pda,
+            sys_program,
+        )?,
+        // escrowed for writer token holders like the accounts of an accepted offer
+        None => {
+            let asset_temp_info = unpack_token_account(asset_temp)?;
+            let writer_receive_info = unpack_token_account(writer_receive)?;
+            if asset_temp_info.owner != pda
+                || asset_temp_info.mint != collateral_type
+                || asset_temp_info.amount != 0
+            {
+                return Err(OptionsError::IncorrectTempAccount.into());
+            }
+            if writer_receive_info.owner != pda
+                || writer_receive_info.mint != writer_rec_type
+                || writer_receive_info.amount != 0
+            {
+                return Err(OptionsError::IncorrectWriterReceive.into());
+            }
+        }
+    }
+
+    let authority_seeds: &[&[u8]] = &[quote.maker.as_ref(), b"quote", &[authority_bump]];
+    let (premium_authority, collateral_authority) = match maker_is_buyer {
+        true => (quote_authority, taker),
+        false => (taker, quote_authority),
+    };
+    msg!("transferring premium to writer...");
+    transfer_quoted(
+        token_program,
+        premium_source,
+        prem_receive_acc,
+        premium_authority,
+        authority_seeds,
+        premium,
+    )?;
+    msg!("transferring collateral to escrow...");
+    transfer_quoted(
+        token_program,
+        collateral_source,
+        asset_temp,
+        collateral_authority,
+        authority_seeds,
+        collateral,
+    )?;
+
+    let mint_seeds: &[&[u8]] = &[&[ct as u8], &series_seed, &[mint_bump]];
+    msg!("minting holder tokens");
+    let ix = spl_token::instruction::mint_to(
+        token_program.key,
+        holder_mint.key,
+        holder_ata.key,
+        mint_pda.key,
+        &[mint_pda.key],
+        lots,
+    )?;
+    invoke_signed(
+        &ix,
+        &[holder_mint.clone(), holder_ata.clone(), mint_pda.clone()],
+        &[mint_seeds],
+    )?;
+    msg!("minting writer tokens");
+    let ix = spl_token::instruction::mint_to(
+        token_program.key,
+        writer_mint.key,
+        writer_ata.key,
+        mint_pda.key,
+        &[mint_pda.key],
+        lots,
+    )?;
+    invoke_signed(
+        &ix,
+        &[writer_mint.clone(), writer_ata.clone(), mint_pda.clone()],
+        &[mint_seeds],
+    )?;
+    mint_pda_data.record_fill(lots)?;
+    mint_pda_data.pack_into_slice(*mint_pda.try_borrow_mut_data()?);
+
+    msg!("marking quote accepted...");
+    create_pda_account(
+        program_id,
+        taker,
+        quote_nonce,
+        0,
+        &[
+            quote.maker.as_ref(),
+            b"quote",
+            &quote.nonce.to_le_bytes(),
+            &[nonce_bump],
+        ],
+        sys_program,
+    )?;
+
+    // nothing of a pooled contract is left in escrow
+    if cd.pooled {
+        return Ok(());
+    }
+    msg!("creating PDA...");
+    create_pda_account(
+        program_id,
+        taker,
+        data_pda,
+        ContractPDA::LEN,
+        &[&seed, &index_seed, &[bump]],
+        sys_program,
+    )?;
+    pda_data.pack_into_slice(*data_pda.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+// create_account fails on an address that holds lamports, and anyone can send them to
+// a quote's addresses, so a funded account is topped up to rent and taken over instead
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+    sys_program: &AccountInfo<'a>,
+) -> Result<(), ProgramError> {
+    let min_rent = rent::Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        let ix = system_instruction::create_account(
+            payer.key,
+            account.key,
+            min_rent,
+            space as u64,
+            program_id,
+        );
+        return invoke_signed(
+            &ix,
+            &[payer.clone(), account.clone(), sys_program.clone()],
+            &[seeds],
+        );
+    }
+
+    let shortfall = min_rent.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        let ix = system_instruction::transfer(payer.key, account.key, shortfall);
+        invoke(&ix, &[payer.clone(), account.clone(), sys_program.clone()])?;
+    }
+    let ix = system_instruction::allocate(account.key, space as u64);
+    invoke_signed(&ix, &[account.clone(), sys_program.clone()], &[seeds])?;
+    let ix = system_instruction::assign(account.key, program_id);
+    invoke_signed(&ix, &[account.clone(), sys_program.clone()], &[seeds])
+}
+
+// a writer mint is a PDA so that no one has to hold its key
+fn create_writer_mint<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    writer_mint: &AccountInfo<'a>,
+    mint_pda: &AccountInfo<'a>,
+    sys_program: &AccountInfo<'a>,
+    seeds: &[&[u8]],
+) -> Result<(), ProgramError> {
+    let (writer_mintk, bump) = Pubkey::find_program_address(seeds, program_id);
+    if writer_mintk != *writer_mint.key {
+        return Err(OptionsError::IncorrectWriterMint.into());
+    }
+
+    msg!("creating writer mint account");
+    // a contract's writer mint has an address known ahead of it, like a quote's
+    let bump = [bump];
+    create_pda_account(
+        &spl_token::id(),
+        payer,
+        writer_mint,
+        spl_token::state::Mint::LEN,
+        &[seeds, &[&bump[..]]].concat(),
+        sys_program,
+    )?;
+
+    msg!("initialising writer mint account");
+    let ix = spl_token::instruction::initialize_mint2(
+        &spl_token::id(),
+        writer_mint.key,
+        mint_pda.key,
+        Some(mint_pda.key),
+        0,
+    )?;
+    invoke(&ix, std::slice::from_ref(writer_mint))
+}
+
+// the writer mint of a contract that is not pooled. one left behind at the address of a
+// closed contract is taken over, as long as none of its writer tokens are outstanding
+fn create_contract_writer_mint<'a>(
+    program_id: &Pubkey,
+    contract_pda: &ContractPDA,
+    data_pda: &Pubkey,
+    payer: &AccountInfo<'a>,
+    writer_mint: &AccountInfo<'a>,
+    mint_pda: &AccountInfo<'a>,
+    sys_program: &AccountInfo<'a>,
+) -> Result<(), ProgramError> {
+    let (mint_pdak, _bump) = instruction::get_mint_pda(
+        program_id,
+        contract_pda.contract_type,
+        &contract_pda.series_seed,
+    );
+    if *mint_pda.key != mint_pdak {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    if writer_mint.try_data_is_empty()? {
+        return create_writer_mint(
+            program_id,
+            payer,
+            writer_mint,
+            mint_pda,
+            sys_program,
+            &[data_pda.as_ref(), b"writer"],
+        );
+    }
+    if *writer_mint.key != instruction::get_contract_writer_mint(program_id, data_pda).0 {
+        return Err(OptionsError::IncorrectWriterMint.into());
+    }
+    check_owner(writer_mint, &spl_token::id())?;
+    if spl_token::state::Mint::unpack(&writer_mint.try_borrow_data()?)?.supply > 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    Ok(())
+}
+
+// the vaults of a pooled series are created by the first offer of the series
+fn create_series_vaults<'a>(
+    program_id: &Pubkey,
+    contract_pda: &ContractPDA,
+    payer: &AccountInfo<'a>,
+    vaults: [&AccountInfo<'a>; 2],
+    mints: [&AccountInfo<'a>; 2],
+    mint_pda: &AccountInfo<'a>,
+    sys_program: &AccountInfo<'a>,
+) -> Result<(), ProgramError> {
+    let ct = contract_pda.contract_type;
+    let cd = &contract_pda.contract_data;
+    let seed = &contract_pda.series_seed;
+    if instruction::get_mint_pda(program_id, ct, seed).0 != *mint_pda.key {
+        return Err(OptionsError::IncorrectMintPda.into());
+    }
+    let (collateral_type, _) = cd.collateral(ct);
+    let (_, proceeds_type) = cd.receive_types(ct);
+    let s1 = [ct as u8];
+    for ((vault, mint), (label, mint_type)) in vaults.iter().zip(mints).zip([
+        (b"vault" as &[u8], collateral_type),
+        (b"proceeds", proceeds_type),
+    ]) {
+        let (vaultk, bump) = Pubkey::find_program_address(&[&s1, seed, label], program_id);
+        if vaultk != *vault.key || *mint.key != mint_type {
+            return Err(OptionsError::IncorrectSeriesVault.into());
+        }
+        if !vault.try_data_is_empty()? {
+            continue;
+        }
+
+        msg!("creating series vault account");
+        let len = spl_token::state::Account::LEN;
+        let min_rent = rent::Rent::get()?.minimum_balance(len);
+        let ix = system_instruction::create_account(
+            payer.key,
+            vault.key,
+            min_rent,
+            len as u64,
+            &spl_token::id(),
+        );
+        invoke_signed(
+            &ix,
+            &[payer.clone(), (*vault).clone(), sys_program.clone()],
+            &[&[&s1, seed, label, &[bump]]],
+        )?;
+
+        msg!("initialising series vault account");
+        let ix = spl_token::instruction::initialize_account3(
+            &spl_token::id(),
+            vault.key,
+            mint.key,
+            mint_pda.key,
+        )?;
+        invoke(&ix, &[(*vault).clone(), (*mint).clone()])?;
+    }
+    Ok(())
+}
+
+// the vaults are only ever created by create_series_vaults, so their address is enough
+fn check_series_vault(
+    program_id: &Pubkey,
+    contract_type: ContractType,
+    seed: &[u8; 32],
+    vault: &AccountInfo,
+    label: &[u8],
+) -> Result<(), ProgramError> {
+    let s1 = [contract_type as u8];
+    let (vaultk, _) = Pubkey::find_program_address(&[&s1, seed, label], program_id);
+    if vaultk != *vault.key {
+        return Err(OptionsError::IncorrectSeriesVault.into());
+    }
+    check_owner(vault, &spl_token::id())
+}
+
+// the instruction before this one must verify the maker's signature of exactly this
+// message with the Ed25519 program, taking its key, signature and message from its own data
+fn check_quote_signature(
+    instructions_sysvar: &AccountInfo,
+    maker: &Pubkey,
+    message: &[u8],
+) -> Result<(), ProgramError> {
+    if !instructions::check_id(instructions_sysvar.key) {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let index = instructions::load_current_index_checked(instructions_sysvar)?;
+    let index = index
+        .checked_sub(1)
+        .ok_or(OptionsError::InvalidQuoteSignature)?;
+    let ix = instructions::load_instruction_at_checked(index as usize, instructions_sysvar)?;
+    let data = &ix.data;
+    if !ed25519_program::check_id(&ix.program_id) || data.len() < 16 || data[0] != 1 {
+        return Err(OptionsError::InvalidQuoteSignature.into());
+    }
+    let offset = |i: usize| u16::from_le_bytes([data[2 + 2 * i], data[3 + 2 * i]]) as usize;
+    let (key_offset, message_offset, message_size) = (offset(2), offset(4), offset(5));
+    let in_place = [offset(1), offset(3), offset(6)]
+        .iter()
+        .all(|ix_index| *ix_index == u16::MAX as usize);
+    if !in_place
+        || data.get(key_offset..key_offset + 32) != Some(maker.as_ref())
+        || data.get(message_offset..message_offset + message_size) != Some(message)
+    {
+        return Err(OptionsError::InvalidQuoteSignature.into());
+    }
+    Ok(())
+}
+
+// the taker signs for its own accounts, the quote authority for the maker's
+fn transfer_quoted<'a>(
+    token_program: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    dest: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    authority_seeds: &[&[u8]],
+    amount: u64,
+) -> Result<(), ProgramError> {
+    let ix = spl_token::instruction::transfer(
+        token_program.key,
+        source.key,
+        dest.key,
+        authority.key,
+        &[authority.key],
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[
+            source.clone(),
+            dest.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ],
+        &[authority_seeds],
+    )
+}
+
+fn close_data_pda(data_pda: &AccountInfo, send_to: &AccountInfo) -> Result<(), ProgramError> {
+    msg!("zeroing PDA account data...");
+    *data_pda.data.borrow_mut() = &mut [];
+    msg!("transferring rent from PDA to initialiser...");
+    **send_to.try_borrow_mut_lamports()? += data_pda.try_lamports()?;
+    **data_pda.try_borrow_mut_lamports()? = 0;
+    msg!("PDA account closed");
+    Ok(())
+}
+
+// physical delivery swaps holder_temp against the collateral, cash settlement splits
+// the collateral between holder and writer at the oracle price; returns what moves from
+// holder_temp, what collateral goes back to the writer side and what goes to the holder
+fn exercise_amounts<'a, 'b: 'a>(
+    program_id: &Pubkey,
+    cd: &ContractData,
+    ct: ContractType,
+    lots: u64,
+    holder_temp_info: &spl_token::state::Account,
+    accounts: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+) -> Result<(u64, u64, u64), ProgramError> {
+    Ok(match cd.settlement {
+        SettlementType::PHYSICAL => {
+            let (temp_type, temp_qty, rec_qty) = match ct {
+                CALL => (cd.strike_type, cd.strike_qty, cd.token_qty),
+                PUT => (cd.token_type, cd.token_qty, cd.strike_qty),
+            };
+            let temp_qty = lot_qty(temp_qty, lots)?;
+            if holder_temp_info.mint != temp_type {
+                return Err(OptionsError::IncorrectTempType.into());
+            }
+            if holder_temp_info.amount < temp_qty {
+                return Err(OptionsError::IncorrectTempBalance.into());
+            }
+            (temp_qty, 0, lot_qty(rec_qty, lots)?)
+        }
+        SettlementType::CASH => {
+            let oracle_acc = next_account_info(accounts)?;
+            if cd.oracle != Some(*oracle_acc.key) {
+                return Err(OptionsError::InvalidOracle.into());
+            }
+            let price = oracle::load_price(program_id, oracle_acc, Clock::get()?.unix_timestamp)?;
+            msg!("oracle price: {} / 10^{}", price.price, price.decimals);
+            let payout = lot_qty(oracle::intrinsic_value(cd, ct, &price)?, lots)?;
+            let collateral = lot_qty(cd.collateral(ct).1, lots)?;
+            (0, collateral - payout, payout)
+        }
+    })
+}
+
+fn resize_account<'a>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    sys_program: &AccountInfo<'a>,
+    len: usize,
+) -> Result<(), ProgramError> {
+    let min_rent = rent::Rent::get()?.minimum_balance(len);
+    let lamports = account.lamports();
+    if min_rent > lamports {
+        let ix = system_instruction::transfer(payer.key, account.key, min_rent - lamports);
+        invoke(&ix, &[payer.clone(), account.clone(), sys_program.clone()])?;
+    }
+    account.realloc(len, false)
+}
+
+// moves lots from the standing offer to the open contract
+fn fill_lots(contract_pda: &mut ContractPDA, lots: u64) -> Result<(), ProgramError> {
+    if lots == 0 || lots > contract_pda.unfilled_lots {
+        return Err(OptionsError::InvalidLotCount.into());
+    }
+    contract_pda.unfilled_lots -= lots;
+    contract_pda.open_lots += lots;
+    contract_pda.contract_state = match contract_pda.unfilled_lots {
+        0 => ContractState::FINAL,
+        _ => ContractState::PARTIALLY_FILLED,
+    };
+    Ok(())
+}
+
+fn lot_qty(qty: u64, lots: u64) -> Result<u64, ProgramError> {
+    qty.checked_mul(lots)
+        .ok_or_else(|| OptionsError::InvalidLotCount.into())
+}
+
+fn current_time_ms() -> Result<i64, ProgramError> {
+    Ok(Clock::get()?.unix_timestamp * 1000)
+}
+
+// the clock expiry_date is measured against
+fn expiry_clock(contract_data: &ContractData) -> Result<i64, ProgramError> {
+    match contract_data.expiry_type {
+        ExpiryType::TIMESTAMP => current_time_ms(),
+        ExpiryType::SLOT => Ok(Clock::get()?.slot as i64),
+    }
+}
+
+fn check_settlement(contract_data: &ContractData) -> Result<(), ProgramError> {
+    match (contract_data.settlement, contract_data.oracle) {
+        (SettlementType::PHYSICAL, None) | (SettlementType::CASH, Some(_)) => (),
+        _ => return Err(OptionsError::InvalidOracle.into()),
+    };
+    // physical delivery needs the holder's strike, nobody can exercise on their behalf
+    if contract_data.auto_exercise && contract_data.settlement != SettlementType::CASH {
+        return Err(OptionsError::InvalidSettlement.into());
+    }
+    // a pooled series is only settled by its holders, lot by lot
+    if contract_data.auto_exercise && contract_data.pooled {
+        return Err(OptionsError::InvalidSettlement.into());
+    }
+    // a price carries only its publish time, which cannot be placed against an expiry slot
+    if contract_data.auto_exercise && contract_data.expiry_type == ExpiryType::SLOT {
+        return Err(OptionsError::InvalidSettlement.into());
+    }
+    Ok(())
+}
+
+// American contracts exercise any time before expiry, European ones from exercise_start,
+// Bermudan ones only inside a window of their schedule
+fn check_exercise_window(contract_data: &ContractData) -> Result<(), ProgramError> {
+    let windows = contract_data.exercise_schedule.windows();
+    let valid = match (contract_data.contract_style, contract_data.exercise_start) {
+        (AMERICAN, None) => windows.is_empty(),
+        (EUROPEAN, Some(start)) => windows.is_empty() && start <= contract_data.expiry_date,
+        (BERMUDAN, None) => {
+            !windows.is_empty()
+                && windows.iter().all(|w| w.start <= w.end)
+                && windows.windows(2).all(|pair| pair[0].end < pair[1].start)
+                && windows.iter().all(|w| w.end <= contract_data.expiry_date)
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(OptionsError::InvalidExerciseWindow.into());
+    }
+    Ok(())
+}
+
+fn exercise_window_open(contract_data: &ContractData, time: i64) -> bool {
+    match contract_data.contract_style {
+        AMERICAN => true,
+        EUROPEAN => !matches!(contract_data.exercise_start, Some(start) if time < start),
+        BERMUDAN => contract_data
+            .exercise_schedule
+            .windows()
+            .iter()
+            .any(|w| w.start <= time && time <= w.end),
+    }
+}
+
+// writer tokens go to the writer's associated account
+fn check_writer_mint(
+    program_id: &Pubkey,
+    contract_pda: &ContractPDA,
+    data_pda: &Pubkey,
+    writer_mint: &AccountInfo,
+    writer_ata: &AccountInfo,
+    writer: &Pubkey,
+) -> Result<(), ProgramError> {
+    let (writer_mintk, _bump) = instruction::get_writer_mint_of(program_id, contract_pda, data_pda);
+    if *writer_mint.key != writer_mintk {
+        return Err(OptionsError::IncorrectWriterMint.into());
+    }
+    if writer_mint.try_data_is_empty()? {
+        return Err(OptionsError::OutdatedAccountLayout.into());
+    }
+    check_owner(writer_mint, &spl_token::id())?;
+    check_owner(writer_ata, &spl_token::id())?;
+    if *writer_ata.key != get_associated_token_address(writer, &writer_mintk) {
+        return Err(OptionsError::IncorrectWriterAta.into());
+    }
+    Ok(())
+}
+
+// a private offer can only be taken by the key it names
+fn check_counterparty(contract_pda: &ContractPDA, taker: &AccountInfo) -> Result<(), ProgramError> {
+    match contract_pda.counterparty {
+        Some(counterparty) if counterparty != *taker.key => {
+            Err(OptionsError::CounterpartyNotAllowed.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_owner(account: &AccountInfo, owner: &Pubkey) -> Result<(), ProgramError> {
+    if account.owner != owner {
+        msg!("{} not owned by {}", account.key, owner);
+        return Err(ProgramError::IllegalOwner);
+    }
+    Ok(())
+}
+
+// a spoofed account can carry the same bytes, only the owner proves who wrote them
+fn unpack_token_account(account: &AccountInfo) -> Result<spl_token::state::Account, ProgramError> {
+    check_owner(account, &spl_token::id())?;
+    spl_token::state::Account::unpack_from_slice(*account.try_borrow_data()?)
+}
+
+// stored pubkeys only mean something if the account lives at the address its seeds derive
+fn check_data_pda(
+    program_id: &Pubkey,
+    contract_pda: &ContractPDA,
+    data_pda: &AccountInfo,
+) -> Result<(), ProgramError> {
+    let pda = Pubkey::create_program_address(
+        &[
+            &contract_pda.seed,
+            &contract_pda.index_seed,
+            &[contract_pda.bump],
+        ],
+        program_id,
+    )
+    .map_err(|_| OptionsError::IncorrectDataPda)?;
+    if pda != *data_pda.key {
+        return Err(OptionsError::IncorrectDataPda.into());
+    }
+    Ok(())
+}
+
+fn unpack_contract_pda(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<ContractPDA, ProgramError> {
+    check_owner(account, program_id)?;
+    ContractPDA::unpack_from_slice(*account.try_borrow_data()?)
+}
+
+fn unpack_mint_pda(program_id: &Pubkey, account: &AccountInfo) -> Result<MintPDA, ProgramError> {
+    check_owner(account, program_id)?;
+    MintPDA::unpack_from_slice(*account.try_borrow_data()?)
+}
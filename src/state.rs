@@ -1,5 +1,6 @@
+use crate::error::OptionsError;
 use crate::instruction::InitParty;
-use arrayref::{array_refs, mut_array_refs};
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use sha2::{Digest, Sha256};
 use solana_program::{
     program_error::ProgramError,
@@ -20,32 +21,395 @@ pub struct ContractPDA {
     pub bump: u8,
     pub init_party: InitParty,
     pub contract_type: ContractType,
+    // lots neither exercised nor expired, one holder token each
+    pub open_lots: u64,
+    // lots of the offer nobody has taken yet
+    pub unfilled_lots: u64,
+    // writer tokens minted against this contract and not yet redeemed, zero for
+    // contracts written before the writer mint
+    pub writer_lots: u64,
+    // seed of the series mint_pda, writer mint and vaults: the economic terms, or
+    // for contracts written before premium left the seed, get_seed
+    pub series_seed: [u8; 32],
+    // same unit as expiry_date, after which the unfilled lots can no longer be taken
+    // and anyone may cancel them; None for offers open until the option expires
+    pub offer_expiry: Option<i64>,
+    // the only key that may take the offer, for negotiated trades; None for public offers
+    pub counterparty: Option<Pubkey>,
+    // amendments made to the offer, so a taker can pin the terms it priced
+    pub offer_seq: u64,
+    // owed to each open lot of a contract settled in the money at expiry, paid to
+    // whoever burns a holder token for it
+    pub settle_payout: u64,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct MintPDA {
     pub holder_mint: Pubkey,
+    // the terms of the series, with the premium and lots zeroed as they are per offer;
+    // series keyed before premium left the seed keep their premium_type
+    pub contract_type: ContractType,
+    pub contract_data: ContractData,
+    // unix timestamp, seconds; the migration time for series created before the registry
+    pub created_at: i64,
+    // lots filled and not yet exercised, expired or closed out
+    pub open_interest: u64,
+    pub total_exercised: u64,
+    pub total_expired: u64,
 }
 impl Sealed for MintPDA {}
 
+impl MintPDA {
+    pub const DISCRIMINATOR: [u8; 8] = *b"optmint\0";
+    pub const VERSION: u8 = 1;
+    pub const LEGACY_LEN: usize = 32;
+
+    pub fn new(
+        holder_mint: Pubkey,
+        contract_type: ContractType,
+        contract_data: &ContractData,
+        created_at: i64,
+    ) -> Self {
+        MintPDA {
+            holder_mint,
+            contract_type,
+            contract_data: ContractData {
+                premium_type: Pubkey::default(),
+                premium_qty: 0,
+                lots: 0,
+                ..contract_data.clone()
+            },
+            created_at,
+            open_interest: 0,
+            total_exercised: 0,
+            total_expired: 0,
+        }
+    }
+
+    // the pre-header layout held nothing but the holder mint
+    pub fn unpack_legacy(src: &[u8]) -> Result<Pubkey, ProgramError> {
+        let src: &[u8; MintPDA::LEGACY_LEN] = src
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        Ok(Pubkey::new_from_array(*src))
+    }
+
+    pub fn record_fill(&mut self, lots: u64) -> Result<(), ProgramError> {
+        self.open_interest = checked_total(self.open_interest, lots)?;
+        Ok(())
+    }
+
+    // the counters are informational, so a series migrated with too little open
+    // interest must not stop its lots from settling
+    pub fn record_exercise(&mut self, lots: u64) -> Result<(), ProgramError> {
+        self.open_interest = self.open_interest.saturating_sub(lots);
+        self.total_exercised = checked_total(self.total_exercised, lots)?;
+        Ok(())
+    }
+
+    pub fn record_expiry(&mut self, lots: u64) -> Result<(), ProgramError> {
+        self.open_interest = self.open_interest.saturating_sub(lots);
+        self.total_expired = checked_total(self.total_expired, lots)?;
+        Ok(())
+    }
+
+    pub fn record_close_out(&mut self, lots: u64) {
+        self.open_interest = self.open_interest.saturating_sub(lots);
+    }
+}
+
+fn checked_total(total: u64, lots: u64) -> Result<u64, ProgramError> {
+    total
+        .checked_add(lots)
+        .ok_or_else(|| OptionsError::Overflow.into())
+}
+
 impl Pack for MintPDA {
-    const LEN: usize = 32;
+    const LEN: usize = HEADER_LEN + 65 + ContractData::LEN;
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src: &[u8; 32] = src
+        if src.len() == MintPDA::LEGACY_LEN {
+            return Err(OptionsError::OutdatedAccountLayout.into());
+        }
+        let src: &[u8; MintPDA::LEN - HEADER_LEN] =
+            unpack_header(src, &MintPDA::DISCRIMINATOR, MintPDA::VERSION)?
+                .try_into()
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+        let (
+            holder_mint,
+            contract_type,
+            contract_data,
+            created_at,
+            open_interest,
+            total_exercised,
+            total_expired,
+        ) = array_refs![src, 32, 1, ContractData::LEN, 8, 8, 8, 8];
+        let contract_type = match contract_type[0] {
+            0 => ContractType::CALL,
+            1 => ContractType::PUT,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(MintPDA {
+            holder_mint: Pubkey::new_from_array(*holder_mint),
+            contract_type,
+            contract_data: ContractData::deserialize(contract_data)?,
+            created_at: i64::from_le_bytes(*created_at),
+            open_interest: u64::from_le_bytes(*open_interest),
+            total_exercised: u64::from_le_bytes(*total_exercised),
+            total_expired: u64::from_le_bytes(*total_expired),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        pack_header(dst, &MintPDA::DISCRIMINATOR, MintPDA::VERSION);
+        let dst: &mut [u8; MintPDA::LEN - HEADER_LEN] =
+            (&mut dst[HEADER_LEN..]).try_into().unwrap();
+        let (
+            holder_mint,
+            contract_type,
+            contract_data,
+            created_at,
+            open_interest,
+            total_exercised,
+            total_expired,
+        ) = mut_array_refs![dst, 32, 1, ContractData::LEN, 8, 8, 8, 8];
+        holder_mint.copy_from_slice(&self.holder_mint.to_bytes());
+        contract_type[0] = match self.contract_type {
+            ContractType::CALL => 0,
+            ContractType::PUT => 1,
+        };
+        contract_data.copy_from_slice(&self.contract_data.serialize());
+        created_at.copy_from_slice(&self.created_at.to_le_bytes());
+        open_interest.copy_from_slice(&self.open_interest.to_le_bytes());
+        total_exercised.copy_from_slice(&self.total_exercised.to_le_bytes());
+        total_expired.copy_from_slice(&self.total_expired.to_le_bytes());
+    }
+}
+
+// a resting offer of an order book, priced like the offer's premium_qty
+#[derive(Debug, PartialEq, Clone)]
+pub struct Order {
+    pub premium_qty: u64,
+    pub lots: u64,
+    pub data_pda: Pubkey,
+}
+
+impl Order {
+    const LEN: usize = 48;
+}
+
+// index of the pooled offers of one series paid for in one premium type, each side
+// best price first and oldest first within a price level
+#[derive(Debug, PartialEq)]
+pub struct OrderBook {
+    // the terms of the series and the premium type, with premium_qty and lots zeroed
+    pub contract_type: ContractType,
+    pub contract_data: ContractData,
+    pub bids: Vec<Order>,
+    pub asks: Vec<Order>,
+}
+impl Sealed for OrderBook {}
+
+impl OrderBook {
+    pub const DISCRIMINATOR: [u8; 8] = *b"optbook\0";
+    pub const VERSION: u8 = 1;
+    pub const MAX_ORDERS: usize = 16;
+
+    pub fn new(contract_type: ContractType, contract_data: &ContractData) -> Self {
+        OrderBook {
+            contract_type,
+            contract_data: ContractData {
+                premium_qty: 0,
+                lots: 0,
+                ..contract_data.clone()
+            },
+            bids: vec![],
+            asks: vec![],
+        }
+    }
+
+    // orders an incoming offer of `party` could cross
+    pub fn opposite(&mut self, party: &InitParty) -> &mut Vec<Order> {
+        match party {
+            InitParty::BUYER => &mut self.asks,
+            InitParty::WRITER => &mut self.bids,
+        }
+    }
+
+    // a full side makes room for a better priced order by evicting its worst, so
+    // cheap orders cannot lock the book; the evicted offer stays open outside it
+    pub fn insert(
+        &mut self,
+        party: &InitParty,
+        order: Order,
+    ) -> Result<Option<Order>, ProgramError> {
+        let side = match party {
+            InitParty::BUYER => &mut self.bids,
+            InitParty::WRITER => &mut self.asks,
+        };
+        let at = side
+            .iter()
+            .position(|o| match party {
+                InitParty::BUYER => o.premium_qty < order.premium_qty,
+                InitParty::WRITER => o.premium_qty > order.premium_qty,
+            })
+            .unwrap_or(side.len());
+        if side.len() == OrderBook::MAX_ORDERS && at == side.len() {
+            return Err(OptionsError::OrderBookFull.into());
+        }
+        side.insert(at, order);
+        Ok(match side.len() > OrderBook::MAX_ORDERS {
+            true => side.pop(),
+            false => None,
+        })
+    }
+
+    pub fn remove(&mut self, data_pda: &Pubkey) -> Option<Order> {
+        for side in [&mut self.bids, &mut self.asks] {
+            if let Some(at) = side.iter().position(|o| o.data_pda == *data_pda) {
+                return Some(side.remove(at));
+            }
+        }
+        None
+    }
+}
+
+impl Pack for OrderBook {
+    const LEN: usize = HEADER_LEN + 3 + ContractData::LEN + 2 * OrderBook::MAX_ORDERS * Order::LEN;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = unpack_header(src, &OrderBook::DISCRIMINATOR, OrderBook::VERSION)?;
+        if src.len() != OrderBook::LEN - HEADER_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (head, orders) = src.split_at(3 + ContractData::LEN);
+        let contract_type = match head[0] {
+            0 => ContractType::CALL,
+            1 => ContractType::PUT,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let (bid_count, ask_count) = (head[1] as usize, head[2] as usize);
+        if bid_count > OrderBook::MAX_ORDERS || ask_count > OrderBook::MAX_ORDERS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (bids, asks) = orders.split_at(OrderBook::MAX_ORDERS * Order::LEN);
+        let unpack_orders = |src: &[u8], count: usize| -> Vec<Order> {
+            src.chunks(Order::LEN)
+                .take(count)
+                .map(|o| {
+                    let o = array_ref![o, 0, 48];
+                    let (premium_qty, lots, data_pda) = array_refs![o, 8, 8, 32];
+                    Order {
+                        premium_qty: u64::from_le_bytes(*premium_qty),
+                        lots: u64::from_le_bytes(*lots),
+                        data_pda: Pubkey::new_from_array(*data_pda),
+                    }
+                })
+                .collect()
+        };
+
+        Ok(OrderBook {
+            contract_type,
+            contract_data: ContractData::deserialize(&head[3..])?,
+            bids: unpack_orders(bids, bid_count),
+            asks: unpack_orders(asks, ask_count),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        pack_header(dst, &OrderBook::DISCRIMINATOR, OrderBook::VERSION);
+        let dst = &mut dst[HEADER_LEN..OrderBook::LEN];
+        let (head, orders) = dst.split_at_mut(3 + ContractData::LEN);
+        head[0] = match self.contract_type {
+            ContractType::CALL => 0,
+            ContractType::PUT => 1,
+        };
+        head[1] = self.bids.len() as u8;
+        head[2] = self.asks.len() as u8;
+        head[3..].copy_from_slice(&self.contract_data.serialize());
+        // slots past the last order are zeroed so a removed order leaves nothing behind
+        orders.fill(0);
+        let (bids, asks) = orders.split_at_mut(OrderBook::MAX_ORDERS * Order::LEN);
+        for (side, dst) in [(&self.bids, bids), (&self.asks, asks)] {
+            for (order, dst) in side.iter().zip(dst.chunks_mut(Order::LEN)) {
+                let dst = array_mut_ref![dst, 0, 48];
+                let (premium_qty, lots, data_pda) = mut_array_refs![dst, 8, 8, 32];
+                premium_qty.copy_from_slice(&order.premium_qty.to_le_bytes());
+                lots.copy_from_slice(&order.lots.to_le_bytes());
+                data_pda.copy_from_slice(&order.data_pda.to_bytes());
+            }
+        }
+    }
+}
+
+// price account owned by this program, read through oracle::TestOracle. the program
+// never writes one, so only solana-program-test can give it data
+#[cfg(feature = "test-bpf")]
+#[derive(Debug, PartialEq)]
+pub struct TestOracle {
+    // strike_type base units per 10^decimals token_type base units
+    pub price: u64,
+    pub decimals: u8,
+    // unix timestamp, seconds
+    pub publish_time: i64,
+}
+#[cfg(feature = "test-bpf")]
+impl Sealed for TestOracle {}
+
+#[cfg(feature = "test-bpf")]
+impl TestOracle {
+    pub const DISCRIMINATOR: [u8; 8] = *b"optorcl\0";
+    pub const VERSION: u8 = 1;
+}
+
+#[cfg(feature = "test-bpf")]
+impl Pack for TestOracle {
+    const LEN: usize = HEADER_LEN + 17;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src: &[u8; 17] = unpack_header(src, &TestOracle::DISCRIMINATOR, TestOracle::VERSION)?
             .try_into()
             .map_err(|_| ProgramError::InvalidAccountData)?;
-        let holder_mint = Pubkey::new(&src[..32]);
-
-        Ok(MintPDA { holder_mint })
+        let (price, decimals, publish_time) = array_refs![src, 8, 1, 8];
+        Ok(TestOracle {
+            price: u64::from_le_bytes(*price),
+            decimals: decimals[0],
+            publish_time: i64::from_le_bytes(*publish_time),
+        })
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        dst[..32].copy_from_slice(&self.holder_mint.to_bytes()[..]);
+        pack_header(dst, &TestOracle::DISCRIMINATOR, TestOracle::VERSION);
+        let dst: &mut [u8; 17] = (&mut dst[HEADER_LEN..]).try_into().unwrap();
+        let (price, decimals, publish_time) = mut_array_refs![dst, 8, 1, 8];
+        price.copy_from_slice(&self.price.to_le_bytes());
+        decimals[0] = self.decimals;
+        publish_time.copy_from_slice(&self.publish_time.to_le_bytes());
     }
 }
 
-impl Sealed for ContractPDA {}
+// account header: [0..8 = discriminator, 8 = layout version]
+pub const HEADER_LEN: usize = 9;
+
+fn unpack_header<'a>(
+    src: &'a [u8],
+    discriminator: &[u8; 8],
+    version: u8,
+) -> Result<&'a [u8], ProgramError> {
+    if src.len() < HEADER_LEN || src[..8] != discriminator[..] {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if src[8] != version {
+        return Err(OptionsError::OutdatedAccountLayout.into());
+    }
+    Ok(&src[HEADER_LEN..])
+}
+
+fn pack_header(dst: &mut [u8], discriminator: &[u8; 8], version: u8) {
+    dst[..8].copy_from_slice(discriminator);
+    dst[8] = version;
+}
 
 impl IsInitialized for ContractPDA {
     fn is_initialized(&self) -> bool {
@@ -53,30 +417,117 @@ impl IsInitialized for ContractPDA {
     }
 }
 
-impl Pack for ContractPDA {
-    const LEN: usize = 421;
+// not Pack: packing can fail on malformed party/contract data
+impl ContractPDA {
+    pub const DISCRIMINATOR: [u8; 8] = *b"optcntrc";
+    pub const VERSION: u8 = 1;
+    pub const LEGACY_LEN: usize = 1 + ContractData::LEGACY_LEN + 292;
+    pub const LEN: usize = HEADER_LEN + 1 + ContractData::LEN + 292 + 114;
 
-    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        if src.len() != ContractPDA::LEN {
+    // `time` on the clock of expiry_date
+    pub fn offer_expired(&self, time: i64) -> bool {
+        matches!(self.offer_expiry, Some(expiry) if time > expiry)
+    }
+
+    pub fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() == ContractPDA::LEGACY_LEN {
+            return Err(OptionsError::OutdatedAccountLayout.into());
+        }
+        Self::unpack_body(unpack_header(
+            src,
+            &ContractPDA::DISCRIMINATOR,
+            ContractPDA::VERSION,
+        )?)
+    }
+
+    // pre-header layout, only read by MigrateContract
+    pub fn unpack_legacy(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != ContractPDA::LEGACY_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (head, tail) = src.split_at(1 + ContractData::LEGACY_LEN);
+        let contract_data = ContractData::deserialize_legacy(&head[1..])?;
+        // the series of a legacy contract was keyed by its premium type as well
+        let series_seed = contract_data.get_seed();
+        let mut contract_pda =
+            Self::unpack_parts(head[0], contract_data, tail, 0, 0, 0, series_seed)?;
+        // every legacy contract is a single lot, offered or taken whole
+        match contract_pda.contract_state {
+            ContractState::BID | ContractState::ASK => contract_pda.unfilled_lots = 1,
+            ContractState::FINAL => contract_pda.open_lots = 1,
+            ContractState::PARTIALLY_FILLED | ContractState::SETTLED => {
+                return Err(ProgramError::InvalidAccountData)
+            }
+        };
+        Ok(contract_pda)
+    }
+
+    fn unpack_body(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() != ContractPDA::LEN - HEADER_LEN {
             return Err(ProgramError::InvalidAccountData);
         }
-        let src: &[u8; ContractPDA::LEN] = src
+        let (head, tail) = src.split_at(1 + ContractData::LEN);
+        let (tail, lots) = tail.split_at(292);
+        let lots: &[u8; 114] = lots
             .try_into()
             .map_err(|_| ProgramError::InvalidAccountData)?;
         let (
-            is_initialised,
-            seed,
-            contract_state,
-            buyer_data,
-            writer_data,
-            bump,
-            init_party,
-            contract_type,
-            index_seed,
-        ) = array_refs![src, 1, 128, 1, 128, 128, 1, 1, 1, 32];
+            open_lots,
+            unfilled_lots,
+            writer_lots,
+            series_seed,
+            offer_expiry,
+            counterparty,
+            offer_seq,
+            settle_payout,
+        ) = array_refs![lots, 8, 8, 8, 32, 9, 33, 8, 8];
+        let (is_some, offer_expiry) = array_refs![offer_expiry, 1, 8];
+        let offer_expiry = match is_some[0] {
+            0 => None,
+            1 => Some(i64::from_le_bytes(*offer_expiry)),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let (is_some, counterparty) = array_refs![counterparty, 1, 32];
+        let counterparty = match is_some[0] {
+            0 => None,
+            1 => Some(Pubkey::new_from_array(*counterparty)),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(ContractPDA {
+            offer_expiry,
+            counterparty,
+            offer_seq: u64::from_le_bytes(*offer_seq),
+            settle_payout: u64::from_le_bytes(*settle_payout),
+            ..Self::unpack_parts(
+                head[0],
+                ContractData::deserialize(&head[1..])?,
+                tail,
+                u64::from_le_bytes(*open_lots),
+                u64::from_le_bytes(*unfilled_lots),
+                u64::from_le_bytes(*writer_lots),
+                *series_seed,
+            )?
+        })
+    }
+
+    // everything between contract_data and open_lots, unchanged since the legacy layout
+    fn unpack_parts(
+        is_initialised: u8,
+        contract_data: ContractData,
+        src: &[u8],
+        open_lots: u64,
+        unfilled_lots: u64,
+        writer_lots: u64,
+        series_seed: [u8; 32],
+    ) -> Result<Self, ProgramError> {
+        let src: &[u8; 292] = src
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let (contract_state, buyer_data, writer_data, bump, init_party, contract_type, index_seed) =
+            array_refs![src, 1, 128, 128, 1, 1, 1, 32];
         let bump = bump[0];
 
-        let is_initialised = match is_initialised[0] {
+        let is_initialised = match is_initialised {
             0 => false,
             1 => true,
             _ => return Err(ProgramError::InvalidAccountData),
@@ -97,24 +548,32 @@ impl Pack for ContractPDA {
         let (contract_state, buyer_data, writer_data) = match contract_state[0] {
             0 => (
                 ContractState::BID,
-                Some(PartyData::from_bytes(buyer_data)),
+                Some(PartyData::from_bytes(buyer_data)?),
                 None,
             ),
             1 => (
                 ContractState::ASK,
                 None,
-                Some(PartyData::from_bytes(writer_data)),
+                Some(PartyData::from_bytes(writer_data)?),
             ),
             2 => (
                 ContractState::FINAL,
-                Some(PartyData::from_bytes(buyer_data)),
-                Some(PartyData::from_bytes(writer_data)),
+                Some(PartyData::from_bytes(buyer_data)?),
+                Some(PartyData::from_bytes(writer_data)?),
+            ),
+            3 => (
+                ContractState::PARTIALLY_FILLED,
+                Some(PartyData::from_bytes(buyer_data)?),
+                Some(PartyData::from_bytes(writer_data)?),
+            ),
+            4 => (
+                ContractState::SETTLED,
+                Some(PartyData::from_bytes(buyer_data)?),
+                Some(PartyData::from_bytes(writer_data)?),
             ),
             _ => return Err(ProgramError::InvalidAccountData),
         };
 
-        let contract_data = ContractData::deserialize(seed);
-
         let seed = contract_data.get_seed();
 
         Ok(ContractPDA {
@@ -128,11 +587,25 @@ impl Pack for ContractPDA {
             init_party,
             contract_type,
             index_seed: *index_seed,
+            open_lots,
+            unfilled_lots,
+            writer_lots,
+            series_seed,
+            offer_expiry: None,
+            counterparty: None,
+            offer_seq: 0,
+            settle_payout: 0,
         })
     }
 
-    fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst: &mut [u8; ContractPDA::LEN] = dst.try_into().unwrap();
+    pub fn pack_into_slice(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() != ContractPDA::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        pack_header(dst, &ContractPDA::DISCRIMINATOR, ContractPDA::VERSION);
+        let dst: &mut [u8; ContractPDA::LEN - HEADER_LEN] = (&mut dst[HEADER_LEN..])
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
         let (
             is_initialised,
             contract_data,
@@ -143,7 +616,34 @@ impl Pack for ContractPDA {
             init_party,
             contract_type,
             index_seed,
-        ) = mut_array_refs![dst, 1, 128, 1, 128, 128, 1, 1, 1, 32];
+            open_lots,
+            unfilled_lots,
+            writer_lots,
+            series_seed,
+            offer_expiry,
+            counterparty,
+            offer_seq,
+            settle_payout,
+        ) = mut_array_refs![
+            dst,
+            1,
+            ContractData::LEN,
+            1,
+            128,
+            128,
+            1,
+            1,
+            1,
+            32,
+            8,
+            8,
+            8,
+            32,
+            9,
+            33,
+            8,
+            8
+        ];
 
         is_initialised[0] = match self.is_initialised {
             true => 1,
@@ -159,6 +659,8 @@ impl Pack for ContractPDA {
             ContractState::BID => 0,
             ContractState::ASK => 1,
             ContractState::FINAL => 2,
+            ContractState::PARTIALLY_FILLED => 3,
+            ContractState::SETTLED => 4,
         };
 
         contract_type[0] = match self.contract_type {
@@ -169,6 +671,26 @@ impl Pack for ContractPDA {
         contract_data.copy_from_slice(&self.contract_data.serialize());
         bump.copy_from_slice(&[self.bump]);
         index_seed.copy_from_slice(&self.index_seed);
+        open_lots.copy_from_slice(&self.open_lots.to_le_bytes());
+        unfilled_lots.copy_from_slice(&self.unfilled_lots.to_le_bytes());
+        writer_lots.copy_from_slice(&self.writer_lots.to_le_bytes());
+        series_seed.copy_from_slice(&self.series_seed);
+        match self.offer_expiry {
+            Some(expiry) => {
+                offer_expiry[0] = 1;
+                offer_expiry[1..].copy_from_slice(&expiry.to_le_bytes());
+            }
+            None => offer_expiry.copy_from_slice(&[0; 9]),
+        };
+        match self.counterparty {
+            Some(key) => {
+                counterparty[0] = 1;
+                counterparty[1..].copy_from_slice(key.as_ref());
+            }
+            None => counterparty.copy_from_slice(&[0; 33]),
+        };
+        offer_seq.copy_from_slice(&self.offer_seq.to_le_bytes());
+        settle_payout.copy_from_slice(&self.settle_payout.to_le_bytes());
 
         match &self.buyer_data {
             Some(bd) => {
@@ -183,6 +705,7 @@ impl Pack for ContractPDA {
             Some(wd) => writer_data.copy_from_slice(&wd.to_bytes()),
             None => writer_data.copy_from_slice(&[0; 128]),
         };
+        Ok(())
     }
 }
 
@@ -207,33 +730,19 @@ pub enum ContractType {
 }
 
 impl PartyData {
-    pub fn from_bytes(bytes: &[u8; 128]) -> Self {
-        PartyData {
-            party_pub: Pubkey::new_from_array(
-                bytes[0..32]
-                    .try_into()
-                    .map_err(|_| ProgramError::InvalidAccountData)
-                    .unwrap(),
-            ),
-            temp_pub: Pubkey::new_from_array(
-                bytes[32..64]
-                    .try_into()
-                    .map_err(|_| ProgramError::InvalidAccountData)
-                    .unwrap(),
-            ),
-            receive_pub: Pubkey::new_from_array(
-                bytes[64..96]
-                    .try_into()
-                    .map_err(|_| ProgramError::InvalidAccountData)
-                    .unwrap(),
-            ),
-            receive_ata: Pubkey::new_from_array(
-                bytes[96..]
-                    .try_into()
-                    .map_err(|_| ProgramError::InvalidAccountData)
-                    .unwrap(),
-            ),
-        }
+    pub const LEN: usize = 128;
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProgramError> {
+        let bytes: &[u8; Self::LEN] = bytes
+            .try_into()
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let (party_pub, temp_pub, receive_pub, receive_ata) = array_refs![bytes, 32, 32, 32, 32];
+
+        Ok(PartyData {
+            party_pub: Pubkey::new_from_array(*party_pub),
+            temp_pub: Pubkey::new_from_array(*temp_pub),
+            receive_pub: Pubkey::new_from_array(*receive_pub),
+            receive_ata: Pubkey::new_from_array(*receive_ata),
+        })
     }
     pub fn to_bytes(&self) -> [u8; 128] {
         [
@@ -248,31 +757,163 @@ impl PartyData {
     }
 }
 
+#[allow(non_camel_case_types)]
 #[derive(Debug, PartialEq)]
 pub enum ContractState {
     BID,
     ASK,
     FINAL,
+    // taken in part, the rest of the offer still stands
+    PARTIALLY_FILLED,
+    // every lot exercised or expired, writer tokens left to redeem
+    SETTLED,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExpiryType {
+    TIMESTAMP,
+    SLOT,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ContractStyle {
+    AMERICAN,
+    EUROPEAN,
+    BERMUDAN,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SettlementType {
+    PHYSICAL,
+    // pays the intrinsic value in strike_type, priced by the contract's oracle
+    CASH,
+}
+
+// inclusive, same unit as expiry_date
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExerciseWindow {
+    pub start: i64,
+    pub end: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExerciseSchedule(Vec<ExerciseWindow>);
+
+impl ExerciseSchedule {
+    pub const MAX_WINDOWS: usize = 12;
+    pub const LEN: usize = 1 + ExerciseSchedule::MAX_WINDOWS * 16;
+
+    pub fn new(windows: Vec<ExerciseWindow>) -> Result<Self, ProgramError> {
+        if windows.len() > ExerciseSchedule::MAX_WINDOWS {
+            return Err(OptionsError::InvalidExerciseWindow.into());
+        }
+        Ok(ExerciseSchedule(windows))
+    }
+
+    pub fn windows(&self) -> &[ExerciseWindow] {
+        &self.0
+    }
+
+    fn deserialize(src: &[u8; ExerciseSchedule::LEN]) -> Result<Self, ProgramError> {
+        let (count, windows) = array_refs![src, 1, ExerciseSchedule::MAX_WINDOWS * 16];
+        let count = count[0] as usize;
+        if count > ExerciseSchedule::MAX_WINDOWS {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(ExerciseSchedule(
+            windows
+                .chunks_exact(16)
+                .take(count)
+                .map(|window| {
+                    let (start, end) = array_refs![window.try_into().unwrap(), 8, 8];
+                    ExerciseWindow {
+                        start: i64::from_le_bytes(*start),
+                        end: i64::from_le_bytes(*end),
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>) {
+        dst.push(self.0.len() as u8);
+        for window in &self.0 {
+            dst.extend_from_slice(&window.start.to_le_bytes());
+            dst.extend_from_slice(&window.end.to_le_bytes());
+        }
+        let unused = ExerciseSchedule::MAX_WINDOWS - self.0.len();
+        dst.extend_from_slice(&vec![0; unused * 16]);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ContractData {
     pub token_type: Pubkey,
     pub token_qty: u64,
+    // unix time in milliseconds (second resolution on chain), or a slot for ExpiryType::SLOT
     pub expiry_date: i64,
     pub strike_type: Pubkey,
     pub strike_qty: u64,
     pub premium_type: Pubkey,
     pub premium_qty: u64,
+    pub expiry_type: ExpiryType,
+    pub contract_style: ContractStyle,
+    // European only, same unit as expiry_date
+    pub exercise_start: Option<i64>,
+    // Bermudan only, sorted and non-overlapping
+    pub exercise_schedule: ExerciseSchedule,
+    pub settlement: SettlementType,
+    // cash settlement only, the price account both parties agreed on
+    pub oracle: Option<Pubkey>,
+    // cash settlement of timestamp expiries only, in-the-money lots are paid out by
+    // SettleAtExpiry instead of expiring worthless
+    pub auto_exercise: bool,
+    // collateral of filled lots goes to the series vault, and holder tokens are exercised
+    // against the vault rather than this contract
+    pub pooled: bool,
+    // token_qty, strike_qty and premium_qty are per lot
+    pub lots: u64,
 }
 
 impl ContractData {
-    pub const LEN: usize = 128;
-    pub fn deserialize(data_array: &[u8]) -> ContractData {
+    pub const LEGACY_LEN: usize = 128;
+    pub const LEN: usize = 183 + ExerciseSchedule::LEN;
+
+    // mint and per-lot amount the writer escrows; a cash-settled call pays at most its strike
+    pub fn collateral(&self, contract_type: ContractType) -> (Pubkey, u64) {
+        match (self.settlement, contract_type) {
+            (SettlementType::PHYSICAL, ContractType::CALL) => (self.token_type, self.token_qty),
+            _ => (self.strike_type, self.strike_qty),
+        }
+    }
+
+    // mints the buyer and the writer receive at exercise, in that order
+    pub fn receive_types(&self, contract_type: ContractType) -> (Pubkey, Pubkey) {
+        match (self.settlement, contract_type) {
+            (SettlementType::CASH, _) => (self.strike_type, self.strike_type),
+            (SettlementType::PHYSICAL, ContractType::CALL) => (self.token_type, self.strike_type),
+            (SettlementType::PHYSICAL, ContractType::PUT) => (self.strike_type, self.token_type),
+        }
+    }
+
+    // fields added since the legacy layout are appended and zero by default,
+    // except lots: a legacy contract is one lot
+    pub fn deserialize_legacy(data_array: &[u8]) -> Result<ContractData, ProgramError> {
+        if data_array.len() != Self::LEGACY_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut buf = [0; Self::LEN];
+        buf[..Self::LEGACY_LEN].copy_from_slice(data_array);
+        Ok(ContractData {
+            lots: 1,
+            ..Self::deserialize(&buf)?
+        })
+    }
+
+    pub fn deserialize(data_array: &[u8]) -> Result<ContractData, ProgramError> {
         let data_array: &[u8; Self::LEN] = data_array
             .try_into()
-            .map_err(|_| ProgramError::InvalidAccountData)
-            .unwrap();
+            .map_err(|_| ProgramError::InvalidAccountData)?;
         let (
             token_type,
             token_qty,
@@ -281,7 +922,34 @@ impl ContractData {
             strike_qty,
             premium_type,
             premium_qty,
-        ) = array_refs![data_array, 32, 8, 8, 32, 8, 32, 8];
+            expiry_type,
+            contract_style,
+            exercise_start,
+            exercise_schedule,
+            settlement,
+            oracle,
+            auto_exercise,
+            pooled,
+            lots,
+        ) = array_refs![
+            data_array,
+            32,
+            8,
+            8,
+            32,
+            8,
+            32,
+            8,
+            1,
+            1,
+            9,
+            ExerciseSchedule::LEN,
+            1,
+            33,
+            1,
+            1,
+            8
+        ];
 
         let token_type = Pubkey::new_from_array(*token_type);
         let token_qty = u64::from_le_bytes(*token_qty);
@@ -290,8 +958,48 @@ impl ContractData {
         let strike_qty = u64::from_le_bytes(*strike_qty);
         let premium_type = Pubkey::new_from_array(*premium_type);
         let premium_qty = u64::from_le_bytes(*premium_qty);
+        let expiry_type = match expiry_type[0] {
+            0 => ExpiryType::TIMESTAMP,
+            1 => ExpiryType::SLOT,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let contract_style = match contract_style[0] {
+            0 => ContractStyle::AMERICAN,
+            1 => ContractStyle::EUROPEAN,
+            2 => ContractStyle::BERMUDAN,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let (is_some, start) = array_refs![exercise_start, 1, 8];
+        let exercise_start = match is_some[0] {
+            0 => None,
+            1 => Some(i64::from_le_bytes(*start)),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let exercise_schedule = ExerciseSchedule::deserialize(exercise_schedule)?;
+        let settlement = match settlement[0] {
+            0 => SettlementType::PHYSICAL,
+            1 => SettlementType::CASH,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let (is_some, oracle) = array_refs![oracle, 1, 32];
+        let oracle = match is_some[0] {
+            0 => None,
+            1 => Some(Pubkey::new_from_array(*oracle)),
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let auto_exercise = match auto_exercise[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let pooled = match pooled[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let lots = u64::from_le_bytes(*lots);
 
-        ContractData {
+        Ok(ContractData {
             token_type,
             token_qty,
             expiry_date,
@@ -299,7 +1007,16 @@ impl ContractData {
             strike_qty,
             premium_type,
             premium_qty,
-        }
+            expiry_type,
+            contract_style,
+            exercise_start,
+            exercise_schedule,
+            settlement,
+            oracle,
+            auto_exercise,
+            pooled,
+            lots,
+        })
     }
 
     pub fn serialize(&self) -> [u8; Self::LEN] {
@@ -311,17 +1028,70 @@ impl ContractData {
         v.extend_from_slice(&self.strike_qty.to_le_bytes());
         v.extend_from_slice(&self.premium_type.to_bytes());
         v.extend_from_slice(&self.premium_qty.to_le_bytes());
+        self.serialize_exercise_terms(&mut v);
+        v.extend_from_slice(&self.lots.to_le_bytes());
         v.try_into().unwrap()
     }
 
+    // how a lot expires, is exercised and settles: every term added since the legacy
+    // layout but lots. these split a series, since holder tokens that differ in any of
+    // them pay out differently; pooled lots pay from the series vaults rather than the
+    // escrow of the contract they were filled on
+    fn serialize_exercise_terms(&self, v: &mut Vec<u8>) {
+        v.push(match self.expiry_type {
+            ExpiryType::TIMESTAMP => 0,
+            ExpiryType::SLOT => 1,
+        });
+        v.push(match self.contract_style {
+            ContractStyle::AMERICAN => 0,
+            ContractStyle::EUROPEAN => 1,
+            ContractStyle::BERMUDAN => 2,
+        });
+        match self.exercise_start {
+            Some(start) => {
+                v.push(1);
+                v.extend_from_slice(&start.to_le_bytes());
+            }
+            None => v.extend_from_slice(&[0; 9]),
+        };
+        self.exercise_schedule.serialize(v);
+        v.push(match self.settlement {
+            SettlementType::PHYSICAL => 0,
+            SettlementType::CASH => 1,
+        });
+        match self.oracle {
+            Some(oracle) => {
+                v.push(1);
+                v.extend_from_slice(&oracle.to_bytes());
+            }
+            None => v.extend_from_slice(&[0; 33]),
+        };
+        v.push(self.auto_exercise as u8);
+        v.push(self.pooled as u8);
+    }
+
+    // every term but premium_qty, which is negotiated per offer, and lots; keys the
+    // data PDA of a contract
     pub fn get_seed(&self) -> [u8; 32] {
-        let mut dst = [0; 120];
-        dst[0..32].copy_from_slice(&self.token_type.to_bytes());
-        dst[32..40].copy_from_slice(&self.token_qty.to_le_bytes());
-        dst[40..48].copy_from_slice(&self.expiry_date.to_le_bytes());
-        dst[48..80].copy_from_slice(&self.strike_type.to_bytes());
-        dst[80..88].copy_from_slice(&self.strike_qty.to_le_bytes());
-        dst[88..120].copy_from_slice(&self.premium_type.to_bytes());
+        self.hash_terms(120)
+    }
+
+    // the economic terms only: the underlying, strike and expiry, then the exercise
+    // terms. premium_type is an attribute of the trade, so that contracts paid for in
+    // different currencies share a holder mint
+    pub fn series_seed(&self) -> [u8; 32] {
+        self.hash_terms(88)
+    }
+
+    fn hash_terms(&self, legacy_terms: usize) -> [u8; 32] {
+        let mut dst = self.serialize()[..legacy_terms].to_vec();
+        // the exercise terms only enter the hash when set, so contracts written before
+        // they existed keep their mint and data PDA addresses
+        let mut exercise_terms = Vec::new();
+        self.serialize_exercise_terms(&mut exercise_terms);
+        if exercise_terms.iter().any(|b| *b != 0) {
+            dst.extend_from_slice(&exercise_terms);
+        }
 
         get_seed(&dst)
     }
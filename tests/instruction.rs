@@ -0,0 +1,234 @@
+use sol_options::{
+    instruction::{
+        decode_instruction, InitParty, InstructionType, OfferData, Quote, INSTRUCTION_VERSION,
+    },
+    state::{
+        get_seed, ContractData, ContractStyle, ContractType, ExerciseSchedule, ExpiryType,
+        SettlementType,
+    },
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+fn contract_data() -> ContractData {
+    ContractData {
+        token_type: Pubkey::new_unique(),
+        token_qty: 5,
+        expiry_date: 1_650_000_000_000,
+        strike_type: Pubkey::new_unique(),
+        strike_qty: 10,
+        premium_type: Pubkey::new_unique(),
+        premium_qty: 2,
+        expiry_type: ExpiryType::TIMESTAMP,
+        contract_style: ContractStyle::AMERICAN,
+        exercise_start: None,
+        exercise_schedule: ExerciseSchedule::default(),
+        settlement: SettlementType::CASH,
+        oracle: Some(Pubkey::new_unique()),
+        auto_exercise: false,
+        pooled: false,
+        lots: 3,
+    }
+}
+
+fn offer(program_id: &Pubkey, party: InitParty, contract_type: ContractType) -> OfferData {
+    let (offer_ttl, counterparty) = match party {
+        InitParty::BUYER => (None, Some(Pubkey::new_unique())),
+        InitParty::WRITER => (Some(60_000), None),
+    };
+    let mut index_data = [0; 41];
+    index_data[..32].copy_from_slice(&Pubkey::new_unique().to_bytes());
+    index_data[32] = 1;
+    index_data[33..].copy_from_slice(&7u64.to_le_bytes());
+    OfferData::new(
+        program_id,
+        party,
+        contract_type,
+        contract_data(),
+        index_data,
+        offer_ttl,
+        counterparty,
+    )
+}
+
+fn quote(party: InitParty) -> Quote {
+    Quote {
+        maker: Pubkey::new_unique(),
+        party,
+        taker: Pubkey::new_unique(),
+        contract_type: ContractType::PUT,
+        contract_data: contract_data(),
+        valid_until: 1_649_000_000_000,
+        nonce: 42,
+    }
+}
+
+fn all_instructions(program_id: &Pubkey) -> Vec<InstructionType> {
+    vec![
+        InstructionType::Bid {
+            instruction: offer(program_id, InitParty::BUYER, ContractType::CALL),
+        },
+        InstructionType::Ask {
+            instruction: offer(program_id, InitParty::WRITER, ContractType::PUT),
+        },
+        InstructionType::AcceptBid {
+            lots: 1,
+            offer_seq: None,
+        },
+        InstructionType::AcceptAsk {
+            lots: 3,
+            offer_seq: Some(2),
+        },
+        InstructionType::CancelOffer,
+        InstructionType::Execute { lots: 2 },
+        InstructionType::Expire,
+        InstructionType::CreateMint {
+            contract_type: ContractType::PUT,
+            contract_data: contract_data(),
+        },
+        InstructionType::MigrateContract,
+        InstructionType::CrankExpire,
+        InstructionType::SettleAtExpiry,
+        InstructionType::RedeemWriter { lots: 2 },
+        InstructionType::CloseOut { lots: 1 },
+        InstructionType::ExercisePooled {
+            contract_type: ContractType::CALL,
+            contract_data: contract_data(),
+            lots: 2,
+        },
+        InstructionType::RedeemPooled {
+            contract_type: ContractType::PUT,
+            contract_data: contract_data(),
+            lots: 1,
+        },
+        InstructionType::CloseOutPooled {
+            contract_type: ContractType::CALL,
+            contract_data: contract_data(),
+            lots: 3,
+        },
+        InstructionType::PlaceOrder {
+            instruction: offer(program_id, InitParty::BUYER, ContractType::CALL),
+        },
+        InstructionType::PlaceOrder {
+            instruction: offer(program_id, InitParty::WRITER, ContractType::PUT),
+        },
+        InstructionType::CancelOrder,
+        InstructionType::AcceptQuote {
+            quote: quote(InitParty::BUYER),
+        },
+        InstructionType::AcceptQuote {
+            quote: quote(InitParty::WRITER),
+        },
+        InstructionType::CrankCancel,
+        InstructionType::AmendOffer { premium_qty: 5 },
+        InstructionType::ClaimSettlement { lots: 2 },
+        InstructionType::MigrateSeries {
+            contract_type: ContractType::CALL,
+            contract_data: contract_data(),
+        },
+    ]
+}
+
+#[test]
+fn round_trip() {
+    let program_id = Pubkey::new_unique();
+    for ix in all_instructions(&program_id) {
+        let packed = ix.pack();
+        assert_eq!(packed[0], INSTRUCTION_VERSION);
+        let decoded = decode_instruction(&program_id, &packed).unwrap();
+        assert_eq!(ix, decoded);
+    }
+}
+
+#[test]
+fn quote_message_and_index() {
+    let quote = quote(InitParty::WRITER);
+    let program_id = Pubkey::new_unique();
+    let message = quote.message(&program_id);
+    assert_ne!(message, quote.message(&Pubkey::new_unique()));
+    // the maker signs exactly what AcceptQuote carries
+    let packed = InstructionType::AcceptQuote {
+        quote: quote.clone(),
+    }
+    .pack();
+    assert_eq!(message[8 + 32..], packed[2..]);
+
+    let index_data = quote.index_data();
+    assert_eq!(index_data[..32], quote.maker.to_bytes());
+    assert_eq!(index_data[32], ContractType::PUT as u8);
+    assert_eq!(index_data[33..41], 42u64.to_le_bytes());
+    // never the index of the maker's offer with the same contract_no
+    assert_ne!(get_seed(&index_data), get_seed(&index_data[..41]));
+}
+
+#[test]
+fn ask_decodes_as_ask() {
+    let program_id = Pubkey::new_unique();
+    let packed = InstructionType::Ask {
+        instruction: offer(&program_id, InitParty::WRITER, ContractType::CALL),
+    }
+    .pack();
+    match decode_instruction(&program_id, &packed).unwrap() {
+        InstructionType::Ask { instruction } => assert_eq!(instruction.party, InitParty::WRITER),
+        ix => panic!("decoded ask as {:?}", ix),
+    }
+}
+
+#[test]
+fn truncated_input() {
+    let program_id = Pubkey::new_unique();
+    for ix in all_instructions(&program_id) {
+        let packed = ix.pack();
+        for len in 0..packed.len() {
+            assert_eq!(
+                decode_instruction(&program_id, &packed[..len]),
+                Err(ProgramError::InvalidInstructionData),
+                "decoded truncated {:?}",
+                ix
+            );
+        }
+    }
+}
+
+#[test]
+fn trailing_bytes() {
+    let program_id = Pubkey::new_unique();
+    for ix in all_instructions(&program_id) {
+        let mut packed = ix.pack();
+        packed.push(0);
+        assert_eq!(
+            decode_instruction(&program_id, &packed),
+            Err(ProgramError::InvalidInstructionData),
+            "decoded {:?} with trailing byte",
+            ix
+        );
+    }
+}
+
+#[test]
+fn unknown_version_and_tag() {
+    let program_id = Pubkey::new_unique();
+    let mut packed = InstructionType::Execute { lots: 1 }.pack();
+    packed[0] = INSTRUCTION_VERSION + 1;
+    assert_eq!(
+        decode_instruction(&program_id, &packed),
+        Err(ProgramError::InvalidInstructionData)
+    );
+    assert_eq!(
+        decode_instruction(&program_id, &[INSTRUCTION_VERSION, 255]),
+        Err(ProgramError::InvalidInstructionData)
+    );
+}
+
+#[test]
+fn invalid_contract_type() {
+    let program_id = Pubkey::new_unique();
+    let mut packed = InstructionType::Bid {
+        instruction: offer(&program_id, InitParty::BUYER, ContractType::CALL),
+    }
+    .pack();
+    packed[2] = 2;
+    assert_eq!(
+        decode_instruction(&program_id, &packed),
+        Err(ProgramError::InvalidInstructionData)
+    );
+}
@@ -2,22 +2,32 @@
 
 use sol_options::{
     entrypoint::process_instruction,
-    instruction::InitParty,
-    state::{get_seed, ContractData, ContractPDA, ContractState, ContractType, PartyData},
+    error::OptionsError,
+    instruction::{self, InitParty, InstructionType, Quote},
+    oracle::{MAX_PRICE_AGE, SETTLE_WINDOW},
+    state::{
+        get_seed, ContractData, ContractPDA, ContractState, ContractStyle, ContractType,
+        ExerciseSchedule, ExerciseWindow, ExpiryType, MintPDA, OrderBook, PartyData,
+        SettlementType, TestOracle,
+    },
 };
 use solana_program_test::*;
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
+    account::{Account, AccountSharedData},
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
     program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
     signer::{keypair, Signer},
-    system_instruction, system_program, sysvar,
-    transaction::Transaction,
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account,
 };
-use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
 use spl_token;
-use std::time::SystemTime;
+use std::convert::TryInto;
 
 const MINT_SIZE: u64 = 82;
 
@@ -38,7 +48,25 @@ struct TestEnv {
     buyer_temp: Pubkey,
     writer_temp: Pubkey,
     buyer_holder: Pubkey,
+    // the writer's writer-token ATA, set once the writer mint is known
+    writer_tokens: Pubkey,
+    // escrowed by the contract, unlike the writer's ATAs
+    writer_receive_1: Pubkey,
+    writer_receive_2: Pubkey,
     index_seed: [u8; 41],
+    expiry_type: ExpiryType,
+    contract_style: ContractStyle,
+    // offsets from contract creation, in the unit of expiry_type
+    exercise_start: Option<i64>,
+    exercise_windows: Vec<ExerciseWindow>,
+    settlement: SettlementType,
+    oracle: Option<Pubkey>,
+    auto_exercise: bool,
+    pooled: bool,
+    lots: u64,
+    // in the unit of expiry_type
+    offer_ttl: Option<i64>,
+    counterparty: Option<Pubkey>,
 }
 
 enum InitMode {
@@ -52,10 +80,9 @@ async fn call_bid_execute() {
     let init_mode = InitMode::BUYER;
     let mut test_env = init_env(contract_type, &init_mode).await;
     let expire_time = 10000;
-    let (contract_pda, mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    let contract_pda = accept_bid(&mut test_env, contract_pda, mint_pda).await;
-    execute(&mut test_env, contract_pda, mint_pda, &contract_type).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
 }
 
 #[tokio::test]
@@ -64,10 +91,9 @@ async fn call_ask_execute() {
     let init_mode = InitMode::WRITER;
     let mut test_env = init_env(contract_type, &init_mode).await;
     let expire_time = 10000;
-    let (contract_pda, mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    let contract_pda = accept_ask(&mut test_env, contract_pda, mint_pda).await;
-    execute(&mut test_env, contract_pda, mint_pda, &contract_type).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
 }
 
 #[tokio::test]
@@ -76,8 +102,7 @@ async fn call_bid_cancel() {
     let init_mode = InitMode::BUYER;
     let mut test_env = init_env(contract_type, &init_mode).await;
     let expire_time = 10000;
-    let (contract_pda, _mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
     cancel_offer(&mut test_env, contract_pda, &init_mode).await;
 }
 
@@ -87,471 +112,4231 @@ async fn call_ask_cancel() {
     let init_mode = InitMode::WRITER;
     let mut test_env = init_env(contract_type, &init_mode).await;
     let expire_time = 10000;
-    let (contract_pda, _mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
     cancel_offer(&mut test_env, contract_pda, &init_mode).await;
 }
 
 #[tokio::test]
-async fn call_bid_expire() {
+async fn call_bid_cancel_by_non_initialiser() {
     let contract_type = ContractType::CALL;
     let init_mode = InitMode::BUYER;
     let mut test_env = init_env(contract_type, &init_mode).await;
-    let expire_time = 1000;
-    let (contract_pda, mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    let contract_pda = accept_bid(&mut test_env, contract_pda, mint_pda).await;
-    test_env.ctx.warp_to_slot(10).unwrap();
-    expire_contract(&mut test_env, contract_pda).await;
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+
+    let mut ix = instruction::cancel_offer(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+    ix.accounts[0] = AccountMeta::new(test_env.writer.main.pubkey(), true);
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.writer.main],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("cancel by non-initialiser did not fail");
+    assert_options_error(err, OptionsError::IncorrectInitialiserAccount);
 }
 
 #[tokio::test]
-async fn call_ask_expire() {
+async fn offer_spoofed_accounts() {
     let contract_type = ContractType::CALL;
-    let init_mode = InitMode::WRITER;
+    for init_mode in [InitMode::BUYER, InitMode::WRITER] {
+        let mut test_env = init_env(contract_type, &init_mode).await;
+        let contract_data = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+        let ix = offer_instruction(&test_env, &init_mode, &contract_type, &contract_data);
+        let signer: fn(&TestEnv) -> &keypair::Keypair = match init_mode {
+            InitMode::BUYER => |env| &env.buyer.main,
+            InitMode::WRITER => |env| &env.writer.main,
+        };
+        // token_temp, receive_acc, receive_ata, mint_pda, holder_mint
+        assert_spoofs_rejected(&mut test_env, ix, signer, &[1, 2, 3, 4, 5]).await;
+    }
+}
+
+#[tokio::test]
+async fn accept_bid_spoofed_accounts() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
     let mut test_env = init_env(contract_type, &init_mode).await;
-    let expire_time = 1000;
-    let (contract_pda, mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    let contract_pda = accept_ask(&mut test_env, contract_pda, mint_pda).await;
-    test_env.ctx.warp_to_slot(10).unwrap();
-    expire_contract(&mut test_env, contract_pda).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let ix = instruction::accept_bid(
+        &test_env.program_key.pubkey(),
+        &test_env.writer.main.pubkey(),
+        &test_env.writer_temp,
+        &test_env.writer_receive_2,
+        &test_env.writer.mint_2,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    // writer_temp, writer_receive, receive_ata, data_pda, premium_temp, buyer_holder_ata,
+    // mint_pda, holder_mint, writer_ata
+    assert_spoofs_rejected(
+        &mut test_env,
+        ix,
+        |env| &env.writer.main,
+        &[1, 2, 3, 4, 5, 7, 8, 9, 13],
+    )
+    .await;
 }
 
 #[tokio::test]
-async fn put_bid_execute() {
-    let contract_type = ContractType::PUT;
+async fn accept_bid_wrong_writer_tokens() {
+    let contract_type = ContractType::CALL;
     let init_mode = InitMode::BUYER;
     let mut test_env = init_env(contract_type, &init_mode).await;
-    let expire_time = 10000;
-    let (contract_pda, mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    let contract_pda = accept_bid(&mut test_env, contract_pda, mint_pda).await;
-    execute(&mut test_env, contract_pda, mint_pda, &contract_type).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let ix = instruction::accept_bid(
+        &test_env.program_key.pubkey(),
+        &test_env.writer.main.pubkey(),
+        &test_env.writer_temp,
+        &test_env.writer_receive_2,
+        &test_env.writer.mint_2,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let signer: fn(&TestEnv) -> &keypair::Keypair = |env| &env.writer.main;
+    // a token mint of the wrong series, and a token account that is not the writer's ATA
+    let holder_mint = test_env.holder_mint.pubkey();
+    let (writer_premium, buyer_premium) = (test_env.writer.mint_2, test_env.buyer.mint_2);
+    assert_spoof_error(
+        &mut test_env,
+        &ix,
+        signer,
+        12,
+        holder_mint,
+        OptionsError::IncorrectWriterMint,
+    )
+    .await;
+    for ata in [writer_premium, buyer_premium] {
+        assert_spoof_error(
+            &mut test_env,
+            &ix,
+            signer,
+            13,
+            ata,
+            OptionsError::IncorrectWriterAta,
+        )
+        .await;
+    }
 }
 
 #[tokio::test]
-async fn put_ask_execute() {
-    let contract_type = ContractType::PUT;
+async fn accept_ask_spoofed_accounts() {
+    let contract_type = ContractType::CALL;
     let init_mode = InitMode::WRITER;
     let mut test_env = init_env(contract_type, &init_mode).await;
-    let expire_time = 10000;
-    let (contract_pda, mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    let contract_pda = accept_ask(&mut test_env, contract_pda, mint_pda).await;
-    execute(&mut test_env, contract_pda, mint_pda, &contract_type).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let ix = instruction::accept_ask(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    // premium_temp, buyer_receive, holder_ata, mint_pda, holder_mint, data_pda, seller_prem_acc,
+    // writer_ata
+    assert_spoofs_rejected(
+        &mut test_env,
+        ix,
+        |env| &env.buyer.main,
+        &[1, 2, 3, 4, 5, 6, 7, 11],
+    )
+    .await;
 }
 
 #[tokio::test]
-async fn put_bid_cancel() {
-    let contract_type = ContractType::PUT;
+async fn accept_ask_wrong_premium_receiver() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let mut ix = instruction::accept_ask(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    ix.accounts[7] = AccountMeta::new(test_env.buyer.mint_2, false);
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.buyer.main],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("premium paid to buyer did not fail");
+    assert_options_error(err, OptionsError::IncorrectWriterReceive);
+}
+
+#[tokio::test]
+async fn execute_spoofed_accounts() {
+    let contract_type = ContractType::CALL;
     let init_mode = InitMode::BUYER;
     let mut test_env = init_env(contract_type, &init_mode).await;
-    let expire_time = 10000;
-    let (contract_pda, _mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    cancel_offer(&mut test_env, contract_pda, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    // holder_temp, holder_receive, holder_ata, mint_pda, holder_mint, writer_temp,
+    // data_pda, writer_receive
+    assert_spoofs_rejected(
+        &mut test_env,
+        ix,
+        |env| &env.buyer.main,
+        &[1, 2, 3, 4, 5, 6, 7, 9],
+    )
+    .await;
 }
 
 #[tokio::test]
-async fn put_ask_cancel() {
-    let contract_type = ContractType::PUT;
-    let init_mode = InitMode::WRITER;
+async fn execute_holder_ata_not_owned() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
     let mut test_env = init_env(contract_type, &init_mode).await;
-    let expire_time = 10000;
-    let (contract_pda, _mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    cancel_offer(&mut test_env, contract_pda, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    // an account of the holder mint that the signer does not own
+    let (holder_mint, writer) = (test_env.holder_mint.pubkey(), test_env.writer.main.pubkey());
+    let foreign = create_token_account(&mut test_env, holder_mint, &writer).await;
+    assert_spoof_error(
+        &mut test_env,
+        &ix,
+        |env| &env.buyer.main,
+        3,
+        foreign,
+        OptionsError::HolderAtaNotOwned,
+    )
+    .await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
 }
 
 #[tokio::test]
-async fn put_bid_expire() {
-    let contract_type = ContractType::PUT;
+async fn expire_spoofed_accounts() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 1000).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    let ix = instruction::expire(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+    // writer_temp, data_pda, mint_pda
+    assert_spoofs_rejected(&mut test_env, ix, |env| &env.writer.main, &[1, 2, 6]).await;
+}
+
+#[tokio::test]
+async fn cancel_spoofed_accounts() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let ix = instruction::cancel_offer(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+    // token_temp, data_pda
+    assert_spoofs_rejected(&mut test_env, ix, |env| &env.buyer.main, &[1, 2]).await;
+}
+
+#[tokio::test]
+async fn create_mint_wrong_mint_pda() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::BUYER).await;
+    let contract_data = ContractData {
+        token_type: test_env.mint_1.pubkey(),
+        token_qty: 5,
+        strike_type: test_env.mint_2.pubkey(),
+        strike_qty: 5,
+        premium_type: test_env.mint_2.pubkey(),
+        premium_qty: 5,
+        expiry_date: 0,
+        expiry_type: ExpiryType::TIMESTAMP,
+        contract_style: ContractStyle::AMERICAN,
+        exercise_start: None,
+        exercise_schedule: ExerciseSchedule::default(),
+        settlement: SettlementType::PHYSICAL,
+        oracle: None,
+        auto_exercise: false,
+        pooled: false,
+        lots: 1,
+    };
+    let mut ix = instruction::create_mint(
+        &test_env.program_key.pubkey(),
+        &test_env.ctx.payer.pubkey(),
+        &test_env.holder_mint.pubkey(),
+        contract_type,
+        &contract_data,
+    );
+    ix.accounts[2] = AccountMeta::new(Pubkey::new_unique(), false);
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.holder_mint],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("create mint with foreign mint PDA did not fail");
+    assert_options_error(err, OptionsError::IncorrectMintPda);
+}
+
+#[tokio::test]
+async fn call_bid_expire() {
+    let contract_type = ContractType::CALL;
     let init_mode = InitMode::BUYER;
     let mut test_env = init_env(contract_type, &init_mode).await;
     let expire_time = 1000;
-    let (contract_pda, mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    let contract_pda = accept_bid(&mut test_env, contract_pda, mint_pda).await;
-    test_env.ctx.warp_to_slot(10).unwrap();
-    expire_contract(&mut test_env, contract_pda).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    expire_contract(&mut test_env, contract_pda, false).await;
 }
 
 #[tokio::test]
-async fn put_ask_expire() {
-    let contract_type = ContractType::PUT;
+async fn call_ask_expire() {
+    let contract_type = ContractType::CALL;
     let init_mode = InitMode::WRITER;
     let mut test_env = init_env(contract_type, &init_mode).await;
     let expire_time = 1000;
-    let (contract_pda, mint_pda) =
-        init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
-    let contract_pda = accept_ask(&mut test_env, contract_pda, mint_pda).await;
-    test_env.ctx.warp_to_slot(10).unwrap();
-    expire_contract(&mut test_env, contract_pda).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    expire_contract(&mut test_env, contract_pda, false).await;
 }
 
-async fn init_env(contract_type: ContractType, init_mode: &InitMode) -> TestEnv {
-    println!("\n-----CREATING-TEST-ENVIRONMENT-----\n");
-    let program_key = keypair::Keypair::new();
-    let buyer_key = keypair::Keypair::new();
-    let writer_key = keypair::Keypair::new();
-    let mint_1 = keypair::Keypair::new();
-    let mint_2 = keypair::Keypair::new();
-    let buyer_temp = keypair::Keypair::new();
-    let writer_temp = keypair::Keypair::new();
-    let holder_mint = keypair::Keypair::new();
+#[tokio::test]
+async fn call_ask_slot_expiry_execute() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.expiry_type = ExpiryType::SLOT;
+    let expire_slots = 1000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_slots).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
 
-    println!("starting test-server");
+#[tokio::test]
+async fn call_bid_slot_expiry() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.expiry_type = ExpiryType::SLOT;
+    let expire_slots = 5;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_slots).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
 
-    let mut ctx = ProgramTest::new(
-        "sol_options",
-        program_key.pubkey(),
-        processor!(process_instruction),
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
     )
-    .start_with_context()
-    .await;
-
-    let client = &mut ctx.banks_client;
-    let payer = &ctx.payer;
-    let block = ctx.last_blockhash.clone();
-
-    println!("started test-server, creating mint account instructions...");
-    let min_rent = Rent::default().minimum_balance(MINT_SIZE as usize);
-
-    let c1 = system_instruction::create_account(
-        &payer.pubkey(),
-        &mint_1.pubkey(),
-        min_rent,
-        MINT_SIZE,
-        &spl_token::id(),
-    );
-    let c2 = system_instruction::create_account(
-        &payer.pubkey(),
-        &mint_2.pubkey(),
-        min_rent,
-        MINT_SIZE,
-        &spl_token::id(),
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.buyer.main],
+        test_env.ctx.last_blockhash,
     );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("execute after expiry slot did not fail");
+    assert_options_error(err, OptionsError::ContractExpired);
 
-    let i1 = spl_token::instruction::initialize_mint(
-        &spl_token::id(),
-        &mint_1.pubkey(),
-        &payer.pubkey(),
-        None,
-        1,
-    )
-    .expect("could not create initialise_mint instruction");
+    expire_contract(&mut test_env, contract_pda, false).await;
+}
 
-    let i2 = spl_token::instruction::initialize_mint(
-        &spl_token::id(),
-        &mint_2.pubkey(),
-        &payer.pubkey(),
-        None,
+#[tokio::test]
+async fn call_bid_european_execute() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.contract_style = ContractStyle::EUROPEAN;
+    test_env.exercise_start = Some(5000);
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
         1,
     )
-    .expect("could not create initialise_mint instruction");
-
-    println!("creating mint 1...");
+    .unwrap();
     let tx = Transaction::new_signed_with_payer(
-        &[c1, i1],
-        Some(&payer.pubkey()),
-        &[payer, &mint_1],
-        block,
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.buyer.main],
+        test_env.ctx.last_blockhash,
     );
-    client.process_transaction(tx).await.unwrap();
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("execute before exercise window did not fail");
+    assert_options_error(err, OptionsError::ExerciseWindowNotOpen);
 
-    println!("creating mint 2...");
-    let tx = Transaction::new_signed_with_payer(
+    let start = contract_pda.contract_data.exercise_start.unwrap();
+    warp_to(&mut test_env, &contract_pda.contract_data, start).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn call_ask_bermudan_execute() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.contract_style = ContractStyle::BERMUDAN;
+    test_env.exercise_windows = vec![
+        ExerciseWindow {
+            start: 2000,
+            end: 3000,
+        },
+        ExerciseWindow {
+            start: 6000,
+            end: 7000,
+        },
+    ];
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    let windows = contract_pda
+        .contract_data
+        .exercise_schedule
+        .windows()
+        .to_vec();
+
+    // before the first window, then between the two
+    for time in [None, Some(windows[0].end + 1000)] {
+        if let Some(time) = time {
+            warp_to(&mut test_env, &contract_pda.contract_data, time).await;
+        }
+        let ix = instruction::execute(
+            &test_env.program_key.pubkey(),
+            &test_env.buyer.main.pubkey(),
+            &test_env.buyer.mint_2,
+            &test_env.buyer.mint_1,
+            &test_env.holder_mint.pubkey(),
+            &contract_pda,
+            1,
+        )
+        .unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            &[&test_env.ctx.payer, &test_env.buyer.main],
+            test_env.ctx.last_blockhash,
+        );
+        let err = test_env
+            .ctx
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .expect_err("execute outside exercise windows did not fail");
+        assert_options_error(err, OptionsError::ExerciseWindowNotOpen);
+    }
+
+    warp_to(&mut test_env, &contract_pda.contract_data, windows[1].start).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn invalid_exercise_windows() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expiry_date = 10000;
+    let contract_data = ContractData {
+        token_type: test_env.mint_1.pubkey(),
+        token_qty: 5,
+        strike_type: test_env.mint_2.pubkey(),
+        strike_qty: 5,
+        premium_type: test_env.mint_2.pubkey(),
+        premium_qty: 5,
+        expiry_date,
+        expiry_type: ExpiryType::TIMESTAMP,
+        contract_style: ContractStyle::AMERICAN,
+        exercise_start: None,
+        exercise_schedule: ExerciseSchedule::default(),
+        settlement: SettlementType::PHYSICAL,
+        oracle: None,
+        auto_exercise: false,
+        pooled: false,
+        lots: 1,
+    };
+    let window = |start, end| ExerciseWindow { start, end };
+    for (contract_style, exercise_start, windows) in [
+        (ContractStyle::AMERICAN, Some(0), vec![]),
+        (ContractStyle::AMERICAN, None, vec![window(0, 1)]),
+        (ContractStyle::EUROPEAN, None, vec![]),
+        (ContractStyle::EUROPEAN, Some(expiry_date + 1), vec![]),
+        (ContractStyle::EUROPEAN, Some(0), vec![window(0, 1)]),
+        (ContractStyle::BERMUDAN, None, vec![]),
+        (ContractStyle::BERMUDAN, Some(0), vec![window(0, 1)]),
+        (ContractStyle::BERMUDAN, None, vec![window(1, 0)]),
+        (
+            ContractStyle::BERMUDAN,
+            None,
+            vec![window(5, 6), window(0, 1)],
+        ),
+        (
+            ContractStyle::BERMUDAN,
+            None,
+            vec![window(0, 5), window(5, 6)],
+        ),
+        (
+            ContractStyle::BERMUDAN,
+            None,
+            vec![window(0, expiry_date + 1)],
+        ),
+    ] {
+        let contract_data = ContractData {
+            contract_style,
+            exercise_start,
+            exercise_schedule: ExerciseSchedule::new(windows).unwrap(),
+            ..contract_data.clone()
+        };
+        let ix = offer_instruction(&test_env, &init_mode, &contract_type, &contract_data);
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            &[&test_env.ctx.payer, &test_env.buyer.main],
+            test_env.ctx.last_blockhash,
+        );
+        let err = test_env
+            .ctx
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .expect_err("offer with invalid exercise window did not fail");
+        assert_options_error(err, OptionsError::InvalidExerciseWindow);
+    }
+}
+
+#[tokio::test]
+async fn call_bid_crank_expire() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 1000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    expire_contract(&mut test_env, contract_pda, true).await;
+}
+
+#[tokio::test]
+async fn call_ask_crank_expire() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 1000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    expire_contract(&mut test_env, contract_pda, true).await;
+}
+
+#[tokio::test]
+async fn call_bid_expire_unsigned() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 1000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+
+    let mut ix = instruction::expire(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+    ix.accounts[0].is_signer = false;
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("unsigned expire did not fail");
+    assert_eq!(
+        err.unwrap(),
+        TransactionError::InstructionError(0, InstructionError::MissingRequiredSignature)
+    );
+}
+
+#[tokio::test]
+async fn call_bid_expire_wrong_data_pda() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 1000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+
+    // a program-owned copy of the contract passes every check but the address
+    let program_id = test_env.program_key.pubkey();
+    let real_pda =
+        instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let copy = spoof_account(&mut test_env, real_pda, program_id).await;
+    for crank in [false, true] {
+        let mut ix = if crank {
+            instruction::crank_expire(&program_id, &contract_pda).unwrap()
+        } else {
+            instruction::expire(&program_id, &contract_pda).unwrap()
+        };
+        ix.accounts[2].pubkey = copy;
+        let signers: &[&keypair::Keypair] = if crank {
+            &[&test_env.ctx.payer]
+        } else {
+            &[&test_env.ctx.payer, &test_env.writer.main]
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            signers,
+            test_env.ctx.last_blockhash,
+        );
+        let err = test_env
+            .ctx
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .expect_err("expire with copied PDA did not fail");
+        assert_options_error(err, OptionsError::IncorrectDataPda);
+    }
+}
+
+#[tokio::test]
+async fn call_bid_multi_lot_execute() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 3;
+    fund_extra_lots(&mut test_env, contract_type).await;
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+
+    for lots in [0, contract_pda.open_lots + 1] {
+        let ix = instruction::execute(
+            &test_env.program_key.pubkey(),
+            &test_env.buyer.main.pubkey(),
+            &test_env.buyer.mint_2,
+            &test_env.buyer.mint_1,
+            &test_env.holder_mint.pubkey(),
+            &contract_pda,
+            lots,
+        )
+        .unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            &[&test_env.ctx.payer, &test_env.buyer.main],
+            test_env.ctx.last_blockhash,
+        );
+        let err = test_env
+            .ctx
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .expect_err("execute with invalid lot count did not fail");
+        assert_options_error(err, OptionsError::InvalidLotCount);
+    }
+
+    let contract_pda = execute_lots(&mut test_env, contract_pda, &contract_type, 2)
+        .await
+        .expect("contract closed with a lot open");
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn put_ask_multi_lot_expire() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    fund_extra_lots(&mut test_env, contract_type).await;
+    let expire_time = 1000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_data = contract_pda.contract_data.clone();
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    assert_series(&mut test_env, contract_type, &contract_data, (2, 0, 0)).await;
+    let contract_pda = execute_lots(&mut test_env, contract_pda, &contract_type, 1)
+        .await
+        .expect("contract closed with a lot open");
+    assert_series(&mut test_env, contract_type, &contract_data, (1, 1, 0)).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    expire_contract(&mut test_env, contract_pda, false).await;
+    assert_series(&mut test_env, contract_type, &contract_data, (0, 1, 1)).await;
+}
+
+#[tokio::test]
+async fn offer_zero_lots() {
+    let contract_type = ContractType::CALL;
+    for init_mode in [InitMode::BUYER, InitMode::WRITER] {
+        let mut test_env = init_env(contract_type, &init_mode).await;
+        test_env.lots = 0;
+        let contract_data = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+        let ix = offer_instruction(&test_env, &init_mode, &contract_type, &contract_data);
+        let signer = match init_mode {
+            InitMode::BUYER => &test_env.buyer.main,
+            InitMode::WRITER => &test_env.writer.main,
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            &[&test_env.ctx.payer, signer],
+            test_env.ctx.last_blockhash,
+        );
+        let err = test_env
+            .ctx
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .expect_err("offer of zero lots did not fail");
+        assert_options_error(err, OptionsError::InvalidLotCount);
+    }
+}
+
+// the writer's receive account is escrowed, so it can't double as the premium account
+#[tokio::test]
+async fn writer_receive_shared_with_premium() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_data = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+    let mut ix = offer_instruction(&test_env, &init_mode, &contract_type, &contract_data);
+    ix.accounts[2].pubkey = test_env.writer.mint_2;
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.writer.main],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("ask receiving into its premium account did not fail");
+    assert_options_error(err, OptionsError::IncorrectWriterReceive);
+
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let ix = instruction::accept_bid(
+        &test_env.program_key.pubkey(),
+        &test_env.writer.main.pubkey(),
+        &test_env.writer_temp,
+        &test_env.writer.mint_2,
+        &test_env.writer.mint_2,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.writer.main],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("accept receiving into its premium account did not fail");
+    assert_options_error(err, OptionsError::IncorrectWriterReceive);
+}
+
+#[tokio::test]
+async fn call_bid_partial_fill() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    let (premium_mint, premium_acc) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(&mut test_env, &[(premium_mint, premium_acc, 5)]).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 1000).await;
+    let contract_pda = accept_bid_lots(&mut test_env, contract_pda, 1).await;
+
+    // another writer fills the second lot from its own collateral
+    let other = transfer_writer_tokens(&mut test_env, &contract_pda, 0).await;
+    let program_id = test_env.program_key.pubkey();
+    let ix = instruction::accept_bid(
+        &program_id,
+        &other.main.pubkey(),
+        &other.mint_1,
+        &test_env.writer_receive_2,
+        &other.mint_2,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        std::slice::from_ref(&ix),
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &other.main],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("fill without collateral did not fail");
+    assert_options_error(err, OptionsError::IncorrectTempBalance);
+
+    let asset_mint = test_env.mint_1.pubkey();
+    mint_tokens(&mut test_env, &[(asset_mint, other.mint_1, 5)]).await;
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &other.main],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let contract_pda = filled(contract_pda, 1);
+    assert_eq!(get_contract(&mut test_env, pda).await, contract_pda);
+    let writer_temp = test_env.writer_temp;
+    assert_eq!(get_token_balance(&mut test_env, writer_temp).await, 10);
+    assert_eq!(get_token_balance(&mut test_env, other.mint_2).await, 5);
+
+    // each writer redeems the lot it filled
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    let ix = instruction::crank_expire(&program_id, &contract_pda).unwrap();
+    process_cranked(&mut test_env, ix).await.unwrap();
+    let settled = assert_settled(&mut test_env, pda, contract_pda).await;
+    let settled = redeem_writer_lots(&mut test_env, settled, 1, Some(&other))
+        .await
+        .expect("contract closed with writer tokens left");
+    assert_eq!(get_token_balance(&mut test_env, other.mint_1).await, 5);
+    test_env.ctx.last_blockhash = test_env.ctx.get_new_latest_blockhash().await.unwrap();
+    assert!(redeem_writer_lots(&mut test_env, settled, 1, None)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn put_ask_partial_fill() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 3;
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    let (asset_mint, asset_acc) = (test_env.mint_2.pubkey(), test_env.buyer.mint_2);
+    mint_tokens(
+        &mut test_env,
+        &[
+            (collateral_mint, writer_temp, 10),
+            (asset_mint, asset_acc, 10),
+        ],
+    )
+    .await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_ask_lots(&mut test_env, contract_pda, 1).await;
+
+    // filled lots can be exercised while the rest of the ask still stands
+    let contract_pda = execute_lots(&mut test_env, contract_pda, &contract_type, 1)
+        .await
+        .expect("contract closed with lots unfilled");
+
+    let premium_acc = test_env.buyer_temp;
+    mint_tokens(&mut test_env, &[(asset_mint, premium_acc, 10)]).await;
+    let contract_pda = accept_ask_lots(&mut test_env, contract_pda, 2).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn call_ask_partial_cancel() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 3;
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    mint_tokens(&mut test_env, &[(collateral_mint, writer_temp, 10)]).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_ask_lots(&mut test_env, contract_pda, 1).await;
+
+    let refund_acc = test_env.writer.mint_1;
+    let refund_init_balance = get_token_balance(&mut test_env, refund_acc).await;
+    let ix = instruction::cancel_offer(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.writer.main],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        get_token_balance(&mut test_env, refund_acc).await,
+        refund_init_balance + 10,
+        "unfilled collateral not refunded"
+    );
+    assert_eq!(
+        get_token_balance(&mut test_env, writer_temp).await,
+        5,
+        "collateral of the filled lot not kept"
+    );
+    let (pda, _bump) = Pubkey::find_program_address(
+        &[&contract_pda.seed, &contract_pda.index_seed],
+        &test_env.program_key.pubkey(),
+    );
+    let pda_account = test_env
+        .ctx
+        .banks_client
+        .get_account(pda)
+        .await
+        .unwrap()
+        .expect("PDA account closed");
+    let expected_data = ContractPDA {
+        contract_state: ContractState::FINAL,
+        unfilled_lots: 0,
+        ..contract_pda
+    };
+    assert_eq!(
+        ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap(),
+        expected_data,
+        "incorrect PDA data"
+    );
+    execute(&mut test_env, expected_data, &contract_type).await;
+}
+
+#[tokio::test]
+async fn call_ask_ttl_partial_crank_cancel() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 3;
+    test_env.offer_ttl = Some(60_000);
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    mint_tokens(&mut test_env, &[(collateral_mint, writer_temp, 10)]).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 600_000).await;
+    let offer_expiry = contract_pda.offer_expiry.unwrap();
+
+    let program_id = test_env.program_key.pubkey();
+    let ix = instruction::crank_cancel(&program_id, &contract_pda).unwrap();
+    let err = process_cranked(&mut test_env, ix)
+        .await
+        .expect_err("crank cancelled a live offer");
+    assert_options_error(err, OptionsError::OfferNotExpired);
+    let contract_pda = accept_ask_lots(&mut test_env, contract_pda, 1).await;
+
+    // the option is still live, but its offer is not
+    let contract_data = contract_pda.contract_data.clone();
+    warp_to(&mut test_env, &contract_data, offer_expiry + 1).await;
+    let ix = instruction::accept_ask(
+        &program_id,
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer_temp,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let err = process_pooled(&mut test_env, ix, |env| &env.buyer.main)
+        .await
+        .expect_err("expired offer was taken");
+    assert_options_error(err, OptionsError::OfferExpired);
+
+    let refund_acc = test_env.writer.mint_1;
+    let refund_init_balance = get_token_balance(&mut test_env, refund_acc).await;
+    let ix = instruction::crank_cancel(&program_id, &contract_pda).unwrap();
+    process_cranked(&mut test_env, ix).await.unwrap();
+    assert_eq!(
+        get_token_balance(&mut test_env, refund_acc).await,
+        refund_init_balance + 10,
+        "unfilled collateral not refunded"
+    );
+    assert_eq!(get_token_balance(&mut test_env, writer_temp).await, 5);
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let expected_data = ContractPDA {
+        contract_state: ContractState::FINAL,
+        unfilled_lots: 0,
+        ..contract_pda
+    };
+    assert_eq!(get_contract(&mut test_env, pda).await, expected_data);
+    // the filled lot is unaffected
+    execute(&mut test_env, expected_data, &contract_type).await;
+}
+
+#[tokio::test]
+async fn put_bid_slot_ttl_crank_cancel() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.expiry_type = ExpiryType::SLOT;
+    test_env.offer_ttl = Some(0);
+    let contract_data = init_holder_mint(&mut test_env, &contract_type, 1000).await;
+    let ix = offer_instruction(&test_env, &init_mode, &contract_type, &contract_data);
+    let err = process_pooled(&mut test_env, ix, |env| &env.buyer.main)
+        .await
+        .expect_err("offer with an empty TTL did not fail");
+    assert_options_error(err, OptionsError::InvalidOfferTtl);
+
+    test_env.offer_ttl = Some(20);
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 1000).await;
+    let offer_expiry = contract_pda.offer_expiry.unwrap();
+    warp_to(&mut test_env, &contract_data, offer_expiry + 1).await;
+
+    // premium_temp and the rent go back to the buyer
+    let (buyer, buyer_temp) = (test_env.buyer.main.pubkey(), test_env.buyer_temp);
+    let rent_init_balance = get_lamports(&mut test_env, buyer).await;
+    let program_id = test_env.program_key.pubkey();
+    let ix = instruction::crank_cancel(&program_id, &contract_pda).unwrap();
+    process_cranked(&mut test_env, ix).await.unwrap();
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let pda_account = test_env.ctx.banks_client.get_account(pda).await.unwrap();
+    assert!(pda_account.is_none(), "cancelled bid not closed");
+    assert!(get_lamports(&mut test_env, buyer).await > rent_init_balance);
+    let temp = test_env
+        .ctx
+        .banks_client
+        .get_account(buyer_temp)
+        .await
+        .unwrap()
+        .unwrap();
+    let temp = spl_token::state::Account::unpack_from_slice(&temp.data[..]).unwrap();
+    assert_eq!((temp.owner, temp.amount), (buyer, 5));
+}
+
+#[tokio::test]
+async fn call_ask_private_execute() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.counterparty = Some(test_env.buyer.main.pubkey());
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 600_000).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn put_bid_private_rejects_other_writers() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.counterparty = Some(Pubkey::new_unique());
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 600_000).await;
+
+    let program_id = test_env.program_key.pubkey();
+    let receive_pub = writer_receive(
+        &test_env,
+        &contract_pda.contract_type,
+        &contract_pda.contract_data,
+    );
+    let ix = instruction::accept_bid(
+        &program_id,
+        &test_env.writer.main.pubkey(),
+        &test_env.writer_temp,
+        &receive_pub,
+        &test_env.writer.mint_2,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let err = process_pooled(&mut test_env, ix, |env| &env.writer.main)
+        .await
+        .expect_err("private bid was taken by another writer");
+    assert_options_error(err, OptionsError::CounterpartyNotAllowed);
+    cancel_offer(&mut test_env, contract_pda, &init_mode).await;
+}
+
+#[tokio::test]
+async fn call_ask_amend_premium() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 600_000).await;
+
+    // priced before the amendment
+    let program_id = test_env.program_key.pubkey();
+    let stale_ix = instruction::accept_ask(
+        &program_id,
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer_temp,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let ix = instruction::amend_offer(&program_id, &contract_pda, 7).unwrap();
+    process_pooled(&mut test_env, ix, |env| &env.writer.main)
+        .await
+        .unwrap();
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let mut amended = contract_pda;
+    amended.contract_data.premium_qty = 7;
+    amended.offer_seq = 1;
+    assert_eq!(get_contract(&mut test_env, pda).await, amended);
+
+    let (premium_mint, buyer_temp) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(&mut test_env, &[(premium_mint, buyer_temp, 2)]).await;
+    let err = process_pooled(&mut test_env, stale_ix, |env| &env.buyer.main)
+        .await
+        .expect_err("ask was taken at its old premium");
+    assert_options_error(err, OptionsError::OfferAmended);
+    let contract_pda = accept_ask(&mut test_env, amended).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn put_bid_amend_premium() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 600_000).await;
+    let program_id = test_env.program_key.pubkey();
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let (buyer_temp, premium_acc) = (test_env.buyer_temp, test_env.buyer.mint_2);
+    let premium_init_balance = get_token_balance(&mut test_env, premium_acc).await;
+
+    // the surplus is withdrawn, then the shortfall topped up
+    let mut contract_pda = contract_pda;
+    for (premium_qty, offer_seq) in [(3, 1), (4, 2)] {
+        let ix = instruction::amend_offer(&program_id, &contract_pda, premium_qty).unwrap();
+        process_pooled(&mut test_env, ix, |env| &env.buyer.main)
+            .await
+            .unwrap();
+        contract_pda.contract_data.premium_qty = premium_qty;
+        contract_pda.offer_seq = offer_seq;
+        assert_eq!(get_contract(&mut test_env, pda).await, contract_pda);
+        assert_eq!(
+            get_token_balance(&mut test_env, buyer_temp).await,
+            premium_qty,
+            "premium_temp does not escrow the amended premium"
+        );
+    }
+    assert_eq!(
+        get_token_balance(&mut test_env, premium_acc).await,
+        premium_init_balance + 1
+    );
+
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    let ix = instruction::amend_offer(&program_id, &contract_pda, 5).unwrap();
+    let err = process_pooled(&mut test_env, ix, |env| &env.buyer.main)
+        .await
+        .expect_err("filled bid was amended");
+    assert_options_error(err, OptionsError::InvalidContractState);
+}
+
+#[tokio::test]
+async fn call_bid_partial_expire() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    let (premium_mint, premium_acc) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(&mut test_env, &[(premium_mint, premium_acc, 5)]).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 1000).await;
+    let contract_pda = accept_bid_lots(&mut test_env, contract_pda, 1).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    expire_contract(&mut test_env, contract_pda, true).await;
+
+    // premium of the unfilled lot goes back with the temp account
+    let acc = test_env
+        .ctx
+        .banks_client
+        .get_account(test_env.buyer_temp)
+        .await
+        .unwrap()
+        .expect("could not find premium temp");
+    let info = spl_token::state::Account::unpack_from_slice(&acc.data[..]).unwrap();
+    assert_eq!(info.owner, test_env.buyer.main.pubkey());
+    assert_eq!(info.amount, 5);
+}
+
+#[tokio::test]
+async fn put_ask_cash_settled_execute() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    test_env.settlement = SettlementType::CASH;
+    test_env.oracle = Some(Pubkey::new_unique());
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    let (premium_mint, premium_acc) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(
+        &mut test_env,
+        &[
+            (collateral_mint, writer_temp, 5),
+            (premium_mint, premium_acc, 5),
+        ],
+    )
+    .await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+
+    // 5 tokens at 0.6 are worth 3 against a strike of 5
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+    let contract_pda = execute_cash_lots(&mut test_env, contract_pda, 1, 2)
+        .await
+        .expect("contract closed with a lot open");
+    // out of the money pays nothing and returns the whole lot of collateral
+    set_oracle_price(&mut test_env, 12, 1, 0).await;
+    assert!(execute_cash_lots(&mut test_env, contract_pda, 1, 0)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn put_ask_cash_settled_bad_oracle() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.settlement = SettlementType::CASH;
+    test_env.oracle = Some(Pubkey::new_unique());
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_1,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let oracle_index = ix.accounts.len() - 1;
+
+    // no price account yet, then a price older than the contract accepts
+    assert_execute_error(&mut test_env, ix.clone(), OptionsError::InvalidOracle).await;
+    set_oracle_price(&mut test_env, 6, 1, MAX_PRICE_AGE + 1).await;
+    assert_execute_error(&mut test_env, ix.clone(), OptionsError::StaleOraclePrice).await;
+
+    // a fresh price from an account the parties did not agree on
+    let agreed = test_env.oracle.replace(Pubkey::new_unique());
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+    let mut other = ix.clone();
+    other.accounts[oracle_index] = AccountMeta::new_readonly(test_env.oracle.unwrap(), false);
+    assert_execute_error(&mut test_env, other, OptionsError::InvalidOracle).await;
+
+    test_env.oracle = agreed;
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+    execute_cash_lots(&mut test_env, contract_pda, 1, 2).await;
+}
+
+#[tokio::test]
+async fn invalid_settlement_offers() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    // cash settlement without an oracle, auto-exercise of a physical contract or of
+    // one expiring at a slot
+    let cases = [
+        (
+            SettlementType::CASH,
+            None,
+            false,
+            ExpiryType::TIMESTAMP,
+            OptionsError::InvalidOracle,
+        ),
+        (
+            SettlementType::PHYSICAL,
+            None,
+            true,
+            ExpiryType::TIMESTAMP,
+            OptionsError::InvalidSettlement,
+        ),
+        (
+            SettlementType::CASH,
+            Some(Pubkey::new_unique()),
+            true,
+            ExpiryType::SLOT,
+            OptionsError::InvalidSettlement,
+        ),
+    ];
+    for (settlement, oracle, auto_exercise, expiry_type, expected) in cases {
+        let mut test_env = init_env(contract_type, &init_mode).await;
+        test_env.settlement = settlement;
+        test_env.oracle = oracle;
+        test_env.auto_exercise = auto_exercise;
+        test_env.expiry_type = expiry_type;
+        let contract_data = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+        let ix = offer_instruction(&test_env, &init_mode, &contract_type, &contract_data);
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            &[&test_env.ctx.payer, &test_env.writer.main],
+            test_env.ctx.last_blockhash,
+        );
+        let err = test_env
+            .ctx
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .expect_err("offer with invalid settlement did not fail");
+        assert_options_error(err, expected);
+    }
+}
+
+#[tokio::test]
+async fn put_ask_auto_exercise_settle() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    test_env.settlement = SettlementType::CASH;
+    test_env.oracle = Some(Pubkey::new_unique());
+    test_env.auto_exercise = true;
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    let (premium_mint, premium_acc) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(
+        &mut test_env,
+        &[
+            (collateral_mint, writer_temp, 5),
+            (premium_mint, premium_acc, 5),
+        ],
+    )
+    .await;
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    let program_id = test_env.program_key.pubkey();
+
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+    let settle = instruction::settle_at_expiry(&program_id, &contract_pda).unwrap();
+    assert_crank_error(
+        &mut test_env,
+        settle.clone(),
+        OptionsError::ContractNotExpired,
+    )
+    .await;
+
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    let crank = instruction::crank_expire(&program_id, &contract_pda).unwrap();
+    assert_crank_error(&mut test_env, crank, OptionsError::InvalidSettlement).await;
+    // published before expiry
+    set_oracle_price(&mut test_env, 6, 1, 2).await;
+    assert_crank_error(&mut test_env, settle, OptionsError::StaleOraclePrice).await;
+
+    // 5 tokens at 0.6 are worth 3 against a strike of 5
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+    settle_at_expiry(&mut test_env, contract_pda, 2).await;
+}
+
+#[tokio::test]
+async fn put_ask_auto_exercise_settle_window() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    test_env.settlement = SettlementType::CASH;
+    test_env.oracle = Some(Pubkey::new_unique());
+    test_env.auto_exercise = true;
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    let (premium_mint, premium_acc) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(
+        &mut test_env,
+        &[
+            (collateral_mint, writer_temp, 5),
+            (premium_mint, premium_acc, 5),
+        ],
+    )
+    .await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    let program_id = test_env.program_key.pubkey();
+    let settle = instruction::settle_at_expiry(&program_id, &contract_pda).unwrap();
+
+    // published after the settlement window
+    let expiry = contract_pda.contract_data.expiry_date;
+    let window = SETTLE_WINDOW * 1000;
+    warp_to(
+        &mut test_env,
+        &contract_pda.contract_data,
+        expiry + window + 1000,
+    )
+    .await;
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+    assert_crank_error(&mut test_env, settle, OptionsError::StaleOraclePrice).await;
+
+    // no price from the window can be loaded any more, so the lot expires unexercised
+    let closed = expiry + window + MAX_PRICE_AGE * 1000 + 1000;
+    warp_to(&mut test_env, &contract_pda.contract_data, closed).await;
+    settle_at_expiry(&mut test_env, contract_pda, 0).await;
+}
+
+#[tokio::test]
+async fn put_ask_auto_exercise_claims_follow_holder_tokens() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 3;
+    test_env.settlement = SettlementType::CASH;
+    test_env.oracle = Some(Pubkey::new_unique());
+    test_env.auto_exercise = true;
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    let (premium_mint, premium_acc) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(
+        &mut test_env,
+        &[
+            (collateral_mint, writer_temp, 10),
+            (premium_mint, premium_acc, 5),
+        ],
+    )
+    .await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_ask_lots(&mut test_env, contract_pda, 2).await;
+
+    // a second buyer takes the last lot and becomes the stored buyer
+    let second = transfer_holder_tokens(&mut test_env, 0).await;
+    mint_tokens(&mut test_env, &[(premium_mint, second.mint_2, 5)]).await;
+    let program_id = test_env.program_key.pubkey();
+    let ix = instruction::accept_ask(
+        &program_id,
+        &second.main.pubkey(),
+        &second.mint_2,
+        &second.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &second.main],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let contract_pda = get_contract(&mut test_env, pda).await;
+    assert_eq!(
+        contract_pda.buyer_data.as_ref().unwrap().party_pub,
+        second.main.pubkey()
+    );
+
+    // and the first buyer sells one of its lots on
+    let third = transfer_holder_tokens(&mut test_env, 1).await;
+
+    // 5 tokens at 0.6 are worth 3 against a strike of 5
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+    let ix = instruction::settle_at_expiry(&program_id, &contract_pda).unwrap();
+    process_cranked(&mut test_env, ix).await.unwrap();
+    let settled = ContractPDA {
+        contract_state: ContractState::SETTLED,
+        settle_payout: 2,
+        ..contract_pda
+    };
+    assert_eq!(get_contract(&mut test_env, pda).await, settled);
+
+    // each holder claims its own lot, whoever bought it
+    let settled = claim_settlement_lots(&mut test_env, settled, 1, Some(&third))
+        .await
+        .unwrap();
+    assert_eq!(get_token_balance(&mut test_env, third.mint_1).await, 2);
+    let settled = redeem_writer_lots(&mut test_env, settled, 3, None)
+        .await
+        .expect("contract closed with holder lots unclaimed");
+    let settled = claim_settlement_lots(&mut test_env, settled, 1, Some(&second))
+        .await
+        .unwrap();
+    assert!(claim_settlement_lots(&mut test_env, settled, 1, None)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn put_bid_auto_exercise_out_of_the_money() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    test_env.settlement = SettlementType::CASH;
+    test_env.oracle = Some(Pubkey::new_unique());
+    test_env.auto_exercise = true;
+    let (premium_mint, premium_acc) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(&mut test_env, &[(premium_mint, premium_acc, 5)]).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 1000).await;
+    let contract_pda = accept_bid_lots(&mut test_env, contract_pda, 1).await;
+
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    set_oracle_price(&mut test_env, 12, 1, 0).await;
+    settle_at_expiry(&mut test_env, contract_pda, 0).await;
+
+    // the unfilled lot's premium goes back with its temp account
+    let acc = test_env
+        .ctx
+        .banks_client
+        .get_account(test_env.buyer_temp)
+        .await
+        .unwrap()
+        .expect("could not find premium temp");
+    let info = spl_token::state::Account::unpack_from_slice(&acc.data[..]).unwrap();
+    assert_eq!(info.owner, test_env.buyer.main.pubkey());
+    assert_eq!(info.amount, 5);
+}
+
+#[tokio::test]
+async fn put_ask_settle_without_auto_exercise() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.settlement = SettlementType::CASH;
+    test_env.oracle = Some(Pubkey::new_unique());
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 1000).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+    let settle =
+        instruction::settle_at_expiry(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+    assert_crank_error(&mut test_env, settle, OptionsError::InvalidSettlement).await;
+    expire_contract(&mut test_env, contract_pda, true).await;
+}
+
+#[tokio::test]
+async fn call_bid_transferred_holder_execute() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    let holder = transfer_holder_tokens(&mut test_env, 1).await;
+    let strike_mint = test_env.mint_2.pubkey();
+    mint_tokens(&mut test_env, &[(strike_mint, holder.mint_2, 5)]).await;
+
+    let (pda, _bump) = Pubkey::find_program_address(
+        &[&contract_pda.seed, &contract_pda.index_seed],
+        &test_env.program_key.pubkey(),
+    );
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &holder.main.pubkey(),
+        &holder.mint_2,
+        &holder.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+
+    // the rent refund belongs to the original buyer, not whoever exercises
+    let mut wrong_buyer = ix.clone();
+    wrong_buyer.accounts[10].pubkey = holder.main.pubkey();
+    let tx = Transaction::new_signed_with_payer(
+        &[wrong_buyer],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &holder.main],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("rent refund redirected to holder");
+    assert_options_error(err, OptionsError::IncorrectBuyerAccount);
+
+    let (buyer_receive, writer_receive) = (test_env.buyer.mint_1, test_env.writer_receive_2);
+    let holder_lamports = get_lamports(&mut test_env, holder.main.pubkey()).await;
+    let buyer_init_balance = get_token_balance(&mut test_env, buyer_receive).await;
+    let writer_init_balance = get_token_balance(&mut test_env, writer_receive).await;
+
+    println!("sending execute transaction from holder...");
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &holder.main],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+
+    assert_eq!(get_token_balance(&mut test_env, holder.mint_1).await, 5);
+    assert_eq!(get_token_balance(&mut test_env, holder.mint_2).await, 0);
+    assert_eq!(
+        get_token_balance(&mut test_env, writer_receive).await,
+        writer_init_balance + 5,
+        "incorrect strike amount transferred"
+    );
+    assert_eq!(
+        get_token_balance(&mut test_env, buyer_receive).await,
+        buyer_init_balance,
+        "original buyer paid out"
+    );
+    assert_eq!(
+        get_lamports(&mut test_env, holder.main.pubkey()).await,
+        holder_lamports
+    );
+
+    // the PDA rent goes back to the original buyer once the writer redeems
+    let settled = assert_settled(&mut test_env, pda, contract_pda).await;
+    assert!(redeem_writer_lots(&mut test_env, settled, 1, None)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn put_ask_transferred_holder_cash_execute() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    test_env.settlement = SettlementType::CASH;
+    test_env.oracle = Some(Pubkey::new_unique());
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    let (premium_mint, premium_acc) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(
+        &mut test_env,
+        &[
+            (collateral_mint, writer_temp, 5),
+            (premium_mint, premium_acc, 5),
+        ],
+    )
+    .await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    let holder = transfer_holder_tokens(&mut test_env, 1).await;
+    set_oracle_price(&mut test_env, 6, 1, 0).await;
+
+    // cash settlement pays the holder in the strike mint, which for a put is mint_1
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &holder.main.pubkey(),
+        &holder.mint_1,
+        &holder.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &holder.main],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+    assert_eq!(get_token_balance(&mut test_env, holder.mint_1).await, 2);
+
+    // the buyer exercises the lot they kept as before
+    let contract_pda = ContractPDA {
+        open_lots: 1,
+        ..contract_pda
+    };
+    assert!(execute_cash_lots(&mut test_env, contract_pda, 1, 2)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn call_bid_transferred_writer_redeem() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    fund_extra_lots(&mut test_env, contract_type).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    let redeemer = transfer_writer_tokens(&mut test_env, &contract_pda, 1).await;
+
+    // nothing to redeem while holders can still exercise
+    let ix = instruction::redeem_writer(
+        &test_env.program_key.pubkey(),
+        &redeemer.main.pubkey(),
+        &redeemer.mint_1,
+        &redeemer.mint_2,
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &redeemer.main],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("redeem before settlement did not fail");
+    assert_options_error(err, OptionsError::ContractNotSettled);
+    test_env.ctx.last_blockhash = test_env.ctx.get_new_latest_blockhash().await.unwrap();
+
+    let (pda, _bump) = Pubkey::find_program_address(
+        &[&contract_pda.seed, &contract_pda.index_seed],
+        &test_env.program_key.pubkey(),
+    );
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        2,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.buyer.main],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+
+    // the strike paid for both lots is split between the two token holders
+    let settled = assert_settled(&mut test_env, pda, contract_pda).await;
+    let settled = redeem_writer_lots(&mut test_env, settled, 1, Some(&redeemer))
+        .await
+        .expect("contract closed with writer tokens left");
+    assert_eq!(get_token_balance(&mut test_env, redeemer.mint_2).await, 5);
+    assert!(redeem_writer_lots(&mut test_env, settled, 1, None)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn put_ask_split_redeem_after_expire() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    fund_extra_lots(&mut test_env, contract_type).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 1000).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+
+    let (pda, _bump) = Pubkey::find_program_address(
+        &[&contract_pda.seed, &contract_pda.index_seed],
+        &test_env.program_key.pubkey(),
+    );
+    let ix = instruction::crank_expire(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+
+    let settled = assert_settled(&mut test_env, pda, contract_pda).await;
+    let settled = redeem_writer_lots(&mut test_env, settled, 1, None)
+        .await
+        .expect("contract closed with writer tokens left");
+    test_env.ctx.last_blockhash = test_env.ctx.get_new_latest_blockhash().await.unwrap();
+    assert!(redeem_writer_lots(&mut test_env, settled, 1, None)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn call_ask_partial_expire_redeem() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 3;
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    mint_tokens(&mut test_env, &[(collateral_mint, writer_temp, 10)]).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 1000).await;
+    let contract_pda = accept_ask_lots(&mut test_env, contract_pda, 1).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+
+    // the two unfilled lots go back to the writer, the filled one to its writer token
+    let refund_acc = test_env.writer.mint_1;
+    let refund_init_balance = get_token_balance(&mut test_env, refund_acc).await;
+    expire_contract(&mut test_env, contract_pda, true).await;
+    assert_eq!(
+        get_token_balance(&mut test_env, refund_acc).await,
+        refund_init_balance + 10 + 5,
+        "unfilled collateral not refunded"
+    );
+}
+
+#[tokio::test]
+async fn call_ask_close_out() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    fund_extra_lots(&mut test_env, contract_type).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_data = contract_pda.contract_data.clone();
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    give_writer_holder_tokens(&mut test_env, 2).await;
+    let contract_pda = close_out_lots(&mut test_env, contract_pda, 1)
+        .await
+        .expect("contract closed with a lot open");
+    assert!(close_out_lots(&mut test_env, contract_pda, 1)
+        .await
+        .is_none());
+    // closed out lots were neither exercised nor expired
+    assert_series(&mut test_env, contract_type, &contract_data, (0, 0, 0)).await;
+}
+
+#[tokio::test]
+async fn call_ask_close_out_after_expiry() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    give_writer_holder_tokens(&mut test_env, 1).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+
+    let ix = instruction::close_out(
+        &test_env.program_key.pubkey(),
+        &test_env.writer.main.pubkey(),
+        &test_env.writer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let err = process_pooled(&mut test_env, ix, |env| &env.writer.main)
+        .await
+        .expect_err("expired contract was closed out");
+    assert_options_error(err, OptionsError::ContractExpired);
+    expire_contract(&mut test_env, contract_pda, true).await;
+}
+
+#[tokio::test]
+async fn put_bid_close_out_after_exercise() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.lots = 2;
+    fund_extra_lots(&mut test_env, contract_type).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    let contract_pda = execute_lots(&mut test_env, contract_pda, &contract_type, 1)
+        .await
+        .expect("contract closed with a lot open");
+    give_writer_holder_tokens(&mut test_env, 1).await;
+
+    // the exercised lot's writer token still has the asset to redeem
+    let settled = close_out_lots(&mut test_env, contract_pda, 1)
+        .await
+        .expect("contract closed with writer tokens left");
+    assert!(redeem_writer_lots(&mut test_env, settled, 1, None)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn close_out_without_writer_tokens() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+
+    // holding the option alone releases nothing
+    let ix = instruction::close_out(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.buyer.main],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("close out without writer tokens did not fail");
+    assert_eq!(
+        err.unwrap(),
+        TransactionError::InstructionError(0, InstructionError::IllegalOwner)
+    );
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn writer_tokens_of_another_contract() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::BUYER).await;
+    let terms = init_holder_mint(&mut test_env, &contract_type, 1000).await;
+    let (collateral_mint, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    let (premium_mint, buyer_temp) = (test_env.mint_2.pubkey(), test_env.buyer_temp);
+    mint_tokens(
+        &mut test_env,
+        &[
+            (collateral_mint, writer_temp, 5),
+            (premium_mint, buyer_temp, 5),
+        ],
+    )
+    .await;
+    approve_quote_authority(&mut test_env, InitParty::WRITER, writer_temp, 10).await;
+
+    // two contracts of one series, a lot of each written by the writer
+    let program_id = test_env.program_key.pubkey();
+    let mut contracts = vec![];
+    for nonce in [1, 2] {
+        let quote = quote(
+            &mut test_env,
+            InitParty::WRITER,
+            contract_type,
+            &terms,
+            nonce,
+        )
+        .await;
+        accept_quote(&mut test_env, &quote, Some(&quote))
+            .await
+            .unwrap();
+        let pda = instruction::get_data_pda(
+            &program_id,
+            &terms.get_seed(),
+            &get_seed(&quote.index_data()),
+        );
+        contracts.push((pda, get_contract(&mut test_env, pda).await));
+    }
+    let (other_pda, other) = contracts.remove(0);
+    let (pda, contract_pda) = contracts.remove(0);
+    let (other_mint, _bump) = instruction::get_contract_writer_mint(&program_id, &other_pda);
+    let writer = test_env.writer.main.pubkey();
+    let other_tokens = get_associated_token_address(&writer, &other_mint);
+    assert_ne!(other_tokens, test_env.writer_tokens);
+    let signer: fn(&TestEnv) -> &keypair::Keypair = |env| &env.writer.main;
+
+    // the writer token of one contract releases nothing of the other
+    give_writer_holder_tokens(&mut test_env, 1).await;
+    let mut ix = instruction::close_out(
+        &program_id,
+        &writer,
+        &test_env.writer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        1,
+    )
+    .unwrap();
+    ix.accounts[4].pubkey = other_tokens;
+    ix.accounts[5].pubkey = other_mint;
+    let err = process_pooled(&mut test_env, ix, signer)
+        .await
+        .expect_err("closed out with another contract's writer token");
+    assert_options_error(err, OptionsError::IncorrectWriterMint);
+
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    let ix = instruction::crank_expire(&program_id, &contract_pda).unwrap();
+    process_cranked(&mut test_env, ix).await.unwrap();
+    let settled = assert_settled(&mut test_env, pda, contract_pda).await;
+    let mut ix = instruction::redeem_writer(
+        &program_id,
+        &writer,
+        &test_env.writer.mint_1,
+        &test_env.writer.mint_2,
+        &settled,
+        1,
+    )
+    .unwrap();
+    ix.accounts[1].pubkey = other_tokens;
+    ix.accounts[2].pubkey = other_mint;
+    let err = process_pooled(&mut test_env, ix, signer)
+        .await
+        .expect_err("redeemed another contract's writer token");
+    assert_options_error(err, OptionsError::IncorrectWriterMint);
+
+    assert!(redeem_writer_lots(&mut test_env, settled, 1, None)
+        .await
+        .is_none());
+    assert_eq!(get_token_balance(&mut test_env, other_tokens).await, 1);
+    assert_eq!(get_contract(&mut test_env, other_pda).await, other);
+}
+
+#[tokio::test]
+async fn call_pooled_series_exercise_and_redeem() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::WRITER).await;
+    test_env.pooled = true;
+    let ask = init_contract(&mut test_env, &InitMode::WRITER, &contract_type, 10000).await;
+    let contract_data = ask.contract_data.clone();
+    assert!(accept_pooled_lots(&mut test_env, ask, 1).await.is_none());
+
+    // a bid on the same terms fills into the same vault
+    let (mint_1, mint_2) = (test_env.mint_1.pubkey(), test_env.mint_2.pubkey());
+    let (buyer_temp, writer_temp) = (test_env.buyer_temp, test_env.writer_temp);
+    mint_tokens(
+        &mut test_env,
+        &[(mint_2, buyer_temp, 5), (mint_1, writer_temp, 5)],
+    )
+    .await;
+    let bid = pooled_offer(
+        &mut test_env,
+        &InitMode::BUYER,
+        contract_type,
+        &contract_data,
+    )
+    .await;
+    assert!(accept_pooled_lots(&mut test_env, bid, 1).await.is_none());
+
+    let program_id = test_env.program_key.pubkey();
+    let (vault, _bump) =
+        instruction::get_series_vault(&program_id, contract_type, &contract_data.series_seed());
+    let (proceeds, _bump) =
+        instruction::get_series_proceeds(&program_id, contract_type, &contract_data.series_seed());
+    assert_eq!(get_token_balance(&mut test_env, vault).await, 10);
+
+    let (buyer_asset, buyer_holder) = (test_env.buyer.mint_1, test_env.buyer_holder);
+    let asset_init_balance = get_token_balance(&mut test_env, buyer_asset).await;
+    let ix = instruction::exercise_pooled(
+        &program_id,
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        contract_type,
+        &contract_data,
+        1,
+    )
+    .unwrap();
+    // the series vaults swapped for one another
+    for (index, spoof) in [(6, proceeds), (7, vault)] {
+        assert_spoof_error(
+            &mut test_env,
+            &ix,
+            |env| &env.buyer.main,
+            index,
+            spoof,
+            OptionsError::IncorrectSeriesVault,
+        )
+        .await;
+    }
+    process_pooled(&mut test_env, ix, |env| &env.buyer.main)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&mut test_env, buyer_asset).await,
+        asset_init_balance + 5
+    );
+    assert_eq!(get_token_balance(&mut test_env, buyer_holder).await, 1);
+    assert_eq!(get_token_balance(&mut test_env, vault).await, 5);
+    assert_eq!(get_token_balance(&mut test_env, proceeds).await, 5);
+
+    let writer = test_env.writer.main.pubkey();
+    let (writer_asset, writer_strike) = (test_env.writer.mint_1, test_env.writer.mint_2);
+    let ix = instruction::redeem_pooled(
+        &program_id,
+        &writer,
+        &writer_asset,
+        &writer_strike,
+        contract_type,
+        &contract_data,
+        2,
+    );
+    let err = process_pooled(&mut test_env, ix.clone(), |env| &env.writer.main)
+        .await
+        .expect_err("redeem before expiry did not fail");
+    assert_options_error(err, OptionsError::ContractNotExpired);
+
+    // the two writers share what the exercised lot left behind
+    warp_to(&mut test_env, &contract_data, contract_data.expiry_date + 1).await;
+    let asset_init_balance = get_token_balance(&mut test_env, writer_asset).await;
+    let strike_init_balance = get_token_balance(&mut test_env, writer_strike).await;
+    process_pooled(&mut test_env, ix, |env| &env.writer.main)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&mut test_env, writer_asset).await,
+        asset_init_balance + 5
+    );
+    assert_eq!(
+        get_token_balance(&mut test_env, writer_strike).await,
+        strike_init_balance + 5
+    );
+    assert_eq!(get_token_balance(&mut test_env, vault).await, 0);
+    assert_eq!(get_token_balance(&mut test_env, proceeds).await, 0);
+    let writer_tokens = test_env.writer_tokens;
+    assert_eq!(get_token_balance(&mut test_env, writer_tokens).await, 0);
+    // the lot left open at expiry is retired by the redemption
+    assert_series(&mut test_env, contract_type, &contract_data, (0, 1, 1)).await;
+}
+
+#[tokio::test]
+async fn put_pooled_bid_close_out_and_cancel() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    test_env.pooled = true;
+    test_env.lots = 2;
+    fund_extra_lots(&mut test_env, contract_type).await;
+    let bid = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let bid = accept_pooled_lots(&mut test_env, bid, 1)
+        .await
+        .expect("contract closed with a lot unfilled");
+
+    // the filled lot is backed by the vault, not by this contract
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &bid,
+        1,
+    )
+    .unwrap();
+    assert_execute_error(&mut test_env, ix, OptionsError::ContractPooled).await;
+
+    give_writer_holder_tokens(&mut test_env, 1).await;
+    let collateral_dest = test_env.writer.mint_1;
+    let collateral_init_balance = get_token_balance(&mut test_env, collateral_dest).await;
+    let ix = instruction::close_out_pooled(
+        &test_env.program_key.pubkey(),
+        &test_env.writer.main.pubkey(),
+        &test_env.writer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        contract_type,
+        &bid.contract_data,
+        1,
+    );
+    process_pooled(&mut test_env, ix, |env| &env.writer.main)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(&mut test_env, collateral_dest).await,
+        collateral_init_balance + 5
+    );
+    let (vault, _bump) = instruction::get_series_vault(
+        &test_env.program_key.pubkey(),
+        contract_type,
+        &bid.series_seed,
+    );
+    assert_eq!(get_token_balance(&mut test_env, vault).await, 0);
+
+    cancel_offer(&mut test_env, bid, &init_mode).await;
+}
+
+#[tokio::test]
+async fn call_pooled_series_across_premium_types() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::WRITER).await;
+    test_env.pooled = true;
+    let ask = init_contract(&mut test_env, &InitMode::WRITER, &contract_type, 10000).await;
+    let contract_data = ask.contract_data.clone();
+    assert!(accept_pooled_lots(&mut test_env, ask, 1).await.is_none());
+
+    // an ask paid for in the underlying is a different offer of the same series
+    let (mint_1, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    let buyer_asset = test_env.buyer.mint_1;
+    mint_tokens(
+        &mut test_env,
+        &[(mint_1, writer_temp, 5), (mint_1, buyer_asset, 5)],
+    )
+    .await;
+    let other_premium = ContractData {
+        premium_type: mint_1,
+        ..contract_data.clone()
+    };
+    let ask = pooled_offer(
+        &mut test_env,
+        &InitMode::WRITER,
+        contract_type,
+        &other_premium,
+    )
+    .await;
+    assert_ne!(ask.seed, contract_data.get_seed());
+    assert_eq!(ask.series_seed, contract_data.series_seed());
+
+    let ix = instruction::accept_ask(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &buyer_asset,
+        &buyer_asset,
+        &test_env.holder_mint.pubkey(),
+        &ask,
+        1,
+    )
+    .unwrap();
+    process_pooled(&mut test_env, ix, |env| &env.buyer.main)
+        .await
+        .unwrap();
+    let writer_premium = test_env.writer.mint_1;
+    assert_eq!(get_token_balance(&mut test_env, writer_premium).await, 5);
+
+    let (vault, _bump) = instruction::get_series_vault(
+        &test_env.program_key.pubkey(),
+        contract_type,
+        &contract_data.series_seed(),
+    );
+    assert_eq!(get_token_balance(&mut test_env, vault).await, 10);
+    let buyer_holder = test_env.buyer_holder;
+    assert_eq!(get_token_balance(&mut test_env, buyer_holder).await, 2);
+    assert_series(&mut test_env, contract_type, &other_premium, (2, 0, 0)).await;
+}
+
+#[tokio::test]
+async fn call_order_book_cross_and_rest() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::WRITER).await;
+    test_env.pooled = true;
+    let terms = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+    let order = |premium_qty, lots| ContractData {
+        premium_qty,
+        lots,
+        ..terms.clone()
+    };
+    let (mint_1, mint_2) = (test_env.mint_1.pubkey(), test_env.mint_2.pubkey());
+    let (buyer_temp, writer_temp) = (test_env.buyer_temp, test_env.writer_temp);
+    // writer_temp already holds one lot of collateral and buyer_temp one lot's premium
+    mint_tokens(
+        &mut test_env,
+        &[(mint_1, writer_temp, 10), (mint_2, buyer_temp, 15)],
+    )
+    .await;
+
+    // asks rest cheapest first
+    let ask_high = place_order(
+        &mut test_env,
+        &InitMode::WRITER,
+        contract_type,
+        &order(6, 1),
+        1,
+        &[],
+    )
+    .await
+    .unwrap()
+    .expect("unmatched ask did not rest");
+    let ask_low = place_order(
+        &mut test_env,
+        &InitMode::WRITER,
+        contract_type,
+        &order(4, 1),
+        2,
+        &[],
+    )
+    .await
+    .unwrap()
+    .expect("unmatched ask did not rest");
+    let book = get_order_book(&mut test_env, contract_type, &terms).await;
+    let ask_high_pda = book.asks[1].data_pda;
+    let ask_low_pda = book.asks[0].data_pda;
+    assert_eq!(
+        book.asks.iter().map(|o| o.premium_qty).collect::<Vec<_>>(),
+        vec![4, 6]
+    );
+
+    // a bid for two lots at 5 takes the ask at 4 and rests the other lot
+    let writer_premium = test_env.writer.mint_2;
+    let premium_init_balance = get_token_balance(&mut test_env, writer_premium).await;
+    let bid = place_order(
+        &mut test_env,
+        &InitMode::BUYER,
+        contract_type,
+        &order(5, 2),
+        3,
+        &[(ask_low_pda, Some(&ask_low))],
+    )
+    .await
+    .unwrap()
+    .expect("unmatched lot did not rest");
+    assert_eq!(bid.unfilled_lots, 1);
+    assert_eq!(
+        get_token_balance(&mut test_env, writer_premium).await,
+        premium_init_balance + 4
+    );
+    // the buyer keeps the price improvement
+    assert_eq!(
+        get_token_balance(&mut test_env, buyer_temp).await,
+        20 - 4 - 5
+    );
+    let buyer_holder = test_env.buyer_holder;
+    assert_eq!(get_token_balance(&mut test_env, buyer_holder).await, 1);
+    let gone = test_env.ctx.banks_client.get_account(ask_low_pda).await;
+    assert!(gone.unwrap().is_none(), "filled ask not closed");
+    let book = get_order_book(&mut test_env, contract_type, &terms).await;
+    let bid_pda = book.bids[0].data_pda;
+    assert_eq!((book.bids.len(), book.asks.len()), (1, 1));
+    assert_eq!((book.bids[0].premium_qty, book.bids[0].lots), (5, 1));
+
+    // an ask at 5 fills the resting bid without resting itself
+    assert!(place_order(
+        &mut test_env,
+        &InitMode::WRITER,
+        contract_type,
+        &order(5, 1),
+        4,
+        &[(bid_pda, Some(&bid))],
+    )
+    .await
+    .unwrap()
+    .is_none());
+    assert_eq!(
+        get_token_balance(&mut test_env, writer_premium).await,
+        premium_init_balance + 9
+    );
+    assert_eq!(get_token_balance(&mut test_env, buyer_holder).await, 2);
+    let (vault, _bump) = instruction::get_series_vault(
+        &test_env.program_key.pubkey(),
+        contract_type,
+        &terms.series_seed(),
+    );
+    assert_eq!(get_token_balance(&mut test_env, vault).await, 10);
+    let book = get_order_book(&mut test_env, contract_type, &terms).await;
+    assert_eq!((book.bids.len(), book.asks.len()), (0, 1));
+
+    // an order cancelled outside the book is dropped by the next order to reach it
+    let ix = instruction::cancel_offer(&test_env.program_key.pubkey(), &ask_high).unwrap();
+    process_pooled(&mut test_env, ix, |env| &env.writer.main)
+        .await
+        .unwrap();
+    let bid = place_order(
+        &mut test_env,
+        &InitMode::BUYER,
+        contract_type,
+        &order(7, 1),
+        5,
+        &[(ask_high_pda, None)],
+    )
+    .await
+    .unwrap()
+    .expect("unmatched bid did not rest");
+    let book = get_order_book(&mut test_env, contract_type, &terms).await;
+    assert_eq!((book.bids.len(), book.asks.len()), (1, 0));
+
+    let program_id = test_env.program_key.pubkey();
+    let ix = instruction::cancel_order(&program_id, &bid).unwrap();
+    // the book of the same series paid for in another currency
+    let (other_book, _bump) =
+        instruction::get_order_book(&program_id, contract_type, &terms.series_seed(), &mint_1);
+    assert_spoof_error(
+        &mut test_env,
+        &ix,
+        |env| &env.buyer.main,
+        0,
+        other_book,
+        OptionsError::IncorrectOrderBook,
+    )
+    .await;
+    process_pooled(&mut test_env, ix, |env| &env.buyer.main)
+        .await
+        .unwrap();
+    let book = get_order_book(&mut test_env, contract_type, &terms).await;
+    assert_eq!((book.bids.len(), book.asks.len()), (0, 0));
+    assert_series(&mut test_env, contract_type, &terms, (2, 0, 0)).await;
+
+    // an offer of the series made outside the book
+    let buyer = test_env.buyer.main.pubkey();
+    test_env.buyer_temp = create_token_account(&mut test_env, mint_2, &buyer).await;
+    let buyer_temp = test_env.buyer_temp;
+    mint_tokens(&mut test_env, &[(mint_2, buyer_temp, 5)]).await;
+    let offer = pooled_offer(&mut test_env, &InitMode::BUYER, contract_type, &order(5, 1)).await;
+    let ix = instruction::cancel_order(&program_id, &offer).unwrap();
+    let err = process_pooled(&mut test_env, ix, |env| &env.buyer.main)
+        .await
+        .expect_err("cancelling an order not on the book did not fail");
+    assert_options_error(err, OptionsError::OrderNotFound);
+}
+
+#[tokio::test]
+async fn call_order_book_amend_reprices() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::WRITER).await;
+    test_env.pooled = true;
+    let terms = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+    let order = |premium_qty, lots| ContractData {
+        premium_qty,
+        lots,
+        ..terms.clone()
+    };
+    let (mint_1, writer_temp) = (test_env.mint_1.pubkey(), test_env.writer_temp);
+    mint_tokens(&mut test_env, &[(mint_1, writer_temp, 10)]).await;
+
+    let ask_high = place_order(
+        &mut test_env,
+        &InitMode::WRITER,
+        contract_type,
+        &order(6, 1),
+        1,
+        &[],
+    )
+    .await
+    .unwrap()
+    .expect("unmatched ask did not rest");
+    place_order(
+        &mut test_env,
+        &InitMode::WRITER,
+        contract_type,
+        &order(4, 1),
+        2,
+        &[],
+    )
+    .await
+    .unwrap()
+    .expect("unmatched ask did not rest");
+    let book = get_order_book(&mut test_env, contract_type, &terms).await;
+    let ask_high_pda = book.asks[1].data_pda;
+
+    // the amended ask moves ahead of the one at 4
+    let program_id = test_env.program_key.pubkey();
+    let ix = instruction::amend_offer(&program_id, &ask_high, 3).unwrap();
+    process_pooled(&mut test_env, ix, |env| &env.writer.main)
+        .await
+        .unwrap();
+    let book = get_order_book(&mut test_env, contract_type, &terms).await;
+    assert_eq!(book.asks[0].data_pda, ask_high_pda);
+    assert_eq!(
+        book.asks.iter().map(|o| o.premium_qty).collect::<Vec<_>>(),
+        vec![3, 4]
+    );
+
+    // and crosses a bid at its new premium
+    let mut ask_high = ask_high;
+    ask_high.contract_data.premium_qty = 3;
+    ask_high.offer_seq = 1;
+    let writer_premium = test_env.writer.mint_2;
+    let premium_init_balance = get_token_balance(&mut test_env, writer_premium).await;
+    assert!(place_order(
+        &mut test_env,
+        &InitMode::BUYER,
+        contract_type,
+        &order(3, 1),
+        3,
+        &[(ask_high_pda, Some(&ask_high))],
+    )
+    .await
+    .unwrap()
+    .is_none());
+    assert_eq!(
+        get_token_balance(&mut test_env, writer_premium).await,
+        premium_init_balance + 3
+    );
+    let book = get_order_book(&mut test_env, contract_type, &terms).await;
+    assert_eq!((book.bids.len(), book.asks.len()), (0, 1));
+}
+
+#[tokio::test]
+async fn order_book_rejects_non_pooled_orders() {
+    let contract_type = ContractType::PUT;
+    let mut test_env = init_env(contract_type, &InitMode::BUYER).await;
+    let terms = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+    let err = place_order(
+        &mut test_env,
+        &InitMode::BUYER,
+        contract_type,
+        &terms,
+        1,
+        &[],
+    )
+    .await
+    .expect_err("non-pooled order did not fail");
+    assert_options_error(err, OptionsError::ContractNotPooled);
+}
+
+#[tokio::test]
+async fn order_book_rejects_private_orders() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::WRITER).await;
+    test_env.pooled = true;
+    test_env.counterparty = Some(test_env.buyer.main.pubkey());
+    let terms = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+    let err = place_order(
+        &mut test_env,
+        &InitMode::WRITER,
+        contract_type,
+        &terms,
+        1,
+        &[],
+    )
+    .await
+    .expect_err("private order did not fail");
+    assert_options_error(err, OptionsError::PrivateOrder);
+}
+
+#[tokio::test]
+async fn call_ask_quote_execute() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::BUYER).await;
+    let terms = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+    let quote = quote(&mut test_env, InitParty::WRITER, contract_type, &terms, 1).await;
+    let writer_temp = test_env.writer_temp;
+    approve_quote_authority(&mut test_env, InitParty::WRITER, writer_temp, 5).await;
+
+    // lamports sent to the quote's addresses ahead of time must not block it
+    let program_id = test_env.program_key.pubkey();
+    let pda = instruction::get_data_pda(
+        &program_id,
+        &terms.get_seed(),
+        &get_seed(&quote.index_data()),
+    );
+    let (nonce, _bump) = instruction::get_quote_nonce(&program_id, &quote.maker, quote.nonce);
+    let (writer_mint, _bump) = instruction::get_contract_writer_mint(&program_id, &pda);
+    let payer = test_env.ctx.payer.pubkey();
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::transfer(&payer, &pda, 1_000_000),
+            system_instruction::transfer(&payer, &nonce, 1_000_000_000),
+            system_instruction::transfer(&payer, &writer_mint, 1_000_000),
+        ],
+        Some(&payer),
+        &[&test_env.ctx.payer],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+
+    let writer_premium = test_env.writer.mint_2;
+    let premium_init_balance = get_token_balance(&mut test_env, writer_premium).await;
+    accept_quote(&mut test_env, &quote, Some(&quote))
+        .await
+        .unwrap();
+    let contract = get_contract(&mut test_env, pda).await;
+    assert_eq!(contract.contract_state, ContractState::FINAL);
+    assert_eq!(
+        (
+            contract.open_lots,
+            contract.unfilled_lots,
+            contract.writer_lots
+        ),
+        (1, 0, 1)
+    );
+    assert_eq!(contract.init_party, InitParty::WRITER);
+    assert_eq!(
+        get_token_balance(&mut test_env, writer_premium).await,
+        premium_init_balance + 5
+    );
+    let asset_temp = contract.writer_data.as_ref().unwrap().temp_pub;
+    assert_eq!(get_token_balance(&mut test_env, asset_temp).await, 5);
+    assert_eq!(get_token_balance(&mut test_env, writer_temp).await, 0);
+    let (buyer_holder, writer_tokens) = (test_env.buyer_holder, test_env.writer_tokens);
+    assert_eq!(get_token_balance(&mut test_env, buyer_holder).await, 1);
+    assert_eq!(get_token_balance(&mut test_env, writer_tokens).await, 1);
+    assert_series(&mut test_env, contract_type, &terms, (1, 0, 0)).await;
+
+    let err = accept_quote(&mut test_env, &quote, Some(&quote))
+        .await
+        .expect_err("accepting a quote twice did not fail");
+    assert_options_error_at(err, 1, OptionsError::QuoteAlreadyAccepted);
+
+    // settles like any accepted offer
+    execute(&mut test_env, contract, &contract_type).await;
+}
+
+#[tokio::test]
+async fn put_pooled_bid_quote() {
+    let contract_type = ContractType::PUT;
+    let mut test_env = init_env(contract_type, &InitMode::WRITER).await;
+    test_env.pooled = true;
+    let terms = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+    let quote = quote(&mut test_env, InitParty::BUYER, contract_type, &terms, 2).await;
+    let buyer_temp = test_env.buyer_temp;
+    approve_quote_authority(&mut test_env, InitParty::BUYER, buyer_temp, 5).await;
+
+    accept_quote(&mut test_env, &quote, Some(&quote))
+        .await
+        .unwrap();
+    // the series vaults hold everything, no contract is kept
+    let program_id = test_env.program_key.pubkey();
+    let pda = instruction::get_data_pda(
+        &program_id,
+        &terms.get_seed(),
+        &get_seed(&quote.index_data()),
+    );
+    let contract = test_env.ctx.banks_client.get_account(pda).await.unwrap();
+    assert!(contract.is_none(), "pooled quote kept a contract");
+    let (vault, _bump) =
+        instruction::get_series_vault(&program_id, contract_type, &terms.series_seed());
+    assert_eq!(get_token_balance(&mut test_env, vault).await, 5);
+    let writer_premium = test_env.writer.mint_2;
+    assert_eq!(get_token_balance(&mut test_env, writer_premium).await, 5);
+    let (buyer_holder, writer_tokens) = (test_env.buyer_holder, test_env.writer_tokens);
+    assert_eq!(get_token_balance(&mut test_env, buyer_holder).await, 1);
+    assert_eq!(get_token_balance(&mut test_env, writer_tokens).await, 1);
+    assert_series(&mut test_env, contract_type, &terms, (1, 0, 0)).await;
+
+    let (nonce, _bump) = instruction::get_quote_nonce(&program_id, &quote.maker, quote.nonce);
+    let nonce = test_env.ctx.banks_client.get_account(nonce).await.unwrap();
+    assert_eq!(nonce.expect("quote nonce not recorded").owner, program_id);
+}
+
+#[tokio::test]
+async fn call_ask_quote_rejected() {
+    let contract_type = ContractType::CALL;
+    let mut test_env = init_env(contract_type, &InitMode::BUYER).await;
+    let terms = init_holder_mint(&mut test_env, &contract_type, 10000).await;
+    let quote = quote(&mut test_env, InitParty::WRITER, contract_type, &terms, 3).await;
+    let writer_temp = test_env.writer_temp;
+    approve_quote_authority(&mut test_env, InitParty::WRITER, writer_temp, 5).await;
+
+    let err = accept_quote(&mut test_env, &quote, None)
+        .await
+        .expect_err("unsigned quote did not fail");
+    assert_options_error(err, OptionsError::InvalidQuoteSignature);
+
+    let cheaper = Quote {
+        contract_data: ContractData {
+            premium_qty: 1,
+            ..terms.clone()
+        },
+        ..quote.clone()
+    };
+    let err = accept_quote(&mut test_env, &cheaper, Some(&quote))
+        .await
+        .expect_err("altered quote did not fail");
+    assert_options_error_at(err, 1, OptionsError::InvalidQuoteSignature);
+
+    let expired = Quote {
+        valid_until: quote.valid_until - 120_000,
+        ..quote.clone()
+    };
+    let err = accept_quote(&mut test_env, &expired, Some(&expired))
+        .await
+        .expect_err("expired quote did not fail");
+    assert_options_error_at(err, 1, OptionsError::QuoteExpired);
+
+    let private = Quote {
+        taker: Pubkey::new_unique(),
+        ..quote.clone()
+    };
+    let err = accept_quote(&mut test_env, &private, Some(&private))
+        .await
+        .expect_err("quote for another taker did not fail");
+    assert_options_error_at(err, 1, OptionsError::CounterpartyNotAllowed);
+
+    // another quote's nonce, and the taker's own quote authority
+    let program_id = test_env.program_key.pubkey();
+    let taker = test_env.buyer.main.pubkey();
+    let spoofs = [
+        (
+            2,
+            instruction::get_quote_nonce(&program_id, &quote.maker, quote.nonce + 1).0,
+        ),
+        (3, instruction::get_quote_authority(&program_id, &taker).0),
+    ];
+    for (index, spoof) in spoofs {
+        let mut ixs = accept_quote_ixs(&mut test_env, &quote, Some(&quote)).await;
+        ixs[1].accounts[index].pubkey = spoof;
+        let err = process_as_taker(&mut test_env, quote.party, &ixs)
+            .await
+            .expect_err("spoofed quote account did not fail");
+        assert_options_error_at(err, 1, OptionsError::IncorrectQuoteAccount);
+    }
+
+    // the quote is still good after the failed attempts
+    accept_quote(&mut test_env, &quote, Some(&quote))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn exercise_pooled_non_pooled_series() {
+    let contract_type = ContractType::CALL;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, 10000).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+
+    let ix = instruction::exercise_pooled(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        contract_type,
+        &contract_pda.contract_data,
+        1,
+    )
+    .unwrap();
+    assert_execute_error(&mut test_env, ix, OptionsError::ContractNotPooled).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn put_bid_execute() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn put_ask_execute() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    execute(&mut test_env, contract_pda, &contract_type).await;
+}
+
+#[tokio::test]
+async fn put_bid_cancel() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    cancel_offer(&mut test_env, contract_pda, &init_mode).await;
+}
+
+#[tokio::test]
+async fn put_ask_cancel() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 10000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    cancel_offer(&mut test_env, contract_pda, &init_mode).await;
+}
+
+#[tokio::test]
+async fn put_bid_expire() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::BUYER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 1000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_bid(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    expire_contract(&mut test_env, contract_pda, false).await;
+}
+
+#[tokio::test]
+async fn put_ask_expire() {
+    let contract_type = ContractType::PUT;
+    let init_mode = InitMode::WRITER;
+    let mut test_env = init_env(contract_type, &init_mode).await;
+    let expire_time = 1000;
+    let contract_pda = init_contract(&mut test_env, &init_mode, &contract_type, expire_time).await;
+    let contract_pda = accept_ask(&mut test_env, contract_pda).await;
+    warp_past_expiry(&mut test_env, &contract_pda).await;
+    expire_contract(&mut test_env, contract_pda, false).await;
+}
+
+#[tokio::test]
+async fn migrate_legacy_contract() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test =
+        ProgramTest::new("sol_options", program_id, processor!(process_instruction));
+    let (expected_data, pda, holder_mint) = add_legacy_contract(&mut program_test, &program_id);
+    let contract_type = expected_data.contract_type;
+    let seed = expected_data.seed;
+    // the series of a legacy contract is keyed by its premium type too
+    let (mint_pda, _mint_bump) = instruction::get_mint_pda(&program_id, contract_type, &seed);
+    let (writer_mint, _bump) = instruction::get_contract_writer_mint(&program_id, &pda);
+    assert_ne!(
+        mint_pda,
+        instruction::get_mint_pda(
+            &program_id,
+            contract_type,
+            &expected_data.contract_data.series_seed()
+        )
+        .0
+    );
+    let mut ctx = program_test.start_with_context().await;
+
+    println!("sending migrate_contract transaction...");
+    for _ in 0..2 {
+        let ix = instruction::migrate_contract(
+            &program_id,
+            &ctx.payer.pubkey(),
+            &pda,
+            &mint_pda,
+            &writer_mint,
+            &holder_mint,
+        );
+        let block = ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            block,
+        );
+        ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    let pda_account = ctx.banks_client.get_account(pda).await.unwrap().unwrap();
+    assert_eq!(pda_account.data.len(), ContractPDA::LEN);
+    assert!(pda_account.lamports >= Rent::default().minimum_balance(ContractPDA::LEN));
+    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
+    assert_eq!(expected_data, pda_data, "incorrect PDA data");
+
+    let mint_account = ctx
+        .banks_client
+        .get_account(mint_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(mint_account.data.len(), MintPDA::LEN);
+    let mint_data = MintPDA::unpack_from_slice(&mint_account.data[..]).unwrap();
+    assert_eq!(
+        mint_data.holder_mint, holder_mint,
+        "incorrect mint PDA data"
+    );
+    // a legacy series records its terms and the lot already outstanding
+    assert_eq!(mint_data.contract_type, contract_type);
+    assert_eq!(
+        mint_data.contract_data.get_seed(),
+        expected_data.contract_data.get_seed()
+    );
+    assert_eq!(mint_data.open_interest, 1);
+
+    // the contract predates writer tokens
+    let writer_mint_account = ctx
+        .banks_client
+        .get_account(writer_mint)
+        .await
+        .unwrap()
+        .expect("writer mint not created");
+    let writer_mint_data = spl_token::state::Mint::unpack(&writer_mint_account.data[..]).unwrap();
+    assert_eq!(writer_mint_data.mint_authority, Some(mint_pda).into());
+    assert_eq!(writer_mint_data.supply, 0);
+}
+
+#[tokio::test]
+async fn merge_legacy_series() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test =
+        ProgramTest::new("sol_options", program_id, processor!(process_instruction));
+    let (expected_data, pda, holder_mint) = add_legacy_contract(&mut program_test, &program_id);
+    let contract_type = expected_data.contract_type;
+    let cd = &expected_data.contract_data;
+    let series_seed = cd.series_seed();
+    let (legacy_mint_pda, _bump) =
+        instruction::get_mint_pda(&program_id, contract_type, &cd.get_seed());
+    let (mint_pda, _bump) = instruction::get_mint_pda(&program_id, contract_type, &series_seed);
+    let mut ctx = program_test.start_with_context().await;
+    let payer = ctx.payer.pubkey();
+
+    // the contract cannot leave a series that has not been merged into an account
+    let migrate = instruction::migrate_merged_contract(
+        &program_id,
+        &payer,
+        &pda,
+        contract_type,
+        cd,
+        &holder_mint,
+    );
+    let tx = Transaction::new_signed_with_payer(
+        std::slice::from_ref(&migrate),
+        Some(&payer),
+        &[&ctx.payer],
+        ctx.last_blockhash,
+    );
+    let err = ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("contract of an unmerged series moved");
+    assert_eq!(
+        err.unwrap(),
+        TransactionError::InstructionError(0, InstructionError::IllegalOwner)
+    );
+
+    println!("sending migrate_series transaction...");
+    // whichever premium type the terms name, they key the same legacy series
+    let ix = instruction::migrate_series(&program_id, &payer, &holder_mint, contract_type, cd);
+    let tx =
+        Transaction::new_signed_with_payer(&[ix], Some(&payer), &[&ctx.payer], ctx.last_blockhash);
+    ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let legacy = ctx.banks_client.get_account(legacy_mint_pda).await.unwrap();
+    assert!(legacy.is_none(), "legacy series account not closed");
+    let mint_account = ctx
+        .banks_client
+        .get_account(mint_pda)
+        .await
+        .unwrap()
+        .expect("series account not created");
+    let mint_data = MintPDA::unpack_from_slice(&mint_account.data[..]).unwrap();
+    assert_eq!(mint_data.holder_mint, holder_mint);
+    assert_eq!(mint_data.contract_data.premium_type, Pubkey::default());
+    assert_eq!(mint_data.contract_data.series_seed(), series_seed);
+    assert_eq!(mint_data.open_interest, 1);
+    let holder_mint_account = ctx
+        .banks_client
+        .get_account(holder_mint)
+        .await
+        .unwrap()
+        .unwrap();
+    let holder_mint_data = spl_token::state::Mint::unpack(&holder_mint_account.data[..]).unwrap();
+    assert_eq!(holder_mint_data.mint_authority, Some(mint_pda).into());
+    assert_eq!(holder_mint_data.freeze_authority, Some(mint_pda).into());
+
+    println!("sending migrate_contract transaction...");
+    for _ in 0..2 {
+        let block = ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            std::slice::from_ref(&migrate),
+            Some(&payer),
+            &[&ctx.payer],
+            block,
+        );
+        ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+    let pda_account = ctx.banks_client.get_account(pda).await.unwrap().unwrap();
+    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
+    assert_eq!(
+        pda_data,
+        ContractPDA {
+            series_seed,
+            ..expected_data
+        },
+        "incorrect PDA data"
+    );
+    // its writer mint is still the contract's own
+    let (writer_mint, _bump) = instruction::get_contract_writer_mint(&program_id, &pda);
+    let writer_mint_account = ctx
+        .banks_client
+        .get_account(writer_mint)
+        .await
+        .unwrap()
+        .expect("writer mint not created");
+    let writer_mint_data = spl_token::state::Mint::unpack(&writer_mint_account.data[..]).unwrap();
+    assert_eq!(writer_mint_data.mint_authority, Some(mint_pda).into());
+}
+
+#[tokio::test]
+async fn merge_legacy_series_already_created() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test =
+        ProgramTest::new("sol_options", program_id, processor!(process_instruction));
+    let (expected_data, pda, holder_mint) = add_legacy_contract(&mut program_test, &program_id);
+    let contract_type = expected_data.contract_type;
+    let cd = &expected_data.contract_data;
+    let mut ctx = program_test.start_with_context().await;
+    let payer = ctx.payer.pubkey();
+
+    // the premium-free series got a holder mint of its own before the merge
+    let series_mint = keypair::Keypair::new();
+    let ix = instruction::create_mint(
+        &program_id,
+        &payer,
+        &series_mint.pubkey(),
+        contract_type,
+        cd,
+    );
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&payer),
+        &[&ctx.payer, &series_mint],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let ix = instruction::migrate_series(&program_id, &payer, &holder_mint, contract_type, cd);
+    let tx =
+        Transaction::new_signed_with_payer(&[ix], Some(&payer), &[&ctx.payer], ctx.last_blockhash);
+    let err = ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("series merged into another holder mint");
+    assert_eq!(
+        err.unwrap(),
+        TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized)
+    );
+
+    // so the legacy contract stays with the holder tokens it was filled for
+    let ix = instruction::migrate_merged_contract(
+        &program_id,
+        &payer,
+        &pda,
+        contract_type,
+        cd,
+        &holder_mint,
+    );
+    let tx =
+        Transaction::new_signed_with_payer(&[ix], Some(&payer), &[&ctx.payer], ctx.last_blockhash);
+    let err = ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("contract moved to an unmerged series");
+    assert_options_error(err, OptionsError::IncorrectMintPda);
+}
+
+// a filled legacy contract, its legacy series account and a holder mint with one token out
+fn add_legacy_contract(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+) -> (ContractPDA, Pubkey, Pubkey) {
+    let contract_type = ContractType::PUT;
+    let contract_data = ContractData {
+        token_type: Pubkey::new_unique(),
+        token_qty: 5,
+        strike_type: Pubkey::new_unique(),
+        strike_qty: 5,
+        premium_type: Pubkey::new_unique(),
+        premium_qty: 5,
+        expiry_date: 10000,
+        expiry_type: ExpiryType::TIMESTAMP,
+        contract_style: ContractStyle::AMERICAN,
+        exercise_start: None,
+        exercise_schedule: ExerciseSchedule::default(),
+        settlement: SettlementType::PHYSICAL,
+        oracle: None,
+        auto_exercise: false,
+        pooled: false,
+        lots: 1,
+    };
+    let seed = contract_data.get_seed();
+    let index_seed = get_seed(&[7; 41]);
+    let (pda, bump) = Pubkey::find_program_address(&[&seed, &index_seed], program_id);
+    let (mint_pda, _mint_bump) = instruction::get_mint_pda(program_id, contract_type, &seed);
+    let holder_mint = Pubkey::new_unique();
+    let party = || PartyData {
+        party_pub: Pubkey::new_unique(),
+        temp_pub: Pubkey::new_unique(),
+        receive_pub: Pubkey::new_unique(),
+        receive_ata: Pubkey::new_unique(),
+    };
+    let expected_data = ContractPDA {
+        contract_data,
+        contract_state: ContractState::FINAL,
+        buyer_data: Some(party()),
+        writer_data: Some(party()),
+        is_initialised: true,
+        seed,
+        bump,
+        init_party: InitParty::WRITER,
+        contract_type,
+        index_seed,
+        open_lots: 1,
+        unfilled_lots: 0,
+        writer_lots: 0,
+        series_seed: seed,
+        offer_expiry: None,
+        counterparty: None,
+        offer_seq: 0,
+        settle_payout: 0,
+    };
+
+    let cd = &expected_data.contract_data;
+    let mut legacy = vec![1];
+    legacy.extend_from_slice(&cd.token_type.to_bytes());
+    legacy.extend_from_slice(&cd.token_qty.to_le_bytes());
+    legacy.extend_from_slice(&cd.expiry_date.to_le_bytes());
+    legacy.extend_from_slice(&cd.strike_type.to_bytes());
+    legacy.extend_from_slice(&cd.strike_qty.to_le_bytes());
+    legacy.extend_from_slice(&cd.premium_type.to_bytes());
+    legacy.extend_from_slice(&cd.premium_qty.to_le_bytes());
+    legacy.push(2);
+    legacy.extend_from_slice(&expected_data.buyer_data.as_ref().unwrap().to_bytes());
+    legacy.extend_from_slice(&expected_data.writer_data.as_ref().unwrap().to_bytes());
+    legacy.extend_from_slice(&[bump, 1, 1]);
+    legacy.extend_from_slice(&index_seed);
+
+    program_test.add_account(
+        pda,
+        Account {
+            lamports: Rent::default().minimum_balance(ContractPDA::LEGACY_LEN),
+            data: legacy,
+            owner: *program_id,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    program_test.add_account(
+        mint_pda,
+        Account {
+            lamports: Rent::default().minimum_balance(MintPDA::LEGACY_LEN),
+            data: holder_mint.to_bytes().to_vec(),
+            owner: *program_id,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    let mut mint_data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint {
+        mint_authority: Some(mint_pda).into(),
+        supply: 1,
+        decimals: 0,
+        is_initialized: true,
+        freeze_authority: Some(mint_pda).into(),
+    }
+    .pack_into_slice(&mut mint_data);
+    program_test.add_account(
+        holder_mint,
+        Account {
+            lamports: Rent::default().minimum_balance(MINT_SIZE as usize),
+            data: mint_data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    (expected_data, pda, holder_mint)
+}
+
+async fn init_env(contract_type: ContractType, init_mode: &InitMode) -> TestEnv {
+    println!("\n-----CREATING-TEST-ENVIRONMENT-----\n");
+    let program_key = keypair::Keypair::new();
+    let buyer_key = keypair::Keypair::new();
+    let writer_key = keypair::Keypair::new();
+    let mint_1 = keypair::Keypair::new();
+    let mint_2 = keypair::Keypair::new();
+    let buyer_temp = keypair::Keypair::new();
+    let writer_temp = keypair::Keypair::new();
+    let writer_receive_1 = keypair::Keypair::new();
+    let writer_receive_2 = keypair::Keypair::new();
+    let holder_mint = keypair::Keypair::new();
+
+    println!("starting test-server");
+
+    let mut ctx = ProgramTest::new(
+        "sol_options",
+        program_key.pubkey(),
+        processor!(process_instruction),
+    )
+    .start_with_context()
+    .await;
+
+    let client = &mut ctx.banks_client;
+    let payer = &ctx.payer;
+    let block = ctx.last_blockhash.clone();
+
+    println!("started test-server, creating mint account instructions...");
+    let min_rent = Rent::default().minimum_balance(MINT_SIZE as usize);
+
+    let c1 = system_instruction::create_account(
+        &payer.pubkey(),
+        &mint_1.pubkey(),
+        min_rent,
+        MINT_SIZE,
+        &spl_token::id(),
+    );
+    let c2 = system_instruction::create_account(
+        &payer.pubkey(),
+        &mint_2.pubkey(),
+        min_rent,
+        MINT_SIZE,
+        &spl_token::id(),
+    );
+
+    let i1 = spl_token::instruction::initialize_mint(
+        &spl_token::id(),
+        &mint_1.pubkey(),
+        &payer.pubkey(),
+        None,
+        1,
+    )
+    .expect("could not create initialise_mint instruction");
+
+    let i2 = spl_token::instruction::initialize_mint(
+        &spl_token::id(),
+        &mint_2.pubkey(),
+        &payer.pubkey(),
+        None,
+        1,
+    )
+    .expect("could not create initialise_mint instruction");
+
+    println!("creating mint 1...");
+    let tx = Transaction::new_signed_with_payer(
+        &[c1, i1],
+        Some(&payer.pubkey()),
+        &[payer, &mint_1],
+        block,
+    );
+    client.process_transaction(tx).await.unwrap();
+
+    println!("creating mint 2...");
+    let tx = Transaction::new_signed_with_payer(
         &[c2, i2],
         Some(&payer.pubkey()),
         &[payer, &mint_2],
         block,
     );
-    client.process_transaction(tx).await.unwrap();
+    client.process_transaction(tx).await.unwrap();
+
+    println!("transferring funds from payer to parties...");
+    let split_txs = system_instruction::transfer_many(
+        &payer.pubkey(),
+        &[
+            (buyer_key.pubkey(), 100000000),
+            (writer_key.pubkey(), 100000000),
+        ],
+    );
+    let tx = Transaction::new_signed_with_payer(
+        &split_txs[0..2],
+        Some(&payer.pubkey()),
+        &[payer],
+        block,
+    );
+    client.process_transaction(tx).await.unwrap();
+
+    println!("creating ATA accounts...");
+    let b1 = create_associated_token_account(
+        &payer.pubkey(),
+        &buyer_key.pubkey(),
+        &mint_1.pubkey(),
+        &spl_token::id(),
+    );
+    let b2 = create_associated_token_account(
+        &payer.pubkey(),
+        &buyer_key.pubkey(),
+        &mint_2.pubkey(),
+        &spl_token::id(),
+    );
+    let b3 = create_associated_token_account(
+        &payer.pubkey(),
+        &writer_key.pubkey(),
+        &mint_1.pubkey(),
+        &spl_token::id(),
+    );
+    let b4 = create_associated_token_account(
+        &payer.pubkey(),
+        &writer_key.pubkey(),
+        &mint_2.pubkey(),
+        &spl_token::id(),
+    );
+
+    let tx = Transaction::new_signed_with_payer(
+        &[b1, b2, b3, b4],
+        Some(&payer.pubkey()),
+        &[payer],
+        block,
+    );
+    client.process_transaction(tx).await.unwrap();
+
+    let b1 = get_associated_token_address(&buyer_key.pubkey(), &mint_1.pubkey());
+    let b2 = get_associated_token_address(&buyer_key.pubkey(), &mint_2.pubkey());
+    let b3 = get_associated_token_address(&buyer_key.pubkey(), &holder_mint.pubkey());
+    let w1 = get_associated_token_address(&writer_key.pubkey(), &mint_1.pubkey());
+    let w2 = get_associated_token_address(&writer_key.pubkey(), &mint_2.pubkey());
+
+    let min_rent = Rent::default().minimum_balance(165);
+
+    println!("creating buyer and writer temps");
+    let bt1 = system_instruction::create_account(
+        &buyer_key.pubkey(),
+        &buyer_temp.pubkey(),
+        min_rent,
+        165,
+        &spl_token::id(),
+    );
+    let wt1 = system_instruction::create_account(
+        &writer_key.pubkey(),
+        &writer_temp.pubkey(),
+        min_rent,
+        165,
+        &spl_token::id(),
+    );
+
+    println!("initialising buyer and writer temps");
+    let ibt1 = spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        &buyer_temp.pubkey(),
+        &mint_2.pubkey(),
+        &buyer_key.pubkey(),
+    )
+    .unwrap();
+    let iwt1 = spl_token::instruction::initialize_account(
+        &spl_token::id(),
+        &writer_temp.pubkey(),
+        &mint_1.pubkey(),
+        &writer_key.pubkey(),
+    )
+    .unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[bt1, ibt1],
+        Some(&payer.pubkey()),
+        &[&buyer_temp, &buyer_key, &payer],
+        block,
+    );
+    client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[wt1, iwt1],
+        Some(&payer.pubkey()),
+        &[payer, &writer_key, &writer_temp],
+        block,
+    );
+
+    client.process_transaction(tx).await.unwrap();
+
+    println!("creating writer receive accounts");
+    for (receive, mint) in [(&writer_receive_1, &mint_1), (&writer_receive_2, &mint_2)] {
+        let create = system_instruction::create_account(
+            &writer_key.pubkey(),
+            &receive.pubkey(),
+            min_rent,
+            165,
+            &spl_token::id(),
+        );
+        let init = spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &receive.pubkey(),
+            &mint.pubkey(),
+            &writer_key.pubkey(),
+        )
+        .unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[create, init],
+            Some(&payer.pubkey()),
+            &[payer, &writer_key, receive],
+            block,
+        );
+        client.process_transaction(tx).await.unwrap();
+    }
+
+    println!("minting premium, strike and assets");
+    let prem_mint = spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &mint_2.pubkey(),
+        &buyer_temp.pubkey(),
+        &payer.pubkey(),
+        &[&payer.pubkey()],
+        5,
+    )
+    .unwrap();
+
+    let wtp = writer_temp.pubkey();
+    let (strike_type, strike_acc) = match contract_type {
+        ContractType::CALL => (mint_2.pubkey(), &b2),
+        ContractType::PUT => (mint_1.pubkey(), &wtp),
+    };
+    let strike_mint = spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &strike_type,
+        strike_acc,
+        &payer.pubkey(),
+        &[&payer.pubkey()],
+        5,
+    )
+    .unwrap();
+
+    let (asset_type, asset_acc) = match contract_type {
+        ContractType::CALL => (mint_1.pubkey(), &wtp),
+        ContractType::PUT => (mint_2.pubkey(), &b2),
+    };
+    let asset_mint = spl_token::instruction::mint_to(
+        &spl_token::id(),
+        &asset_type,
+        asset_acc,
+        &payer.pubkey(),
+        &[&payer.pubkey()],
+        5,
+    )
+    .unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[prem_mint, strike_mint, asset_mint],
+        Some(&payer.pubkey()),
+        &[payer],
+        block,
+    );
+    client.process_transaction(tx).await.unwrap();
+
+    let buyer = PartyKeys {
+        main: buyer_key,
+        mint_1: b1,
+        mint_2: b2,
+    };
+
+    let writer = PartyKeys {
+        main: writer_key,
+        mint_1: w1,
+        mint_2: w2,
+    };
+
+    let mut index_seed = [0; 41];
+
+    match init_mode {
+        InitMode::BUYER => index_seed[..32].copy_from_slice(&buyer.main.pubkey().to_bytes()),
+        InitMode::WRITER => index_seed[..32].copy_from_slice(&writer.main.pubkey().to_bytes()),
+    };
+    index_seed[32] = match contract_type {
+        ContractType::CALL => 0,
+        ContractType::PUT => 1,
+    };
+
+    println!("\n\n-----TEST-ENVIRONMENT-SETUP-COMPLETE-----\n\n");
+
+    TestEnv {
+        ctx,
+        buyer,
+        writer,
+        program_key,
+        mint_1,
+        mint_2,
+        holder_mint,
+        buyer_temp: buyer_temp.pubkey(),
+        buyer_holder: b3,
+        writer_tokens: Pubkey::default(),
+        writer_receive_1: writer_receive_1.pubkey(),
+        writer_receive_2: writer_receive_2.pubkey(),
+        writer_temp: writer_temp.pubkey(),
+        index_seed,
+        expiry_type: ExpiryType::TIMESTAMP,
+        contract_style: ContractStyle::AMERICAN,
+        exercise_start: None,
+        exercise_windows: Vec::new(),
+        settlement: SettlementType::PHYSICAL,
+        oracle: None,
+        auto_exercise: false,
+        pooled: false,
+        lots: 1,
+        offer_ttl: None,
+        counterparty: None,
+    }
+}
+
+// init_env funds every party for a single lot of 5
+async fn fund_extra_lots(test_env: &mut TestEnv, contract_type: ContractType) {
+    let amount = 5 * (test_env.lots - 1);
+    let (strike_mint, strike_acc, asset_mint, asset_acc) = match contract_type {
+        ContractType::CALL => (
+            test_env.mint_2.pubkey(),
+            test_env.buyer.mint_2,
+            test_env.mint_1.pubkey(),
+            test_env.writer_temp,
+        ),
+        ContractType::PUT => (
+            test_env.mint_1.pubkey(),
+            test_env.writer_temp,
+            test_env.mint_2.pubkey(),
+            test_env.buyer.mint_2,
+        ),
+    };
+    let premium_mint = test_env.mint_2.pubkey();
+    let premium_acc = test_env.buyer_temp;
+    mint_tokens(
+        test_env,
+        &[
+            (premium_mint, premium_acc, amount),
+            (strike_mint, strike_acc, amount),
+            (asset_mint, asset_acc, amount),
+        ],
+    )
+    .await;
+}
+
+async fn mint_tokens(test_env: &mut TestEnv, mints: &[(Pubkey, Pubkey, u64)]) {
+    let payer = test_env.ctx.payer.pubkey();
+    let ixs: Vec<Instruction> = mints
+        .iter()
+        .map(|(mint, account, amount)| {
+            spl_token::instruction::mint_to(
+                &spl_token::id(),
+                mint,
+                account,
+                &payer,
+                &[&payer],
+                *amount,
+            )
+            .unwrap()
+        })
+        .collect();
+    // a lot of 5 repeats init_env's mint transaction byte for byte
+    test_env.ctx.last_blockhash = test_env.ctx.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&payer),
+        &[&test_env.ctx.payer],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+}
+
+// moves `lots` holder tokens from the buyer to a fresh wallet with its own ATAs
+async fn transfer_holder_tokens(test_env: &mut TestEnv, lots: u64) -> PartyKeys {
+    let (holder_mint, buyer_holder) = (test_env.holder_mint.pubkey(), test_env.buyer_holder);
+    transfer_to_new_wallet(
+        test_env,
+        holder_mint,
+        buyer_holder,
+        |env| &env.buyer.main,
+        lots,
+    )
+    .await
+}
+
+// moves `lots` writer tokens from the writer to a fresh wallet with its own ATAs
+async fn transfer_writer_tokens(
+    test_env: &mut TestEnv,
+    contract_pda: &ContractPDA,
+    lots: u64,
+) -> PartyKeys {
+    let program_id = test_env.program_key.pubkey();
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let (writer_mint, _bump) = instruction::get_writer_mint_of(&program_id, contract_pda, &pda);
+    let writer_tokens = test_env.writer_tokens;
+    transfer_to_new_wallet(
+        test_env,
+        writer_mint,
+        writer_tokens,
+        |env| &env.writer.main,
+        lots,
+    )
+    .await
+}
+
+async fn transfer_to_new_wallet(
+    test_env: &mut TestEnv,
+    mint: Pubkey,
+    source: Pubkey,
+    owner: fn(&TestEnv) -> &keypair::Keypair,
+    lots: u64,
+) -> PartyKeys {
+    let wallet = keypair::Keypair::new();
+    let payer = test_env.ctx.payer.pubkey();
+    let (mint_1, mint_2) = (test_env.mint_1.pubkey(), test_env.mint_2.pubkey());
+    let dest = get_associated_token_address(&wallet.pubkey(), &mint);
+    let ixs = [
+        system_instruction::transfer(&payer, &wallet.pubkey(), 100000000),
+        create_associated_token_account(&payer, &wallet.pubkey(), &mint_1, &spl_token::id()),
+        create_associated_token_account(&payer, &wallet.pubkey(), &mint_2, &spl_token::id()),
+        create_associated_token_account(&payer, &wallet.pubkey(), &mint, &spl_token::id()),
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            &source,
+            &dest,
+            &owner(test_env).pubkey(),
+            &[],
+            lots,
+        )
+        .unwrap(),
+    ];
+    println!("transferring tokens to a third wallet...");
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&payer),
+        &[&test_env.ctx.payer, owner(test_env)],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+    assert_eq!(get_token_balance(test_env, dest).await, lots);
 
-    println!("transferring funds from payer to parties...");
-    let split_txs = system_instruction::transfer_many(
-        &payer.pubkey(),
-        &[
-            (buyer_key.pubkey(), 100000000),
-            (writer_key.pubkey(), 100000000),
-        ],
+    PartyKeys {
+        mint_1: get_associated_token_address(&wallet.pubkey(), &mint_1),
+        mint_2: get_associated_token_address(&wallet.pubkey(), &mint_2),
+        main: wallet,
+    }
+}
+
+// hands `lots` of the buyer's holder tokens to the writer, who then holds both sides
+async fn give_writer_holder_tokens(test_env: &mut TestEnv, lots: u64) {
+    let payer = test_env.ctx.payer.pubkey();
+    let writer = test_env.writer.main.pubkey();
+    let holder_mint = test_env.holder_mint.pubkey();
+    let ixs = [
+        create_associated_token_account(&payer, &writer, &holder_mint, &spl_token::id()),
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            &test_env.buyer_holder,
+            &get_associated_token_address(&writer, &holder_mint),
+            &test_env.buyer.main.pubkey(),
+            &[],
+            lots,
+        )
+        .unwrap(),
+    ];
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&payer),
+        &[&test_env.ctx.payer, &test_env.buyer.main],
+        test_env.ctx.last_blockhash,
     );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+}
+
+async fn init_contract(
+    test_env: &mut TestEnv,
+    init_mode: &InitMode,
+    contract_type: &ContractType,
+    expire_time: i64,
+) -> ContractPDA {
+    let contract_data = init_holder_mint(test_env, contract_type, expire_time).await;
+
+    let buyer_data = match init_mode {
+        InitMode::BUYER => Some(PartyData {
+            party_pub: test_env.buyer.main.pubkey(),
+            temp_pub: test_env.buyer_temp.clone(),
+            receive_pub: test_env.buyer.mint_1.clone(),
+            receive_ata: test_env.buyer_holder.clone(),
+        }),
+        InitMode::WRITER => None,
+    };
+
+    let writer_data = match init_mode {
+        InitMode::BUYER => None,
+        InitMode::WRITER => Some(PartyData {
+            party_pub: test_env.writer.main.pubkey(),
+            temp_pub: test_env.writer_temp.clone(),
+            receive_pub: writer_receive(test_env, contract_type, &contract_data),
+            receive_ata: test_env.writer.mint_2.clone(),
+        }),
+    };
+
+    let seed = contract_data.get_seed();
+    let index_seed = get_seed(&test_env.index_seed);
+
+    let (pda, bump) =
+        Pubkey::find_program_address(&[&seed, &index_seed], &test_env.program_key.pubkey());
+
+    println!("sending initialise contract instruction...");
+    let mut ixs = vec![offer_instruction(
+        test_env,
+        init_mode,
+        contract_type,
+        &contract_data,
+    )];
+    if !contract_data.pooled {
+        let (writer_mint, _bump) =
+            instruction::get_contract_writer_mint(&test_env.program_key.pubkey(), &pda);
+        ixs.push(create_writer_ata(test_env, &writer_mint));
+    }
+
+    let signer = match init_mode {
+        InitMode::BUYER => &test_env.buyer.main,
+        InitMode::WRITER => &test_env.writer.main,
+    };
     let tx = Transaction::new_signed_with_payer(
-        &split_txs[0..2],
-        Some(&payer.pubkey()),
-        &[payer],
-        block,
+        &ixs,
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, signer],
+        test_env.ctx.last_blockhash.clone(),
     );
-    client.process_transaction(tx).await.unwrap();
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
 
-    println!("creating ATA accounts...");
-    let b1 =
-        create_associated_token_account(&payer.pubkey(), &buyer_key.pubkey(), &mint_1.pubkey());
-    let b2 =
-        create_associated_token_account(&payer.pubkey(), &buyer_key.pubkey(), &mint_2.pubkey());
-    let b3 =
-        create_associated_token_account(&payer.pubkey(), &writer_key.pubkey(), &mint_1.pubkey());
-    let b4 =
-        create_associated_token_account(&payer.pubkey(), &writer_key.pubkey(), &mint_2.pubkey());
+    println!("asserting contract state");
+    let pda_account = test_env
+        .ctx
+        .banks_client
+        .get_account(pda)
+        .await
+        .unwrap()
+        .expect("could not find PDA account");
+
+    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
+
+    let contract_state = match init_mode {
+        InitMode::BUYER => ContractState::BID,
+        InitMode::WRITER => ContractState::ASK,
+    };
+    let init_party = match init_mode {
+        InitMode::BUYER => InitParty::BUYER,
+        InitMode::WRITER => InitParty::WRITER,
+    };
+
+    // the offer was made on the clock the expiry date was set from
+    let offer_expiry = test_env
+        .offer_ttl
+        .map(|ttl| contract_data.expiry_date - expire_time + ttl);
+    let expected_data = ContractPDA {
+        series_seed: contract_data.series_seed(),
+        offer_expiry,
+        counterparty: test_env.counterparty,
+        offer_seq: 0,
+        settle_payout: 0,
+        contract_data,
+        contract_state,
+        buyer_data,
+        writer_data,
+        is_initialised: true,
+        seed,
+        bump,
+        init_party,
+        contract_type: *contract_type,
+        index_seed,
+        open_lots: 0,
+        unfilled_lots: test_env.lots,
+        writer_lots: 0,
+    };
+
+    assert_eq!(expected_data, pda_data, "incorrect PDA data");
 
+    println!("trying illegal transaction...");
+    let (temp, dest, kp) = match init_mode {
+        InitMode::BUYER => (
+            &test_env.buyer_temp,
+            &test_env.buyer.mint_2,
+            &test_env.buyer.main,
+        ),
+        InitMode::WRITER => (
+            &test_env.writer_temp,
+            &test_env.writer.mint_1,
+            &test_env.writer.main,
+        ),
+    };
+    let ix = spl_token::instruction::transfer(
+        &spl_token::id(),
+        temp,
+        dest,
+        &kp.pubkey(),
+        &[&kp.pubkey()],
+        5,
+    )
+    .unwrap();
     let tx = Transaction::new_signed_with_payer(
-        &[b1, b2, b3, b4],
-        Some(&payer.pubkey()),
-        &[payer],
-        block,
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, kp],
+        test_env.ctx.last_blockhash,
     );
-    client.process_transaction(tx).await.unwrap();
+    let tx_result = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("illegal transaction did not fail");
+    println!("illegal transaction failed: {:?}", tx_result);
+    pda_data
+}
 
-    let b1 = get_associated_token_address(&buyer_key.pubkey(), &mint_1.pubkey());
-    let b2 = get_associated_token_address(&buyer_key.pubkey(), &mint_2.pubkey());
-    let b3 = get_associated_token_address(&buyer_key.pubkey(), &holder_mint.pubkey());
-    let w1 = get_associated_token_address(&writer_key.pubkey(), &mint_1.pubkey());
-    let w2 = get_associated_token_address(&writer_key.pubkey(), &mint_2.pubkey());
+async fn init_holder_mint(
+    test_env: &mut TestEnv,
+    contract_type: &ContractType,
+    expire_time: i64,
+) -> ContractData {
+    let clock = test_env
+        .ctx
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap();
+    // expire_time is in the unit of the contract's expiry type
+    let now = match test_env.expiry_type {
+        ExpiryType::TIMESTAMP => clock.unix_timestamp * 1000,
+        ExpiryType::SLOT => clock.slot as i64,
+    };
+
+    println!("creating contract + instruction data");
+    let (token_type, strike_type, premium_type) = match contract_type {
+        ContractType::CALL => (
+            test_env.mint_1.pubkey(),
+            test_env.mint_2.pubkey(),
+            test_env.mint_2.pubkey(),
+        ),
+        ContractType::PUT => (
+            test_env.mint_2.pubkey(),
+            test_env.mint_1.pubkey(),
+            test_env.mint_2.pubkey(),
+        ),
+    };
+
+    let contract_data = ContractData {
+        token_type,
+        token_qty: 5,
+        strike_type,
+        strike_qty: 5,
+        premium_type,
+        premium_qty: 5,
+        expiry_date: now + expire_time,
+        expiry_type: test_env.expiry_type,
+        contract_style: test_env.contract_style,
+        exercise_start: test_env.exercise_start.map(|start| now + start),
+        exercise_schedule: ExerciseSchedule::new(
+            test_env
+                .exercise_windows
+                .iter()
+                .map(|w| ExerciseWindow {
+                    start: now + w.start,
+                    end: now + w.end,
+                })
+                .collect(),
+        )
+        .unwrap(),
+        settlement: test_env.settlement,
+        oracle: test_env.oracle,
+        auto_exercise: test_env.auto_exercise,
+        pooled: test_env.pooled,
+        lots: test_env.lots,
+    };
+
+    println!("creating holder mint account");
+    let ix = instruction::create_mint(
+        &test_env.program_key.pubkey(),
+        &test_env.ctx.payer.pubkey(),
+        &test_env.holder_mint.pubkey(),
+        *contract_type,
+        &contract_data,
+    );
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.holder_mint],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+
+    println!("creating holder_ata");
+    let mut ixs = vec![create_associated_token_account(
+        &test_env.ctx.payer.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.holder_mint.pubkey(),
+        &spl_token::id(),
+    )];
+    // other contracts mint their writer tokens once offered
+    if contract_data.pooled {
+        let (writer_mint, _bump) = instruction::get_writer_mint(
+            &test_env.program_key.pubkey(),
+            *contract_type,
+            &contract_data.series_seed(),
+        );
+        ixs.push(create_writer_ata(test_env, &writer_mint));
+    }
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+    contract_data
+}
+
+// points writer_tokens at the writer's account for `writer_mint`
+fn create_writer_ata(test_env: &mut TestEnv, writer_mint: &Pubkey) -> Instruction {
+    test_env.writer_tokens =
+        get_associated_token_address(&test_env.writer.main.pubkey(), writer_mint);
+    create_associated_token_account(
+        &test_env.ctx.payer.pubkey(),
+        &test_env.writer.main.pubkey(),
+        writer_mint,
+        &spl_token::id(),
+    )
+}
+
+fn offer_instruction(
+    test_env: &TestEnv,
+    init_mode: &InitMode,
+    contract_type: &ContractType,
+    contract_data: &ContractData,
+) -> Instruction {
+    match init_mode {
+        InitMode::BUYER => instruction::bid(
+            &test_env.program_key.pubkey(),
+            &test_env.buyer.main.pubkey(),
+            &test_env.buyer_temp,
+            &test_env.buyer.mint_1,
+            &test_env.holder_mint.pubkey(),
+            *contract_type,
+            contract_data,
+            &test_env.index_seed,
+            test_env.offer_ttl,
+            test_env.counterparty,
+        ),
+        InitMode::WRITER => instruction::ask(
+            &test_env.program_key.pubkey(),
+            &test_env.writer.main.pubkey(),
+            &test_env.writer_temp,
+            &writer_receive(test_env, contract_type, contract_data),
+            match contract_data.premium_type == test_env.mint_1.pubkey() {
+                true => &test_env.writer.mint_1,
+                false => &test_env.writer.mint_2,
+            },
+            &test_env.holder_mint.pubkey(),
+            *contract_type,
+            contract_data,
+            &test_env.index_seed,
+            test_env.offer_ttl,
+            test_env.counterparty,
+        ),
+    }
+}
 
-    let min_rent = Rent::default().minimum_balance(165);
+// the writer's account for what it receives at exercise
+fn writer_receive(
+    test_env: &TestEnv,
+    contract_type: &ContractType,
+    contract_data: &ContractData,
+) -> Pubkey {
+    let (_, rec_type) = contract_data.receive_types(*contract_type);
+    match rec_type == test_env.mint_1.pubkey() {
+        true => test_env.writer_receive_1,
+        false => test_env.writer_receive_2,
+    }
+}
 
-    println!("creating buyer and writer temps");
-    let bt1 = system_instruction::create_account(
-        &buyer_key.pubkey(),
-        &buyer_temp.pubkey(),
-        min_rent,
-        165,
-        &spl_token::id(),
+async fn accept_bid(test_env: &mut TestEnv, contract_pda: ContractPDA) -> ContractPDA {
+    let lots = contract_pda.unfilled_lots;
+    accept_bid_lots(test_env, contract_pda, lots).await
+}
+
+async fn accept_bid_lots(
+    test_env: &mut TestEnv,
+    contract_pda: ContractPDA,
+    lots: u64,
+) -> ContractPDA {
+    let (pda, _bump) = Pubkey::find_program_address(
+        &[&contract_pda.seed, &contract_pda.index_seed],
+        &test_env.program_key.pubkey(),
     );
-    let wt1 = system_instruction::create_account(
-        &writer_key.pubkey(),
-        &writer_temp.pubkey(),
-        min_rent,
-        165,
-        &spl_token::id(),
+
+    let prem_init_balance = get_token_balance(test_env, test_env.writer.mint_2.clone()).await;
+    let holder_init_balance = get_token_balance(test_env, test_env.buyer_holder).await;
+    let writer_init_balance = get_token_balance(test_env, test_env.writer_tokens).await;
+    let receive_pub = writer_receive(
+        test_env,
+        &contract_pda.contract_type,
+        &contract_pda.contract_data,
     );
 
-    println!("initialising buyer and writer temps");
-    let ibt1 = spl_token::instruction::initialize_account(
-        &spl_token::id(),
-        &buyer_temp.pubkey(),
-        &mint_2.pubkey(),
-        &buyer_key.pubkey(),
-    )
-    .unwrap();
-    let iwt1 = spl_token::instruction::initialize_account(
-        &spl_token::id(),
-        &writer_temp.pubkey(),
-        &mint_1.pubkey(),
-        &writer_key.pubkey(),
+    println!("creating accept-bid instruction...");
+    let ix = instruction::accept_bid(
+        &test_env.program_key.pubkey(),
+        &test_env.writer.main.pubkey(),
+        &test_env.writer_temp,
+        &receive_pub,
+        &test_env.writer.mint_2,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        lots,
     )
     .unwrap();
-
     let tx = Transaction::new_signed_with_payer(
-        &[bt1, ibt1],
-        Some(&payer.pubkey()),
-        &[&buyer_temp, &buyer_key, &payer],
-        block,
+        &[ix],
+        Some(&test_env.writer.main.pubkey()),
+        &[&test_env.writer.main],
+        test_env.ctx.last_blockhash.clone(),
     );
-    client.process_transaction(tx).await.unwrap();
+    println!("sending accept-bid instruction...");
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
 
-    let tx = Transaction::new_signed_with_payer(
-        &[wt1, iwt1],
-        Some(&payer.pubkey()),
-        &[payer, &writer_key, &writer_temp],
-        block,
-    );
+    println!("asserting contract + party states");
+    let pda_account = test_env
+        .ctx
+        .banks_client
+        .get_account(pda)
+        .await
+        .unwrap()
+        .expect("could not find PDA account");
 
-    client.process_transaction(tx).await.unwrap();
+    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
 
-    println!("minting premium, strike and assets");
-    let prem_mint = spl_token::instruction::mint_to(
-        &spl_token::id(),
-        &mint_2.pubkey(),
-        &buyer_temp.pubkey(),
-        &payer.pubkey(),
-        &[&payer.pubkey()],
-        5,
-    )
-    .unwrap();
+    let writer_data = Some(PartyData {
+        party_pub: test_env.writer.main.pubkey(),
+        temp_pub: test_env.writer_temp.clone(),
+        receive_pub,
+        receive_ata: test_env.writer.mint_2.clone(),
+    });
 
-    let wtp = writer_temp.pubkey();
-    let (strike_type, strike_acc) = match contract_type {
-        ContractType::CALL => (mint_2.pubkey(), &b2),
-        ContractType::PUT => (mint_1.pubkey(), &wtp),
+    let expected_data = ContractPDA {
+        writer_data,
+        ..filled(contract_pda, lots)
     };
-    let strike_mint = spl_token::instruction::mint_to(
-        &spl_token::id(),
-        &strike_type,
-        strike_acc,
-        &payer.pubkey(),
-        &[&payer.pubkey()],
-        5,
-    )
-    .unwrap();
 
-    let (asset_type, asset_acc) = match contract_type {
-        ContractType::CALL => (mint_1.pubkey(), &wtp),
-        ContractType::PUT => (mint_2.pubkey(), &b2),
-    };
-    let asset_mint = spl_token::instruction::mint_to(
+    assert_eq!(expected_data, pda_data, "incorrect PDA data");
+
+    let prem_balance = get_token_balance(test_env, test_env.writer.mint_2.clone()).await;
+
+    let prem_paid = prem_balance - prem_init_balance;
+    assert_eq!(
+        prem_paid,
+        expected_data.contract_data.premium_qty * lots,
+        "incorrect writer premium balance"
+    );
+
+    let buyer_holder_balance = get_token_balance(test_env, test_env.buyer_holder.clone()).await;
+
+    assert_eq!(
+        buyer_holder_balance,
+        holder_init_balance + lots,
+        "incorrect buyer holder token balance"
+    );
+    assert_eq!(
+        get_token_balance(test_env, test_env.writer_tokens).await,
+        writer_init_balance + lots,
+        "incorrect writer token balance"
+    );
+
+    let ix = spl_token::instruction::transfer(
         &spl_token::id(),
-        &asset_type,
-        asset_acc,
-        &payer.pubkey(),
-        &[&payer.pubkey()],
+        &test_env.writer_temp,
+        &test_env.writer.mint_1,
+        &test_env.writer.main.pubkey(),
+        &[&test_env.writer.main.pubkey()],
         5,
     )
     .unwrap();
 
+    println!("trying illegal transaction...");
     let tx = Transaction::new_signed_with_payer(
-        &[prem_mint, strike_mint, asset_mint],
-        Some(&payer.pubkey()),
-        &[payer],
-        block,
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.writer.main],
+        test_env.ctx.last_blockhash,
     );
-    client.process_transaction(tx).await.unwrap();
-
-    let buyer = PartyKeys {
-        main: buyer_key,
-        mint_1: b1,
-        mint_2: b2,
-    };
-
-    let writer = PartyKeys {
-        main: writer_key,
-        mint_1: w1,
-        mint_2: w2,
-    };
-
-    let mut index_seed = [0; 41];
-
-    match init_mode {
-        InitMode::BUYER => index_seed[..32].copy_from_slice(&buyer.main.pubkey().to_bytes()),
-        InitMode::WRITER => index_seed[..32].copy_from_slice(&writer.main.pubkey().to_bytes()),
-    };
-    index_seed[32] = match contract_type {
-        ContractType::CALL => 0,
-        ContractType::PUT => 1,
-    };
+    let tx_err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("illegal transaction did not fail");
+    println!("illegal transaction failed: {:?}", tx_err);
 
-    println!("\n\n-----TEST-ENVIRONMENT-SETUP-COMPLETE-----\n\n");
+    expected_data
+}
 
-    TestEnv {
-        ctx,
-        buyer,
-        writer,
-        program_key,
-        mint_1,
-        mint_2,
-        holder_mint,
-        buyer_temp: buyer_temp.pubkey(),
-        buyer_holder: b3,
-        writer_temp: writer_temp.pubkey(),
-        index_seed,
-    }
+async fn accept_ask(test_env: &mut TestEnv, contract_pda: ContractPDA) -> ContractPDA {
+    let lots = contract_pda.unfilled_lots;
+    accept_ask_lots(test_env, contract_pda, lots).await
 }
 
-async fn init_contract(
+async fn accept_ask_lots(
     test_env: &mut TestEnv,
-    init_mode: &InitMode,
-    contract_type: &ContractType,
-    expire_time: i64,
-) -> (ContractPDA, Pubkey) {
-    let now = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as i64;
+    contract_pda: ContractPDA,
+    lots: u64,
+) -> ContractPDA {
+    let (pda, _bump) = Pubkey::find_program_address(
+        &[&contract_pda.seed, &contract_pda.index_seed],
+        &test_env.program_key.pubkey(),
+    );
 
-    println!("creating contract + instruction data");
-    let (token_type, strike_type, premium_type, s1) = match contract_type {
-        ContractType::CALL => (
-            test_env.mint_1.pubkey(),
-            test_env.mint_2.pubkey(),
-            test_env.mint_2.pubkey(),
-            [0],
-        ),
-        ContractType::PUT => (
-            test_env.mint_2.pubkey(),
-            test_env.mint_1.pubkey(),
-            test_env.mint_2.pubkey(),
-            [1],
-        ),
-    };
+    let prem_init_balance = get_token_balance(test_env, test_env.writer.mint_2.clone()).await;
+    let holder_init_balance = get_token_balance(test_env, test_env.buyer_holder).await;
+    let writer_init_balance = get_token_balance(test_env, test_env.writer_tokens).await;
 
-    let contract_data = ContractData {
-        token_type,
-        token_qty: 5,
-        strike_type,
-        strike_qty: 5,
-        premium_type,
-        premium_qty: 5,
-        expiry_date: now + expire_time,
+    println!("creating accept-ask instruction");
+    let ix = instruction::accept_ask(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer_temp,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        lots,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &test_env.buyer.main],
+        test_env.ctx.last_blockhash.clone(),
+    );
+    println!("sending accept-ask transaction...");
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+
+    println!("asserting contract + party states");
+    let buyer_data = Some(PartyData {
+        party_pub: test_env.buyer.main.pubkey(),
+        temp_pub: test_env.buyer_temp.clone(),
+        receive_pub: test_env.buyer.mint_1.clone(),
+        receive_ata: test_env.buyer_holder.clone(),
+    });
+
+    let expected_data = ContractPDA {
+        buyer_data,
+        ..filled(contract_pda, lots)
     };
 
-    let mint_seed = contract_data.get_seed();
-    println!("creating holder mint account");
-    let (mint_pda, _mint_bump) =
-        Pubkey::find_program_address(&[&s1, &mint_seed], &test_env.program_key.pubkey());
-
-    let accs = vec![
-        AccountMeta {
-            pubkey: test_env.ctx.payer.pubkey(),
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.holder_mint.pubkey(),
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: mint_pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: spl_token::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: sysvar::rent::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
+    let pda_account = test_env
+        .ctx
+        .banks_client
+        .get_account(pda)
+        .await
+        .unwrap()
+        .expect("could not find PDA account");
+
+    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
+
+    assert_eq!(expected_data, pda_data, "incorrect PDA data");
+
+    let prem_balance = get_token_balance(test_env, test_env.writer.mint_2.clone()).await;
+
+    let prem_paid = prem_balance - prem_init_balance;
+    assert_eq!(
+        prem_paid,
+        expected_data.contract_data.premium_qty * lots,
+        "incorrect writer premium balance"
+    );
 
-    let mut ixd = [0; 34];
-    ixd[0] = 7;
-    ixd[1] = s1[0];
-    ixd[2..].copy_from_slice(&mint_seed);
+    let buyer_holder_balance = get_token_balance(test_env, test_env.buyer_holder.clone()).await;
+    assert_eq!(
+        buyer_holder_balance,
+        holder_init_balance + lots,
+        "incorrect buyer holder token balance"
+    );
+    assert_eq!(
+        get_token_balance(test_env, test_env.writer_tokens).await,
+        writer_init_balance + lots,
+        "incorrect writer token balance"
+    );
 
-    let ix = Instruction::new_with_bytes(test_env.program_key.pubkey(), &ixd, accs);
+    println!("trying illegal transaction...");
+    let ix = spl_token::instruction::transfer(
+        &spl_token::id(),
+        &test_env.writer_temp,
+        &test_env.writer.mint_1,
+        &test_env.writer.main.pubkey(),
+        &[&test_env.writer.main.pubkey()],
+        5,
+    )
+    .unwrap();
     let tx = Transaction::new_signed_with_payer(
         &[ix],
         Some(&test_env.ctx.payer.pubkey()),
-        &[&test_env.ctx.payer, &test_env.holder_mint],
+        &[&test_env.ctx.payer, &test_env.writer.main],
         test_env.ctx.last_blockhash,
     );
-    test_env
+    let tx_err = test_env
         .ctx
         .banks_client
         .process_transaction(tx)
         .await
-        .unwrap();
+        .expect_err("illegal transaction did not fail");
+    println!("illegal transaction failed: {:?}", tx_err);
 
-    println!("creating holder_ata");
-    let ix = create_associated_token_account(
-        &test_env.ctx.payer.pubkey(),
-        &test_env.buyer.main.pubkey(),
-        &test_env.holder_mint.pubkey(),
-    );
+    expected_data
+}
+
+// a further offer on the terms of an existing series, by the other party
+async fn pooled_offer(
+    test_env: &mut TestEnv,
+    init_mode: &InitMode,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+) -> ContractPDA {
+    let signer = match init_mode {
+        InitMode::BUYER => &test_env.buyer.main,
+        InitMode::WRITER => &test_env.writer.main,
+    };
+    test_env.index_seed[..32].copy_from_slice(&signer.pubkey().to_bytes());
+    let ix = offer_instruction(test_env, init_mode, &contract_type, contract_data);
     let tx = Transaction::new_signed_with_payer(
         &[ix],
         Some(&test_env.ctx.payer.pubkey()),
-        &[&test_env.ctx.payer],
+        &[&test_env.ctx.payer, signer],
         test_env.ctx.last_blockhash,
     );
     test_env
@@ -561,442 +4346,608 @@ async fn init_contract(
         .await
         .unwrap();
 
-    let buyer_data = match init_mode {
-        InitMode::BUYER => Some(PartyData {
-            party_pub: test_env.buyer.main.pubkey(),
-            temp_pub: test_env.buyer_temp.clone(),
-            receive_pub: test_env.buyer.mint_1.clone(),
-            receive_ata: test_env.buyer_holder.clone(),
-        }),
-        InitMode::WRITER => None,
-    };
-
-    let writer_data = match init_mode {
-        InitMode::BUYER => None,
-        InitMode::WRITER => Some(PartyData {
-            party_pub: test_env.writer.main.pubkey(),
-            temp_pub: test_env.writer_temp.clone(),
-            receive_pub: test_env.writer.mint_2.clone(),
-            receive_ata: test_env.writer.mint_2.clone(),
-        }),
-    };
-
-    let mut instruction_data = [0; 171];
-    instruction_data[0] = match init_mode {
-        InitMode::BUYER => 0,
-        InitMode::WRITER => 1,
-    };
-    instruction_data[1] = match contract_type {
-        ContractType::CALL => 0,
-        ContractType::PUT => 1,
-    };
-    let cd = contract_data.serialize();
-    instruction_data[2..ContractData::LEN + 2].copy_from_slice(&cd);
-    instruction_data[ContractData::LEN + 2..].copy_from_slice(&test_env.index_seed);
-    let seed = contract_data.get_seed();
-    let index_seed = get_seed(&test_env.index_seed);
-
-    let (pda, bump) =
-        Pubkey::find_program_address(&[&seed, &index_seed], &test_env.program_key.pubkey());
+    let pda = instruction::get_data_pda(
+        &test_env.program_key.pubkey(),
+        &contract_data.get_seed(),
+        &get_seed(&test_env.index_seed),
+    );
+    let pda_account = test_env
+        .ctx
+        .banks_client
+        .get_account(pda)
+        .await
+        .unwrap()
+        .expect("could not find PDA account");
+    ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap()
+}
 
-    let (initialiser, token_temp, receive_acc, receive_ata) = match init_mode {
+// places an order funded from the party's temp, returning the offer it rests as
+async fn place_order(
+    test_env: &mut TestEnv,
+    init_mode: &InitMode,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    contract_no: u64,
+    crossed: &[(Pubkey, Option<&ContractPDA>)],
+) -> Result<Option<ContractPDA>, BanksClientError> {
+    let program_id = test_env.program_key.pubkey();
+    let (party, signer, source, receive_acc, receive_ata) = match init_mode {
         InitMode::BUYER => (
-            test_env.buyer.main.pubkey(),
-            test_env.buyer_temp.clone(),
-            test_env.buyer.mint_1.clone(),
-            test_env.buyer_holder.clone(),
+            InitParty::BUYER,
+            &test_env.buyer.main,
+            test_env.buyer_temp,
+            test_env.buyer.mint_1,
+            test_env.buyer_holder,
         ),
         InitMode::WRITER => (
-            test_env.writer.main.pubkey(),
-            test_env.writer_temp.clone(),
-            test_env.writer.mint_2.clone(),
-            test_env.writer.mint_2.clone(),
+            InitParty::WRITER,
+            &test_env.writer.main,
+            test_env.writer_temp,
+            writer_receive(test_env, &contract_type, contract_data),
+            test_env.writer.mint_2,
         ),
     };
+    let signer_key = signer.pubkey();
+    let escrow_mint = match party {
+        InitParty::BUYER => contract_data.premium_type,
+        InitParty::WRITER => contract_data.collateral(contract_type).0,
+    };
+    let token_temp = create_token_account(test_env, escrow_mint, &signer_key).await;
 
-    let accounts = vec![
-        AccountMeta {
-            pubkey: initialiser,
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: token_temp,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: receive_acc,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: receive_ata,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: mint_pda.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.holder_mint.pubkey(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: spl_token::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
-
-    println!("sending initialise contract instruction...");
-    let instruction =
-        Instruction::new_with_bytes(test_env.program_key.pubkey(), &instruction_data, accounts);
-
+    let mut index_seed = [0; 41];
+    index_seed[..32].copy_from_slice(&signer_key.to_bytes());
+    index_seed[32] = contract_type as u8;
+    index_seed[33..].copy_from_slice(&contract_no.to_le_bytes());
+    let mut ix = instruction::place_order(
+        &program_id,
+        party,
+        &signer_key,
+        &source,
+        &token_temp,
+        &receive_acc,
+        &receive_ata,
+        &test_env.holder_mint.pubkey(),
+        contract_type,
+        contract_data,
+        &index_seed,
+        None,
+        crossed,
+    )
+    .unwrap();
+    // the builder never names a counterparty
+    if let Some(counterparty) = test_env.counterparty {
+        let mut od = match instruction::decode_instruction(&program_id, &ix.data).unwrap() {
+            InstructionType::PlaceOrder { instruction } => instruction,
+            _ => unreachable!(),
+        };
+        od.counterparty = Some(counterparty);
+        ix.data = InstructionType::PlaceOrder { instruction: od }.pack();
+    }
     let signer = match init_mode {
         InitMode::BUYER => &test_env.buyer.main,
         InitMode::WRITER => &test_env.writer.main,
     };
     let tx = Transaction::new_signed_with_payer(
-        &[instruction],
+        &[ix],
         Some(&test_env.ctx.payer.pubkey()),
         &[&test_env.ctx.payer, signer],
-        test_env.ctx.last_blockhash.clone(),
+        test_env.ctx.last_blockhash,
     );
+    test_env.ctx.banks_client.process_transaction(tx).await?;
 
-    test_env
+    let pda = instruction::get_data_pda(
+        &program_id,
+        &contract_data.get_seed(),
+        &get_seed(&index_seed),
+    );
+    Ok(test_env
         .ctx
         .banks_client
-        .process_transaction(tx)
+        .get_account(pda)
         .await
-        .unwrap();
+        .unwrap()
+        .map(|account| ContractPDA::unpack_from_slice(&account.data[..]).unwrap()))
+}
 
-    println!("asserting contract state");
-    let pda_account = test_env
+async fn get_order_book(
+    test_env: &mut TestEnv,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+) -> OrderBook {
+    let (order_book, _bump) = instruction::get_order_book(
+        &test_env.program_key.pubkey(),
+        contract_type,
+        &contract_data.series_seed(),
+        &contract_data.premium_type,
+    );
+    let account = test_env
         .ctx
         .banks_client
-        .get_account(pda)
+        .get_account(order_book)
         .await
         .unwrap()
-        .expect("could not find PDA account");
+        .expect("could not find order book account");
+    OrderBook::unpack_from_slice(&account.data[..]).unwrap()
+}
 
-    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
+// valid for a minute
+async fn quote(
+    test_env: &mut TestEnv,
+    party: InitParty,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    nonce: u64,
+) -> Quote {
+    let clock = test_env
+        .ctx
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap();
+    let (maker, taker) = match party {
+        InitParty::BUYER => (test_env.buyer.main.pubkey(), test_env.writer.main.pubkey()),
+        InitParty::WRITER => (test_env.writer.main.pubkey(), test_env.buyer.main.pubkey()),
+    };
+    Quote {
+        maker,
+        party,
+        taker,
+        contract_type,
+        contract_data: contract_data.clone(),
+        valid_until: clock.unix_timestamp * 1000 + 60_000,
+        nonce,
+    }
+}
 
-    let contract_state = match init_mode {
-        InitMode::BUYER => ContractState::BID,
-        InitMode::WRITER => ContractState::ASK,
+async fn approve_quote_authority(
+    test_env: &mut TestEnv,
+    maker_party: InitParty,
+    source: Pubkey,
+    amount: u64,
+) {
+    let maker = match maker_party {
+        InitParty::BUYER => &test_env.buyer.main,
+        InitParty::WRITER => &test_env.writer.main,
     };
-    let init_party = match init_mode {
-        InitMode::BUYER => InitParty::BUYER,
-        InitMode::WRITER => InitParty::WRITER,
+    let (quote_authority, _bump) =
+        instruction::get_quote_authority(&test_env.program_key.pubkey(), &maker.pubkey());
+    let ix = spl_token::instruction::approve(
+        &spl_token::id(),
+        &source,
+        &quote_authority,
+        &maker.pubkey(),
+        &[],
+        amount,
+    )
+    .unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, maker],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+}
+
+// the other party accepts `quote`, carrying the maker's signature of `signed` if any
+async fn accept_quote(
+    test_env: &mut TestEnv,
+    quote: &Quote,
+    signed: Option<&Quote>,
+) -> Result<(), BanksClientError> {
+    let ixs = accept_quote_ixs(test_env, quote, signed).await;
+    process_as_taker(test_env, quote.party, &ixs).await
+}
+
+async fn accept_quote_ixs(
+    test_env: &mut TestEnv,
+    quote: &Quote,
+    signed: Option<&Quote>,
+) -> Vec<Instruction> {
+    let program_id = test_env.program_key.pubkey();
+    let ct = quote.contract_type;
+    let cd = &quote.contract_data;
+    let (asset_temp, writer_receive) = match cd.pooled {
+        true => (Pubkey::default(), Pubkey::default()),
+        false => {
+            let pda = instruction::get_data_pda(
+                &program_id,
+                &cd.get_seed(),
+                &get_seed(&quote.index_data()),
+            );
+            // the writer's account for the contract's writer mint is opened by the quote
+            let (writer_mint, _bump) = instruction::get_contract_writer_mint(&program_id, &pda);
+            test_env.writer_tokens =
+                get_associated_token_address(&test_env.writer.main.pubkey(), &writer_mint);
+            (
+                create_token_account(test_env, cd.collateral(ct).0, &pda).await,
+                create_token_account(test_env, cd.receive_types(ct).1, &pda).await,
+            )
+        }
     };
+    let (maker, taker) = match quote.party {
+        InitParty::BUYER => (&test_env.buyer.main, &test_env.writer.main),
+        InitParty::WRITER => (&test_env.writer.main, &test_env.buyer.main),
+    };
+    let mut ixs = vec![];
+    if let Some(signed) = signed {
+        let message = signed.message(&program_id);
+        let signature = maker.sign_message(&message);
+        ixs.push(instruction::quote_signature(
+            &maker.pubkey(),
+            signature.as_ref().try_into().unwrap(),
+            &message,
+        ));
+    }
+    ixs.push(instruction::accept_quote(
+        &program_id,
+        &taker.pubkey(),
+        quote,
+        &test_env.buyer_temp,
+        &test_env.buyer.mint_1,
+        &test_env.writer_temp,
+        &asset_temp,
+        &writer_receive,
+        &test_env.writer.mint_2,
+        &test_env.holder_mint.pubkey(),
+    ));
+    ixs
+}
 
-    let expected_data = ContractPDA {
-        contract_data,
-        contract_state,
-        buyer_data,
-        writer_data,
-        is_initialised: true,
-        seed,
-        bump,
-        init_party,
-        contract_type: *contract_type,
-        index_seed,
+// signed by the party opposite `maker_party`
+async fn process_as_taker(
+    test_env: &mut TestEnv,
+    maker_party: InitParty,
+    ixs: &[Instruction],
+) -> Result<(), BanksClientError> {
+    let taker = match maker_party {
+        InitParty::BUYER => &test_env.writer.main,
+        InitParty::WRITER => &test_env.buyer.main,
     };
+    let tx = Transaction::new_signed_with_payer(
+        ixs,
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, taker],
+        test_env.ctx.last_blockhash,
+    );
+    test_env.ctx.banks_client.process_transaction(tx).await
+}
 
-    assert_eq!(expected_data, pda_data, "incorrect PDA data");
+async fn get_contract(test_env: &mut TestEnv, pda: Pubkey) -> ContractPDA {
+    let account = test_env
+        .ctx
+        .banks_client
+        .get_account(pda)
+        .await
+        .unwrap()
+        .expect("could not find PDA account");
+    ContractPDA::unpack_from_slice(&account.data[..]).unwrap()
+}
 
-    println!("trying illegal transaction...");
-    let (temp, dest, kp) = match init_mode {
-        InitMode::BUYER => (
-            &test_env.buyer_temp,
-            &test_env.buyer.mint_2,
-            &test_env.buyer.main,
-        ),
-        InitMode::WRITER => (
-            &test_env.writer_temp,
-            &test_env.writer.mint_1,
-            &test_env.writer.main,
+// an empty token account owned by `owner`, e.g. to escrow a resting order
+async fn create_token_account(test_env: &mut TestEnv, mint: Pubkey, owner: &Pubkey) -> Pubkey {
+    let account = keypair::Keypair::new();
+    let payer = test_env.ctx.payer.pubkey();
+    let len = spl_token::state::Account::LEN;
+    let ixs = [
+        system_instruction::create_account(
+            &payer,
+            &account.pubkey(),
+            Rent::default().minimum_balance(len),
+            len as u64,
+            &spl_token::id(),
         ),
-    };
-    let ix = spl_token::instruction::transfer(
-        &spl_token::id(),
-        temp,
-        dest,
-        &kp.pubkey(),
-        &[&kp.pubkey()],
-        5,
-    )
-    .unwrap();
+        spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &account.pubkey(),
+            &mint,
+            owner,
+        )
+        .unwrap(),
+    ];
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&payer),
+        &[&test_env.ctx.payer, &account],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .unwrap();
+    account.pubkey()
+}
+
+// fills a pooled offer, whose collateral goes straight to the series vault
+async fn accept_pooled_lots(
+    test_env: &mut TestEnv,
+    contract_pda: ContractPDA,
+    lots: u64,
+) -> Option<ContractPDA> {
+    let program_id = test_env.program_key.pubkey();
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let ct = contract_pda.contract_type;
+    let (vault, _bump) = instruction::get_series_vault(&program_id, ct, &contract_pda.series_seed);
+    let (_, collateral_qty) = contract_pda.contract_data.collateral(ct);
+    let vault_init_balance = get_token_balance(test_env, vault).await;
+    let holder_init_balance = get_token_balance(test_env, test_env.buyer_holder).await;
+
+    let (ix, signer) = match contract_pda.init_party {
+        InitParty::BUYER => (
+            instruction::accept_bid(
+                &program_id,
+                &test_env.writer.main.pubkey(),
+                &test_env.writer_temp,
+                &writer_receive(test_env, &ct, &contract_pda.contract_data),
+                &test_env.writer.mint_2,
+                &test_env.holder_mint.pubkey(),
+                &contract_pda,
+                lots,
+            ),
+            &test_env.writer.main,
+        ),
+        InitParty::WRITER => (
+            instruction::accept_ask(
+                &program_id,
+                &test_env.buyer.main.pubkey(),
+                &test_env.buyer_temp,
+                &test_env.buyer.mint_1,
+                &test_env.holder_mint.pubkey(),
+                &contract_pda,
+                lots,
+            ),
+            &test_env.buyer.main,
+        ),
+    };
     let tx = Transaction::new_signed_with_payer(
-        &[ix],
+        &[ix.unwrap()],
         Some(&test_env.ctx.payer.pubkey()),
-        &[&test_env.ctx.payer, kp],
+        &[&test_env.ctx.payer, signer],
         test_env.ctx.last_blockhash,
     );
-    let tx_result = test_env
+    test_env
         .ctx
         .banks_client
         .process_transaction(tx)
         .await
-        .expect_err("illegal transaction did not fail");
-    println!("illegal transaction failed: {:?}", tx_result);
-    (pda_data, mint_pda)
+        .unwrap();
+
+    assert_eq!(
+        get_token_balance(test_env, vault).await,
+        vault_init_balance + collateral_qty * lots,
+        "incorrect series vault balance"
+    );
+    assert_eq!(
+        get_token_balance(test_env, test_env.buyer_holder).await,
+        holder_init_balance + lots,
+        "incorrect buyer holder token balance"
+    );
+    let pda_account = test_env.ctx.banks_client.get_account(pda).await.unwrap();
+    let expected = filled(contract_pda, lots);
+    if expected.unfilled_lots == 0 {
+        assert!(pda_account.is_none(), "filled pooled contract not closed");
+        return None;
+    }
+    let pda_data = ContractPDA::unpack_from_slice(&pda_account.unwrap().data[..]).unwrap();
+    assert_eq!(pda_data.contract_state, expected.contract_state);
+    assert_eq!(pda_data.open_lots, expected.open_lots);
+    assert_eq!(pda_data.unfilled_lots, expected.unfilled_lots);
+    Some(pda_data)
+}
+
+// signed by the payer alone, as by a keeper
+async fn process_cranked(test_env: &mut TestEnv, ix: Instruction) -> Result<(), BanksClientError> {
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer],
+        test_env.ctx.last_blockhash,
+    );
+    test_env.ctx.banks_client.process_transaction(tx).await
+}
+
+async fn process_pooled(
+    test_env: &mut TestEnv,
+    ix: Instruction,
+    signer: fn(&TestEnv) -> &keypair::Keypair,
+) -> Result<(), BanksClientError> {
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, signer(test_env)],
+        test_env.ctx.last_blockhash,
+    );
+    test_env.ctx.banks_client.process_transaction(tx).await
+}
+
+fn filled(contract_pda: ContractPDA, lots: u64) -> ContractPDA {
+    let unfilled_lots = contract_pda.unfilled_lots - lots;
+    let contract_state = match unfilled_lots {
+        0 => ContractState::FINAL,
+        _ => ContractState::PARTIALLY_FILLED,
+    };
+    ContractPDA {
+        contract_state,
+        open_lots: contract_pda.open_lots + lots,
+        unfilled_lots,
+        writer_lots: contract_pda.writer_lots + lots,
+        ..contract_pda
+    }
+}
+
+async fn execute(test_env: &mut TestEnv, contract_pda: ContractPDA, contract_type: &ContractType) {
+    let lots = contract_pda.open_lots;
+    execute_lots(test_env, contract_pda, contract_type, lots).await;
 }
 
-async fn accept_bid(
+// returns the contract still open after exercising fewer than all open lots
+async fn execute_lots(
     test_env: &mut TestEnv,
     contract_pda: ContractPDA,
-    mint_pda: Pubkey,
-) -> ContractPDA {
+    contract_type: &ContractType,
+    lots: u64,
+) -> Option<ContractPDA> {
     let (pda, _bump) = Pubkey::find_program_address(
         &[&contract_pda.seed, &contract_pda.index_seed],
         &test_env.program_key.pubkey(),
     );
 
-    let prem_init_balance = get_token_balance(test_env, test_env.writer.mint_2.clone()).await;
-
-    println!("creating accept-bid instruction...");
-
-    let accounts = vec![
-        AccountMeta {
-            pubkey: test_env.writer.main.pubkey(),
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.writer_temp.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.writer.mint_2.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.writer.mint_2.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer_temp.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer.main.pubkey(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer_holder.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: mint_pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.holder_mint.pubkey(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: spl_token::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
-
-    let instruction_data = &[2];
+    // the writer's side is held in escrow until redeemed by writer token
+    let writer_receive = contract_pda.writer_data.as_ref().unwrap().receive_pub;
+    let (strike_rec_pub, asset_rec_pub) = match contract_type {
+        ContractType::CALL => (writer_receive, test_env.buyer.mint_1),
+        ContractType::PUT => (test_env.buyer.mint_1, writer_receive),
+    };
+    let strike_init_balance = get_token_balance(test_env, strike_rec_pub.clone()).await;
+    let asset_init_balance = get_token_balance(test_env, asset_rec_pub.clone()).await;
+    let holder_init_balance = get_token_balance(test_env, test_env.buyer_holder).await;
 
-    let ix = Instruction::new_with_bytes(test_env.program_key.pubkey(), instruction_data, accounts);
+    println!("creating execute transaction");
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &test_env.buyer.mint_2,
+        &test_env.buyer.mint_1,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        lots,
+    )
+    .unwrap();
     let tx = Transaction::new_signed_with_payer(
         &[ix],
-        Some(&test_env.writer.main.pubkey()),
-        &[&test_env.writer.main],
-        test_env.ctx.last_blockhash.clone(),
+        Some(&test_env.buyer.main.pubkey()),
+        &[&test_env.buyer.main],
+        test_env.ctx.last_blockhash,
     );
-    println!("sending accept-bid instruction...");
+    println!("sending execute transaction...");
     test_env
         .ctx
         .banks_client
         .process_transaction(tx)
         .await
         .unwrap();
+    println!("asserting contract + party states...");
+    let pda_account = test_env.ctx.banks_client.get_account(pda).await.unwrap();
 
-    println!("asserting contract + party states");
-    let pda_account = test_env
+    let strike_acc = test_env
         .ctx
         .banks_client
-        .get_account(pda)
+        .get_account(strike_rec_pub.clone())
         .await
         .unwrap()
-        .expect("could not find PDA account");
-
-    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
-
-    let writer_data = Some(PartyData {
-        party_pub: test_env.writer.main.pubkey(),
-        temp_pub: test_env.writer_temp.clone(),
-        receive_pub: test_env.writer.mint_2.clone(),
-        receive_ata: test_env.writer.mint_2.clone(),
-    });
-
-    let expected_data = ContractPDA {
-        writer_data,
-        contract_state: ContractState::FINAL,
-        ..contract_pda
-    };
-
-    assert_eq!(expected_data, pda_data, "incorrect PDA data");
+        .unwrap();
+    let asset_acc = test_env
+        .ctx
+        .banks_client
+        .get_account(asset_rec_pub.clone())
+        .await
+        .unwrap()
+        .unwrap();
+    let strike_acc_balance = spl_token::state::Account::unpack_from_slice(&strike_acc.data[..])
+        .unwrap()
+        .amount;
+    let asset_acc_balance = spl_token::state::Account::unpack_from_slice(&asset_acc.data[..])
+        .unwrap()
+        .amount;
 
-    let prem_balance = get_token_balance(test_env, test_env.writer.mint_2.clone()).await;
+    let asset_transferred = asset_acc_balance - asset_init_balance;
+    let strike_transferred = strike_acc_balance - strike_init_balance;
 
-    let prem_paid = prem_balance - prem_init_balance;
     assert_eq!(
-        prem_paid, expected_data.contract_data.premium_qty,
-        "incorrect writer premium balance"
+        asset_transferred,
+        contract_pda.contract_data.token_qty * lots,
+        "incorrect amount of asset transferred"
+    );
+    assert_eq!(
+        strike_transferred,
+        contract_pda.contract_data.strike_qty * lots,
+        "incorrect strike amount transferred"
     );
 
     let buyer_holder_balance = get_token_balance(test_env, test_env.buyer_holder.clone()).await;
 
     assert_eq!(
-        buyer_holder_balance, 1,
+        buyer_holder_balance,
+        holder_init_balance - lots,
         "incorrect buyer holder token balance"
     );
 
-    let ix = spl_token::instruction::transfer(
-        &spl_token::id(),
-        &test_env.writer_temp,
-        &test_env.writer.mint_1,
-        &test_env.writer.main.pubkey(),
-        &[&test_env.writer.main.pubkey()],
-        5,
-    )
-    .unwrap();
-
-    println!("trying illegal transaction...");
-    let tx = Transaction::new_signed_with_payer(
-        &[ix],
-        Some(&test_env.ctx.payer.pubkey()),
-        &[&test_env.ctx.payer, &test_env.writer.main],
-        test_env.ctx.last_blockhash,
-    );
-    let tx_err = test_env
-        .ctx
-        .banks_client
-        .process_transaction(tx)
-        .await
-        .expect_err("illegal transaction did not fail");
-    println!("illegal transaction failed: {:?}", tx_err);
-
-    expected_data
+    let pda_data =
+        ContractPDA::unpack_from_slice(&pda_account.expect("PDA account closed").data[..]).unwrap();
+    let settled = lots == contract_pda.open_lots && contract_pda.unfilled_lots == 0;
+    let expected_data = ContractPDA {
+        contract_state: match settled {
+            true => ContractState::SETTLED,
+            false => contract_pda.contract_state,
+        },
+        open_lots: contract_pda.open_lots - lots,
+        ..contract_pda
+    };
+    assert_eq!(expected_data, pda_data, "incorrect PDA data");
+    if settled {
+        let writer_lots = expected_data.writer_lots;
+        assert!(
+            redeem_writer_lots(test_env, expected_data, writer_lots, None)
+                .await
+                .is_none()
+        );
+        return None;
+    }
+    Some(expected_data)
 }
 
-async fn accept_ask(
+// cash-settled exercise paying `payout` per lot, returns the contract if still open
+async fn execute_cash_lots(
     test_env: &mut TestEnv,
     contract_pda: ContractPDA,
-    mint_pda: Pubkey,
-) -> ContractPDA {
+    lots: u64,
+    payout: u64,
+) -> Option<ContractPDA> {
     let (pda, _bump) = Pubkey::find_program_address(
         &[&contract_pda.seed, &contract_pda.index_seed],
         &test_env.program_key.pubkey(),
     );
-
-    let prem_init_balance = get_token_balance(test_env, test_env.writer.mint_2.clone()).await;
-
-    println!("creating accept-ask instruction");
-
-    let accounts = vec![
-        AccountMeta {
-            pubkey: test_env.buyer.main.pubkey(),
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer_temp.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer.mint_1.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer_holder.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: mint_pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.holder_mint.pubkey(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.writer.mint_2.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: spl_token::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
-
-    let instruction_data = &[3];
-
-    let ix = Instruction::new_with_bytes(test_env.program_key.pubkey(), instruction_data, accounts);
+    let (buyer_rec_type, _) = contract_pda
+        .contract_data
+        .receive_types(contract_pda.contract_type);
+    let buyer_receive =
+        get_associated_token_address(&test_env.buyer.main.pubkey(), &buyer_rec_type);
+    let writer_receive = writer_receive(
+        test_env,
+        &contract_pda.contract_type,
+        &contract_pda.contract_data,
+    );
+    let (_, collateral) = contract_pda
+        .contract_data
+        .collateral(contract_pda.contract_type);
+    let buyer_init_balance = get_token_balance(test_env, buyer_receive).await;
+    let writer_init_balance = get_token_balance(test_env, writer_receive).await;
+    let holder_init_balance = get_token_balance(test_env, test_env.buyer_holder).await;
+
+    println!("sending cash-settled execute transaction...");
+    let ix = instruction::execute(
+        &test_env.program_key.pubkey(),
+        &test_env.buyer.main.pubkey(),
+        &buyer_receive,
+        &buyer_receive,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        lots,
+    )
+    .unwrap();
     let tx = Transaction::new_signed_with_payer(
         &[ix],
-        Some(&test_env.ctx.payer.pubkey()),
-        &[&test_env.ctx.payer, &test_env.buyer.main],
-        test_env.ctx.last_blockhash.clone(),
+        Some(&test_env.buyer.main.pubkey()),
+        &[&test_env.buyer.main],
+        test_env.ctx.last_blockhash,
     );
-    println!("sending accept-ask transaction...");
     test_env
         .ctx
         .banks_client
@@ -1004,220 +4955,239 @@ async fn accept_ask(
         .await
         .unwrap();
 
-    println!("asserting contract + party states");
-    let buyer_data = Some(PartyData {
-        party_pub: test_env.buyer.main.pubkey(),
-        temp_pub: test_env.buyer_temp.clone(),
-        receive_pub: test_env.buyer.mint_1.clone(),
-        receive_ata: test_env.buyer_holder.clone(),
-    });
-
-    let expected_data = ContractPDA {
-        buyer_data,
-        contract_state: ContractState::FINAL,
-        ..contract_pda
-    };
-
-    let pda_account = test_env
-        .ctx
-        .banks_client
-        .get_account(pda)
-        .await
-        .unwrap()
-        .expect("could not find PDA account");
-
-    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
-
-    assert_eq!(expected_data, pda_data, "incorrect PDA data");
-
-    let prem_balance = get_token_balance(test_env, test_env.writer.mint_2.clone()).await;
-
-    let prem_paid = prem_balance - prem_init_balance;
     assert_eq!(
-        prem_paid, expected_data.contract_data.premium_qty,
-        "incorrect writer premium balance"
+        get_token_balance(test_env, buyer_receive).await,
+        buyer_init_balance + payout * lots,
+        "incorrect payout to buyer"
+    );
+    assert_eq!(
+        get_token_balance(test_env, writer_receive).await,
+        writer_init_balance + (collateral - payout) * lots,
+        "incorrect collateral returned to writer"
     );
-
-    let buyer_holder_balance = get_token_balance(test_env, test_env.buyer_holder.clone()).await;
     assert_eq!(
-        buyer_holder_balance, 1,
+        get_token_balance(test_env, test_env.buyer_holder).await,
+        holder_init_balance - lots,
         "incorrect buyer holder token balance"
     );
 
-    println!("trying illegal transaction...");
-    let ix = spl_token::instruction::transfer(
-        &spl_token::id(),
-        &test_env.writer_temp,
-        &test_env.writer.mint_1,
-        &test_env.writer.main.pubkey(),
-        &[&test_env.writer.main.pubkey()],
-        5,
-    )
-    .unwrap();
+    let pda_account = test_env.ctx.banks_client.get_account(pda).await.unwrap();
+    let pda_data =
+        ContractPDA::unpack_from_slice(&pda_account.expect("PDA account closed").data[..]).unwrap();
+    let settled = lots == contract_pda.open_lots && contract_pda.unfilled_lots == 0;
+    let expected_data = ContractPDA {
+        contract_state: match settled {
+            true => ContractState::SETTLED,
+            false => contract_pda.contract_state,
+        },
+        open_lots: contract_pda.open_lots - lots,
+        ..contract_pda
+    };
+    assert_eq!(expected_data, pda_data, "incorrect PDA data");
+    if settled {
+        let writer_lots = expected_data.writer_lots;
+        assert!(
+            redeem_writer_lots(test_env, expected_data, writer_lots, None)
+                .await
+                .is_none()
+        );
+        return None;
+    }
+    Some(expected_data)
+}
+
+async fn assert_execute_error(test_env: &mut TestEnv, ix: Instruction, expected: OptionsError) {
     let tx = Transaction::new_signed_with_payer(
         &[ix],
         Some(&test_env.ctx.payer.pubkey()),
-        &[&test_env.ctx.payer, &test_env.writer.main],
+        &[&test_env.ctx.payer, &test_env.buyer.main],
         test_env.ctx.last_blockhash,
     );
-    let tx_err = test_env
+    let err = test_env
         .ctx
         .banks_client
         .process_transaction(tx)
         .await
-        .expect_err("illegal transaction did not fail");
-    println!("illegal transaction failed: {:?}", tx_err);
-
-    expected_data
+        .expect_err("execute did not fail");
+    assert_options_error(err, expected);
 }
 
-async fn execute(
-    test_env: &mut TestEnv,
-    contract_pda: ContractPDA,
-    mint_pda: Pubkey,
-    contract_type: &ContractType,
-) {
+// auto-exercise at expiry paying `payout` per open lot
+async fn settle_at_expiry(test_env: &mut TestEnv, contract_pda: ContractPDA, payout: u64) {
     let (pda, _bump) = Pubkey::find_program_address(
         &[&contract_pda.seed, &contract_pda.index_seed],
         &test_env.program_key.pubkey(),
     );
+    let (_, collateral) = contract_pda
+        .contract_data
+        .collateral(contract_pda.contract_type);
 
-    let (strike_rec_pub, asset_rec_pub) = match contract_type {
-        ContractType::CALL => (test_env.writer.mint_2, test_env.buyer.mint_1),
-        ContractType::PUT => (test_env.buyer.mint_1, test_env.writer.mint_2),
-    };
-    let strike_init_balance = get_token_balance(test_env, strike_rec_pub.clone()).await;
-    let asset_init_balance = get_token_balance(test_env, asset_rec_pub.clone()).await;
-
-    println!("creating execute transaction");
-    let accounts = vec![
-        AccountMeta {
-            pubkey: test_env.buyer.main.pubkey(),
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer.mint_2.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer.mint_1.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer_holder.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: mint_pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.holder_mint.pubkey(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.writer_temp.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.writer.main.pubkey(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.writer.mint_2.clone(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: spl_token::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
-
-    let instruction_data = &[5];
-
-    let ix = Instruction::new_with_bytes(test_env.program_key.pubkey(), instruction_data, accounts);
+    println!("sending settle_at_expiry instruction");
+    let ix = instruction::settle_at_expiry(&test_env.program_key.pubkey(), &contract_pda).unwrap();
     let tx = Transaction::new_signed_with_payer(
         &[ix],
-        Some(&test_env.buyer.main.pubkey()),
-        &[&test_env.buyer.main],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer],
         test_env.ctx.last_blockhash,
     );
-    println!("sending execute transaction...");
     test_env
         .ctx
         .banks_client
         .process_transaction(tx)
         .await
         .unwrap();
-    println!("asserting contract + party states...");
-    test_env
-        .ctx
-        .banks_client
-        .get_account(pda)
-        .await
-        .unwrap()
-        .ok_or("")
-        .expect_err("PDA account not closed");
+    assert_eq!(
+        get_token_balance(test_env, test_env.writer_temp).await,
+        collateral * contract_pda.open_lots,
+        "collateral moved before it was claimed"
+    );
 
-    let strike_acc = test_env
-        .ctx
-        .banks_client
-        .get_account(strike_rec_pub.clone())
+    let settled = match payout {
+        0 => assert_settled(test_env, pda, contract_pda).await,
+        _ => {
+            let lots = contract_pda.open_lots;
+            let settled = ContractPDA {
+                contract_state: ContractState::SETTLED,
+                unfilled_lots: 0,
+                settle_payout: payout,
+                ..contract_pda
+            };
+            assert_eq!(
+                get_contract(test_env, pda).await,
+                settled,
+                "incorrect PDA data"
+            );
+            claim_settlement_lots(test_env, settled, lots, None)
+                .await
+                .expect("contract closed with writer tokens left")
+        }
+    };
+    let writer_lots = settled.writer_lots;
+    assert!(redeem_writer_lots(test_env, settled, writer_lots, None)
         .await
-        .unwrap()
-        .unwrap();
-    let asset_acc = test_env
+        .is_none());
+}
+
+// burns `lots` holder tokens of `holder` (the buyer by default) for their settlement
+// payout into its mint_1 / mint_2 account, returns the contract while it stays open
+async fn claim_settlement_lots(
+    test_env: &mut TestEnv,
+    contract_pda: ContractPDA,
+    lots: u64,
+    holder: Option<&PartyKeys>,
+) -> Option<ContractPDA> {
+    let program_id = test_env.program_key.pubkey();
+    let pda = instruction::get_data_pda(&program_id, &contract_pda.seed, &contract_pda.index_seed);
+    let holder_mint = test_env.holder_mint.pubkey();
+    let (collateral_type, _) = contract_pda
+        .contract_data
+        .collateral(contract_pda.contract_type);
+    let holder_keys = holder.unwrap_or(&test_env.buyer);
+    let payout_dest = match collateral_type == test_env.mint_1.pubkey() {
+        true => holder_keys.mint_1,
+        false => holder_keys.mint_2,
+    };
+    let holder_pub = holder_keys.main.pubkey();
+    let holder_ata = get_associated_token_address(&holder_pub, &holder_mint);
+    let payout_init_balance = get_token_balance(test_env, payout_dest).await;
+    let holder_init_balance = get_token_balance(test_env, holder_ata).await;
+
+    println!("sending claim_settlement instruction...");
+    let ix = instruction::claim_settlement(
+        &program_id,
+        &holder_pub,
+        &payout_dest,
+        &holder_mint,
+        &contract_pda,
+        lots,
+    )
+    .unwrap();
+    let holder_keys = holder.unwrap_or(&test_env.buyer);
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, &holder_keys.main],
+        test_env.ctx.last_blockhash,
+    );
+    test_env
         .ctx
         .banks_client
-        .get_account(asset_rec_pub.clone())
+        .process_transaction(tx)
         .await
-        .unwrap()
         .unwrap();
-    let strike_acc_balance = spl_token::state::Account::unpack_from_slice(&strike_acc.data[..])
-        .unwrap()
-        .amount;
-    let asset_acc_balance = spl_token::state::Account::unpack_from_slice(&asset_acc.data[..])
-        .unwrap()
-        .amount;
-
-    let asset_transferred = asset_acc_balance - asset_init_balance;
-    let strike_transferred = strike_acc_balance - strike_init_balance;
 
     assert_eq!(
-        asset_transferred, contract_pda.contract_data.token_qty,
-        "incorrect amount of asset transferred"
+        get_token_balance(test_env, holder_ata).await,
+        holder_init_balance - lots,
+        "incorrect holder token balance"
     );
     assert_eq!(
-        strike_transferred, contract_pda.contract_data.strike_qty,
-        "incorrect strike amount transferred"
+        get_token_balance(test_env, payout_dest).await,
+        payout_init_balance + contract_pda.settle_payout * lots,
+        "incorrect payout claimed"
     );
+    let expected_data = ContractPDA {
+        open_lots: contract_pda.open_lots - lots,
+        ..contract_pda
+    };
+    if expected_data.open_lots == 0 && expected_data.writer_lots == 0 {
+        let pda_account = test_env.ctx.banks_client.get_account(pda).await.unwrap();
+        pda_account.ok_or("").expect_err("PDA account not closed");
+        return None;
+    }
+    assert_eq!(
+        get_contract(test_env, pda).await,
+        expected_data,
+        "incorrect PDA data"
+    );
+    Some(expected_data)
+}
 
-    let buyer_holder_balance = get_token_balance(test_env, test_env.buyer_holder.clone()).await;
+// permissionless instructions, signed by the fee payer alone
+async fn assert_crank_error(test_env: &mut TestEnv, ix: Instruction, expected: OptionsError) {
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("crank did not fail");
+    assert_options_error(err, expected);
+}
 
-    assert_eq!(
-        buyer_holder_balance, 0,
-        "incorrect buyer holder token balance"
+// writes a TestOracle at test_env.oracle, published `age` seconds ago
+async fn set_oracle_price(test_env: &mut TestEnv, price: u64, decimals: u8, age: i64) {
+    let clock = test_env
+        .ctx
+        .banks_client
+        .get_sysvar::<Clock>()
+        .await
+        .unwrap();
+    let mut data = vec![0; TestOracle::LEN];
+    TestOracle {
+        price,
+        decimals,
+        publish_time: clock.unix_timestamp - age,
+    }
+    .pack_into_slice(&mut data);
+    let account = Account {
+        lamports: Rent::default().minimum_balance(TestOracle::LEN),
+        data,
+        owner: test_env.program_key.pubkey(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    test_env.ctx.set_account(
+        &test_env.oracle.expect("contract has no oracle"),
+        &AccountSharedData::from(account),
     );
+    // a new price changes the outcome of a transaction sent before, byte for byte
+    test_env.ctx.last_blockhash = test_env.ctx.get_new_latest_blockhash().await.unwrap();
 }
 
 async fn cancel_offer(test_env: &mut TestEnv, contract_pda: ContractPDA, init_mode: &InitMode) {
@@ -1239,37 +5209,8 @@ async fn cancel_offer(test_env: &mut TestEnv, contract_pda: ContractPDA, init_mo
         ),
     };
 
-    let accounts = vec![
-        AccountMeta {
-            pubkey: initialiser.pubkey(),
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: token_temp,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: spl_token::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
-
     println!("sending cancel_offer transaction...");
-    let instruction_data = &[4];
-    let ix = Instruction::new_with_bytes(test_env.program_key.pubkey(), instruction_data, accounts);
+    let ix = instruction::cancel_offer(&test_env.program_key.pubkey(), &contract_pda).unwrap();
     let tx = Transaction::new_signed_with_payer(
         &[ix],
         Some(&test_env.ctx.payer.pubkey()),
@@ -1282,7 +5223,6 @@ async fn cancel_offer(test_env: &mut TestEnv, contract_pda: ContractPDA, init_mo
         .process_transaction(tx)
         .await
         .unwrap();
-
     test_env
         .ctx
         .banks_client
@@ -1345,56 +5285,31 @@ async fn cancel_offer(test_env: &mut TestEnv, contract_pda: ContractPDA, init_mo
     );
 }
 
-async fn expire_contract(test_env: &mut TestEnv, contract_pda: ContractPDA) {
+async fn expire_contract(test_env: &mut TestEnv, contract_pda: ContractPDA, crank: bool) {
     let (pda, _bump) = Pubkey::find_program_address(
         &[&contract_pda.seed, &contract_pda.index_seed],
         &test_env.program_key.pubkey(),
     );
 
-    let accounts = vec![
-        AccountMeta {
-            pubkey: test_env.writer.main.pubkey(),
-            is_signer: true,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.writer_temp,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: pda,
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: test_env.buyer.main.pubkey(),
-            is_signer: false,
-            is_writable: true,
-        },
-        AccountMeta {
-            pubkey: system_program::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-        AccountMeta {
-            pubkey: spl_token::id(),
-            is_signer: false,
-            is_writable: false,
-        },
-    ];
-
-    let instruction_data = &[6];
-
-    println!("sending expire_contract instruction");
-    let ix = Instruction::new_with_bytes(test_env.program_key.pubkey(), instruction_data, accounts);
-    let tx = Transaction::new_signed_with_payer(
-        &[ix],
-        Some(&test_env.ctx.payer.pubkey()),
-        &[&test_env.ctx.payer, &test_env.writer.main],
-        test_env.ctx.last_blockhash,
-    );
-
+    let tx = if crank {
+        println!("sending crank_expire instruction");
+        let ix = instruction::crank_expire(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+        Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            &[&test_env.ctx.payer],
+            test_env.ctx.last_blockhash,
+        )
+    } else {
+        println!("sending expire_contract instruction");
+        let ix = instruction::expire(&test_env.program_key.pubkey(), &contract_pda).unwrap();
+        Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            &[&test_env.ctx.payer, &test_env.writer.main],
+            test_env.ctx.last_blockhash,
+        )
+    };
     test_env
         .ctx
         .banks_client
@@ -1402,29 +5317,110 @@ async fn expire_contract(test_env: &mut TestEnv, contract_pda: ContractPDA) {
         .await
         .unwrap();
 
-    test_env
+    let collateral = get_token_balance(test_env, test_env.writer_temp).await;
+    let collateral_per_lot = match contract_pda.contract_type {
+        ContractType::CALL => contract_pda.contract_data.token_qty,
+        ContractType::PUT => contract_pda.contract_data.strike_qty,
+    };
+    assert_eq!(
+        collateral,
+        collateral_per_lot * contract_pda.open_lots,
+        "incorrect collateral left for writer tokens"
+    );
+
+    let settled = assert_settled(test_env, pda, contract_pda).await;
+    let writer_lots = settled.writer_lots;
+    assert!(redeem_writer_lots(test_env, settled, writer_lots, None)
+        .await
+        .is_none());
+}
+
+// expired or fully exercised, with every lot closed for the holders
+async fn assert_settled(
+    test_env: &mut TestEnv,
+    pda: Pubkey,
+    contract_pda: ContractPDA,
+) -> ContractPDA {
+    let pda_account = test_env
         .ctx
         .banks_client
         .get_account(pda)
         .await
         .unwrap()
-        .ok_or("")
-        .expect_err("PDA account not closed");
+        .expect("PDA account closed before writer tokens were redeemed");
+    let pda_data = ContractPDA::unpack_from_slice(&pda_account.data[..]).unwrap();
+    let expected_data = ContractPDA {
+        contract_state: ContractState::SETTLED,
+        open_lots: 0,
+        unfilled_lots: 0,
+        ..contract_pda
+    };
+    assert_eq!(expected_data, pda_data, "incorrect PDA data");
+    expected_data
+}
 
-    println!("sending tokens to writer ATA");
-    let ix = spl_token::instruction::transfer(
-        &spl_token::id(),
-        &test_env.writer_temp,
-        &test_env.writer.mint_1,
-        &test_env.writer.main.pubkey(),
-        &[&test_env.writer.main.pubkey()],
-        5,
+// redeems writer tokens of `redeemer` (the writer by default) into its mint_1 / mint_2
+// accounts, returns the contract while tokens are left
+async fn redeem_writer_lots(
+    test_env: &mut TestEnv,
+    contract_pda: ContractPDA,
+    lots: u64,
+    redeemer: Option<&PartyKeys>,
+) -> Option<ContractPDA> {
+    let program_id = test_env.program_key.pubkey();
+    let (pda, _bump) =
+        Pubkey::find_program_address(&[&contract_pda.seed, &contract_pda.index_seed], &program_id);
+    let (writer_mint, _bump) = instruction::get_writer_mint_of(&program_id, &contract_pda, &pda);
+    let wd = contract_pda.writer_data.as_ref().unwrap();
+    let (writer_temp, writer_receive) = (wd.temp_pub, wd.receive_pub);
+    let writer = test_env.writer.main.pubkey();
+    let initialiser = match contract_pda.init_party {
+        InitParty::BUYER => test_env.buyer.main.pubkey(),
+        InitParty::WRITER => writer,
+    };
+    let (collateral_type, _) = contract_pda
+        .contract_data
+        .collateral(contract_pda.contract_type);
+    let (_, proceeds_type) = contract_pda
+        .contract_data
+        .receive_types(contract_pda.contract_type);
+    let redeemer_keys = redeemer.unwrap_or(&test_env.writer);
+    let pick = |mint: Pubkey| match mint == test_env.mint_1.pubkey() {
+        true => redeemer_keys.mint_1,
+        false => redeemer_keys.mint_2,
+    };
+    let (collateral_dest, proceeds_dest) = (pick(collateral_type), pick(proceeds_type));
+    let redeemer_pub = redeemer_keys.main.pubkey();
+    let writer_tokens = get_associated_token_address(&redeemer_pub, &writer_mint);
+
+    // the payout owed to unclaimed holder lots stays in writer_temp
+    let collateral = get_token_balance(test_env, writer_temp).await
+        - contract_pda.settle_payout * contract_pda.open_lots;
+    let proceeds = get_token_balance(test_env, writer_receive).await;
+    let collateral_init_balance = get_token_balance(test_env, collateral_dest).await;
+    let proceeds_init_balance = get_token_balance(test_env, proceeds_dest).await;
+    let tokens_init_balance = get_token_balance(test_env, writer_tokens).await;
+    let escrow_rent =
+        get_lamports(test_env, writer_temp).await + get_lamports(test_env, writer_receive).await;
+    let pda_rent = get_lamports(test_env, pda).await;
+    let writer_lamports = get_lamports(test_env, writer).await;
+    let initialiser_lamports = get_lamports(test_env, initialiser).await;
+
+    println!("sending redeem_writer instruction...");
+    let ix = instruction::redeem_writer(
+        &program_id,
+        &redeemer_pub,
+        &collateral_dest,
+        &proceeds_dest,
+        &contract_pda,
+        lots,
     )
     .unwrap();
+    let redeemer_keys = redeemer.unwrap_or(&test_env.writer);
     let tx = Transaction::new_signed_with_payer(
         &[ix],
         Some(&test_env.ctx.payer.pubkey()),
-        &[&test_env.ctx.payer, &test_env.writer.main],
+        &[&test_env.ctx.payer, &redeemer_keys.main],
         test_env.ctx.last_blockhash,
     );
     test_env
@@ -1434,13 +5430,113 @@ async fn expire_contract(test_env: &mut TestEnv, contract_pda: ContractPDA) {
         .await
         .unwrap();
 
-    println!("closing writer temp");
-    let ix = spl_token::instruction::close_account(
-        &spl_token::id(),
-        &test_env.writer_temp,
-        &test_env.writer.main.pubkey(),
-        &test_env.writer.main.pubkey(),
-        &[&test_env.writer.main.pubkey()],
+    let share = |amount: u64| amount * lots / contract_pda.writer_lots;
+    assert_eq!(
+        get_token_balance(test_env, writer_tokens).await,
+        tokens_init_balance - lots,
+        "incorrect writer token balance"
+    );
+    // collateral and proceeds may share a mint, and so a destination
+    let gained = match collateral_dest == proceeds_dest {
+        true => vec![(
+            collateral_dest,
+            collateral_init_balance,
+            share(collateral) + share(proceeds),
+        )],
+        false => vec![
+            (collateral_dest, collateral_init_balance, share(collateral)),
+            (proceeds_dest, proceeds_init_balance, share(proceeds)),
+        ],
+    };
+    for (dest, init_balance, expected) in gained {
+        assert_eq!(
+            get_token_balance(test_env, dest).await,
+            init_balance + expected,
+            "incorrect share redeemed"
+        );
+    }
+
+    let pda_account = test_env.ctx.banks_client.get_account(pda).await.unwrap();
+    if lots < contract_pda.writer_lots || contract_pda.open_lots > 0 {
+        let pda_data =
+            ContractPDA::unpack_from_slice(&pda_account.expect("PDA account closed").data[..])
+                .unwrap();
+        let expected_data = ContractPDA {
+            writer_lots: contract_pda.writer_lots - lots,
+            ..contract_pda
+        };
+        assert_eq!(expected_data, pda_data, "incorrect PDA data");
+        return Some(expected_data);
+    }
+
+    pda_account.ok_or("").expect_err("PDA account not closed");
+    for escrow in [writer_temp, writer_receive] {
+        test_env
+            .ctx
+            .banks_client
+            .get_account(escrow)
+            .await
+            .unwrap()
+            .ok_or("")
+            .expect_err("escrow account not closed");
+    }
+    let (writer_gain, initialiser_gain) = match initialiser == writer {
+        true => (escrow_rent + pda_rent, escrow_rent + pda_rent),
+        false => (escrow_rent, pda_rent),
+    };
+    assert_eq!(
+        get_lamports(test_env, writer).await,
+        writer_lamports + writer_gain,
+        "escrow rent not returned to writer"
+    );
+    assert_eq!(
+        get_lamports(test_env, initialiser).await,
+        initialiser_lamports + initialiser_gain,
+        "rent not returned to initialiser"
+    );
+    None
+}
+
+// closes out `lots` with the writer's holder and writer tokens, returns the contract
+// while it stays open
+async fn close_out_lots(
+    test_env: &mut TestEnv,
+    contract_pda: ContractPDA,
+    lots: u64,
+) -> Option<ContractPDA> {
+    let program_id = test_env.program_key.pubkey();
+    let (pda, _bump) =
+        Pubkey::find_program_address(&[&contract_pda.seed, &contract_pda.index_seed], &program_id);
+    let writer = test_env.writer.main.pubkey();
+    let wd = contract_pda.writer_data.as_ref().unwrap();
+    let (writer_temp, writer_receive) = (wd.temp_pub, wd.receive_pub);
+    let writer_holder = get_associated_token_address(&writer, &test_env.holder_mint.pubkey());
+    let initialiser = match contract_pda.init_party {
+        InitParty::BUYER => test_env.buyer.main.pubkey(),
+        InitParty::WRITER => writer,
+    };
+    // the collateral is in mint_1 for both calls and puts
+    let (_, collateral_qty) = contract_pda
+        .contract_data
+        .collateral(contract_pda.contract_type);
+    let collateral_dest = test_env.writer.mint_1;
+
+    let collateral_init_balance = get_token_balance(test_env, collateral_dest).await;
+    let holder_init_balance = get_token_balance(test_env, writer_holder).await;
+    let tokens_init_balance = get_token_balance(test_env, test_env.writer_tokens).await;
+    let pda_rent = get_lamports(test_env, pda).await;
+    let initialiser_lamports = get_lamports(test_env, initialiser).await;
+
+    // closing out lot by lot sends the same transaction twice
+    test_env.ctx.last_blockhash = test_env.ctx.get_new_latest_blockhash().await.unwrap();
+    println!("sending close_out instruction...");
+    let ix = instruction::close_out(
+        &program_id,
+        &writer,
+        &collateral_dest,
+        &test_env.holder_mint.pubkey(),
+        &contract_pda,
+        lots,
     )
     .unwrap();
     let tx = Transaction::new_signed_with_payer(
@@ -1455,6 +5551,136 @@ async fn expire_contract(test_env: &mut TestEnv, contract_pda: ContractPDA) {
         .process_transaction(tx)
         .await
         .unwrap();
+
+    assert_eq!(
+        get_token_balance(test_env, collateral_dest).await,
+        collateral_init_balance + collateral_qty * lots,
+        "incorrect collateral released"
+    );
+    assert_eq!(
+        get_token_balance(test_env, writer_holder).await,
+        holder_init_balance - lots,
+        "incorrect holder token balance"
+    );
+    assert_eq!(
+        get_token_balance(test_env, test_env.writer_tokens).await,
+        tokens_init_balance - lots,
+        "incorrect writer token balance"
+    );
+
+    let pda_account = test_env.ctx.banks_client.get_account(pda).await.unwrap();
+    let open_lots = contract_pda.open_lots - lots;
+    let writer_lots = contract_pda.writer_lots - lots;
+    if open_lots > 0 || contract_pda.unfilled_lots > 0 || writer_lots > 0 {
+        let pda_data =
+            ContractPDA::unpack_from_slice(&pda_account.expect("PDA account closed").data[..])
+                .unwrap();
+        let expected_data = ContractPDA {
+            contract_state: match open_lots == 0 && contract_pda.unfilled_lots == 0 {
+                true => ContractState::SETTLED,
+                false => contract_pda.contract_state,
+            },
+            open_lots,
+            writer_lots,
+            ..contract_pda
+        };
+        assert_eq!(expected_data, pda_data, "incorrect PDA data");
+        return Some(expected_data);
+    }
+
+    pda_account.ok_or("").expect_err("PDA account not closed");
+    for escrow in [writer_temp, writer_receive] {
+        let acc = test_env
+            .ctx
+            .banks_client
+            .get_account(escrow)
+            .await
+            .unwrap()
+            .expect("could not find escrow account");
+        let info = spl_token::state::Account::unpack_from_slice(&acc.data[..]).unwrap();
+        assert_eq!(info.owner, writer, "escrow not returned to writer");
+    }
+    assert_eq!(
+        get_lamports(test_env, initialiser).await,
+        initialiser_lamports + pda_rent,
+        "rent not returned to initialiser"
+    );
+    None
+}
+
+// (open_interest, total_exercised, total_expired) of the series
+async fn assert_series(
+    test_env: &mut TestEnv,
+    contract_type: ContractType,
+    contract_data: &ContractData,
+    counts: (u64, u64, u64),
+) {
+    let (mint_pda, _bump) = instruction::get_mint_pda(
+        &test_env.program_key.pubkey(),
+        contract_type,
+        &contract_data.series_seed(),
+    );
+    let account = test_env
+        .ctx
+        .banks_client
+        .get_account(mint_pda)
+        .await
+        .unwrap()
+        .expect("could not find mint PDA account");
+    let series = MintPDA::unpack_from_slice(&account.data[..]).unwrap();
+    assert_eq!(series.holder_mint, test_env.holder_mint.pubkey());
+    assert_eq!(series.contract_type, contract_type);
+    assert_eq!(
+        series.contract_data.series_seed(),
+        contract_data.series_seed()
+    );
+    assert!(series.created_at > 0, "series creation time not set");
+    assert_eq!(
+        (
+            series.open_interest,
+            series.total_exercised,
+            series.total_expired
+        ),
+        counts,
+        "incorrect series counts"
+    );
+}
+
+async fn warp_past_expiry(test_env: &mut TestEnv, contract_pda: &ContractPDA) {
+    let contract_data = &contract_pda.contract_data;
+    warp_to(test_env, contract_data, contract_data.expiry_date + 1).await;
+}
+
+// `time` is in the unit of the contract's expiry type
+async fn warp_to(test_env: &mut TestEnv, contract_data: &ContractData, time: i64) {
+    match contract_data.expiry_type {
+        ExpiryType::TIMESTAMP => {
+            let clock = test_env
+                .ctx
+                .banks_client
+                .get_sysvar::<Clock>()
+                .await
+                .unwrap();
+            test_env.ctx.set_sysvar(&Clock {
+                unix_timestamp: (time + 999) / 1000,
+                ..clock
+            });
+        }
+        ExpiryType::SLOT => test_env.ctx.warp_to_slot(time as u64).unwrap(),
+    }
+    // later transactions may repeat earlier ones byte for byte
+    test_env.ctx.last_blockhash = test_env.ctx.get_new_latest_blockhash().await.unwrap();
+}
+
+async fn get_lamports(test_env: &mut TestEnv, pk: Pubkey) -> u64 {
+    test_env
+        .ctx
+        .banks_client
+        .get_account(pk)
+        .await
+        .unwrap()
+        .expect("could not find account")
+        .lamports
 }
 
 async fn get_token_balance(test_env: &mut TestEnv, pk: Pubkey) -> u64 {
@@ -1469,3 +5695,90 @@ async fn get_token_balance(test_env: &mut TestEnv, pk: Pubkey) -> u64 {
     let info = spl_token::state::Account::unpack_from_slice(&acc.data[..]).unwrap();
     info.amount
 }
+
+fn assert_options_error(err: BanksClientError, expected: OptionsError) {
+    assert_options_error_at(err, 0, expected);
+}
+
+// for transactions where the program's instruction is not the first
+fn assert_options_error_at(err: BanksClientError, index: u8, expected: OptionsError) {
+    assert_eq!(
+        err.unwrap(),
+        TransactionError::InstructionError(index, InstructionError::Custom(expected as u32)),
+        "incorrect error returned"
+    );
+}
+
+// `ix` with the account at `index` swapped for `spoof` fails with `expected`
+async fn assert_spoof_error(
+    test_env: &mut TestEnv,
+    ix: &Instruction,
+    signer: fn(&TestEnv) -> &keypair::Keypair,
+    index: usize,
+    spoof: Pubkey,
+    expected: OptionsError,
+) {
+    let mut spoofed_ix = ix.clone();
+    spoofed_ix.accounts[index].pubkey = spoof;
+    let tx = Transaction::new_signed_with_payer(
+        &[spoofed_ix],
+        Some(&test_env.ctx.payer.pubkey()),
+        &[&test_env.ctx.payer, signer(test_env)],
+        test_env.ctx.last_blockhash,
+    );
+    let err = test_env
+        .ctx
+        .banks_client
+        .process_transaction(tx)
+        .await
+        .expect_err("spoofed account was accepted");
+    assert_options_error(err, expected);
+}
+
+// copies the account at `real` to a fresh address owned by `owner`
+async fn spoof_account(test_env: &mut TestEnv, real: Pubkey, owner: Pubkey) -> Pubkey {
+    let account = test_env
+        .ctx
+        .banks_client
+        .get_account(real)
+        .await
+        .unwrap()
+        .expect("could not find account to spoof");
+    let spoof = Pubkey::new_unique();
+    let account = Account { owner, ..account };
+    test_env
+        .ctx
+        .set_account(&spoof, &AccountSharedData::from(account));
+    spoof
+}
+
+async fn assert_spoofs_rejected(
+    test_env: &mut TestEnv,
+    ix: Instruction,
+    signer: fn(&TestEnv) -> &keypair::Keypair,
+    indices: &[usize],
+) {
+    for &i in indices {
+        let mut spoofed_ix = ix.clone();
+        spoofed_ix.accounts[i].pubkey =
+            spoof_account(test_env, ix.accounts[i].pubkey, Pubkey::new_unique()).await;
+        let tx = Transaction::new_signed_with_payer(
+            &[spoofed_ix],
+            Some(&test_env.ctx.payer.pubkey()),
+            &[&test_env.ctx.payer, signer(test_env)],
+            test_env.ctx.last_blockhash,
+        );
+        let err = test_env
+            .ctx
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .expect_err("spoofed account was accepted");
+        assert_eq!(
+            err.unwrap(),
+            TransactionError::InstructionError(0, InstructionError::IllegalOwner),
+            "spoofed account {} not rejected by owner",
+            i
+        );
+    }
+}
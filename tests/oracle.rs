@@ -0,0 +1,96 @@
+use sol_options::{
+    error::OptionsError,
+    oracle::{intrinsic_value, OraclePrice},
+    state::{
+        ContractData, ContractStyle, ContractType, ExerciseSchedule, ExpiryType, SettlementType,
+    },
+};
+use solana_program::pubkey::Pubkey;
+
+fn contract_data() -> ContractData {
+    ContractData {
+        token_type: Pubkey::new_unique(),
+        token_qty: 5,
+        expiry_date: 1_650_000_000_000,
+        strike_type: Pubkey::new_unique(),
+        strike_qty: 10,
+        premium_type: Pubkey::new_unique(),
+        premium_qty: 2,
+        expiry_type: ExpiryType::TIMESTAMP,
+        contract_style: ContractStyle::AMERICAN,
+        exercise_start: None,
+        exercise_schedule: ExerciseSchedule::default(),
+        settlement: SettlementType::CASH,
+        oracle: Some(Pubkey::new_unique()),
+        auto_exercise: false,
+        pooled: false,
+        lots: 1,
+    }
+}
+
+fn price(price: u64, decimals: u8) -> OraclePrice {
+    OraclePrice {
+        price,
+        decimals,
+        publish_time: 0,
+    }
+}
+
+#[test]
+fn value_rounds_down() {
+    assert_eq!(price(25, 1).value_of(5).unwrap(), 12);
+    assert_eq!(price(3, 0).value_of(5).unwrap(), 15);
+    assert_eq!(price(u64::MAX, 0).value_of(1).unwrap(), u64::MAX);
+    assert_eq!(
+        price(u64::MAX, 0).value_of(2),
+        Err(OptionsError::InvalidOracle.into())
+    );
+    assert_eq!(
+        price(1, 39).value_of(1),
+        Err(OptionsError::InvalidOracle.into())
+    );
+}
+
+#[test]
+fn call_intrinsic_value() {
+    let cd = contract_data();
+    // 5 tokens worth 15 against a strike of 10
+    assert_eq!(
+        intrinsic_value(&cd, ContractType::CALL, &price(3, 0)).unwrap(),
+        5
+    );
+    assert_eq!(
+        intrinsic_value(&cd, ContractType::CALL, &price(2, 0)).unwrap(),
+        0
+    );
+    assert_eq!(
+        intrinsic_value(&cd, ContractType::CALL, &price(1, 0)).unwrap(),
+        0
+    );
+    // capped at the strike_qty the writer escrowed
+    assert_eq!(
+        intrinsic_value(&cd, ContractType::CALL, &price(100, 0)).unwrap(),
+        10
+    );
+}
+
+#[test]
+fn put_intrinsic_value() {
+    let cd = contract_data();
+    assert_eq!(
+        intrinsic_value(&cd, ContractType::PUT, &price(1, 0)).unwrap(),
+        5
+    );
+    assert_eq!(
+        intrinsic_value(&cd, ContractType::PUT, &price(0, 0)).unwrap(),
+        10
+    );
+    assert_eq!(
+        intrinsic_value(&cd, ContractType::PUT, &price(2, 0)).unwrap(),
+        0
+    );
+    assert_eq!(
+        intrinsic_value(&cd, ContractType::PUT, &price(3, 0)).unwrap(),
+        0
+    );
+}
@@ -0,0 +1,525 @@
+use sol_options::{
+    error::OptionsError,
+    instruction::InitParty,
+    state::{
+        get_seed, ContractData, ContractPDA, ContractState, ContractStyle, ContractType,
+        ExerciseSchedule, ExerciseWindow, ExpiryType, MintPDA, Order, OrderBook, PartyData,
+        SettlementType,
+    },
+};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+fn contract_data() -> ContractData {
+    ContractData {
+        token_type: Pubkey::new_unique(),
+        token_qty: 5,
+        expiry_date: 1_650_000_000_000,
+        strike_type: Pubkey::new_unique(),
+        strike_qty: 10,
+        premium_type: Pubkey::new_unique(),
+        premium_qty: 2,
+        expiry_type: ExpiryType::TIMESTAMP,
+        contract_style: ContractStyle::AMERICAN,
+        exercise_start: None,
+        exercise_schedule: ExerciseSchedule::default(),
+        settlement: SettlementType::PHYSICAL,
+        oracle: None,
+        auto_exercise: false,
+        pooled: false,
+        lots: 1,
+    }
+}
+
+fn party_data() -> PartyData {
+    PartyData {
+        party_pub: Pubkey::new_unique(),
+        temp_pub: Pubkey::new_unique(),
+        receive_pub: Pubkey::new_unique(),
+        receive_ata: Pubkey::new_unique(),
+    }
+}
+
+fn contract_pda() -> ContractPDA {
+    let contract_data = contract_data();
+    ContractPDA {
+        seed: contract_data.get_seed(),
+        series_seed: contract_data.series_seed(),
+        contract_data,
+        contract_state: ContractState::FINAL,
+        buyer_data: Some(party_data()),
+        writer_data: Some(party_data()),
+        is_initialised: true,
+        index_seed: [3; 32],
+        bump: 254,
+        init_party: InitParty::WRITER,
+        contract_type: ContractType::PUT,
+        open_lots: 1,
+        unfilled_lots: 0,
+        writer_lots: 0,
+        offer_expiry: None,
+        counterparty: None,
+        offer_seq: 0,
+        settle_payout: 0,
+    }
+}
+
+#[test]
+fn contract_pda_round_trip() {
+    let pda = contract_pda();
+    let mut dst = [0; ContractPDA::LEN];
+    pda.pack_into_slice(&mut dst).unwrap();
+    assert_eq!(ContractPDA::unpack_from_slice(&dst).unwrap(), pda);
+
+    let partly_exercised = ContractPDA {
+        open_lots: 3,
+        ..contract_pda()
+    };
+    partly_exercised.pack_into_slice(&mut dst).unwrap();
+    assert_eq!(
+        ContractPDA::unpack_from_slice(&dst).unwrap(),
+        partly_exercised
+    );
+
+    let partly_filled = ContractPDA {
+        contract_state: ContractState::PARTIALLY_FILLED,
+        open_lots: 1,
+        unfilled_lots: 2,
+        ..contract_pda()
+    };
+    partly_filled.pack_into_slice(&mut dst).unwrap();
+    assert_eq!(ContractPDA::unpack_from_slice(&dst).unwrap(), partly_filled);
+
+    let short_lived = ContractPDA {
+        contract_state: ContractState::ASK,
+        buyer_data: None,
+        open_lots: 0,
+        unfilled_lots: 3,
+        offer_expiry: Some(1_650_000_060_000),
+        counterparty: Some(Pubkey::new_unique()),
+        offer_seq: 2,
+        ..contract_pda()
+    };
+    short_lived.pack_into_slice(&mut dst).unwrap();
+    assert_eq!(ContractPDA::unpack_from_slice(&dst).unwrap(), short_lived);
+    assert!(!short_lived.offer_expired(1_650_000_060_000));
+    assert!(short_lived.offer_expired(1_650_000_060_001));
+    assert!(!contract_pda().offer_expired(i64::MAX));
+
+    let settled = ContractPDA {
+        contract_state: ContractState::SETTLED,
+        open_lots: 0,
+        writer_lots: 3,
+        ..contract_pda()
+    };
+    settled.pack_into_slice(&mut dst).unwrap();
+    assert_eq!(ContractPDA::unpack_from_slice(&dst).unwrap(), settled);
+
+    let settled_in_the_money = ContractPDA {
+        contract_state: ContractState::SETTLED,
+        open_lots: 2,
+        writer_lots: 3,
+        settle_payout: 4,
+        ..contract_pda()
+    };
+    settled_in_the_money.pack_into_slice(&mut dst).unwrap();
+    assert_eq!(
+        ContractPDA::unpack_from_slice(&dst).unwrap(),
+        settled_in_the_money
+    );
+}
+
+#[test]
+fn contract_pda_wrong_length() {
+    let pda = contract_pda();
+    let mut dst = [0; ContractPDA::LEN + 1];
+    assert_eq!(
+        pda.pack_into_slice(&mut dst[..ContractPDA::LEN - 1]),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert_eq!(
+        pda.pack_into_slice(&mut dst),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let mut src = [0; ContractPDA::LEN];
+    pda.pack_into_slice(&mut src).unwrap();
+    for len in (0..ContractPDA::LEN).filter(|len| *len != ContractPDA::LEGACY_LEN) {
+        assert_eq!(
+            ContractPDA::unpack_from_slice(&src[..len]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
+
+#[test]
+fn contract_pda_garbage() {
+    let mut src = [0; ContractPDA::LEN];
+    contract_pda().pack_into_slice(&mut src).unwrap();
+
+    // discriminator, is_initialised, expiry_type, contract_style, exercise_start,
+    // exercise_schedule count, settlement, oracle, auto_exercise, pooled, contract_state,
+    // init_party and contract_type tags
+    for offset in [
+        0, 7, 9, 138, 139, 140, 149, 342, 343, 376, 377, 386, 644, 645,
+    ] {
+        let mut garbage = src;
+        garbage[offset] = 0xff;
+        assert_eq!(
+            ContractPDA::unpack_from_slice(&garbage),
+            Err(ProgramError::InvalidAccountData),
+            "accepted garbage at offset {}",
+            offset
+        );
+    }
+}
+
+#[test]
+fn contract_data_truncated() {
+    let bytes = contract_data().serialize();
+    assert!(ContractData::deserialize(&bytes).is_ok());
+    for len in 0..ContractData::LEN {
+        assert_eq!(
+            ContractData::deserialize(&bytes[..len]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
+
+#[test]
+fn extension_seed() {
+    let timestamp = contract_data();
+    let legacy =
+        ContractData::deserialize_legacy(&timestamp.serialize()[..ContractData::LEGACY_LEN])
+            .unwrap();
+    assert_eq!(legacy, timestamp);
+    assert_eq!(legacy.get_seed(), get_seed(&timestamp.serialize()[..120]));
+
+    let slot = ContractData {
+        expiry_type: ExpiryType::SLOT,
+        ..timestamp.clone()
+    };
+    assert_ne!(slot.get_seed(), timestamp.get_seed());
+    assert_eq!(ContractData::deserialize(&slot.serialize()).unwrap(), slot);
+
+    let european = ContractData {
+        contract_style: ContractStyle::EUROPEAN,
+        exercise_start: Some(0),
+        ..timestamp.clone()
+    };
+    let monthly = |months: i64| {
+        ExerciseSchedule::new(
+            (0..months)
+                .map(|m| ExerciseWindow {
+                    start: m * 30,
+                    end: m * 30 + 1,
+                })
+                .collect(),
+        )
+        .unwrap()
+    };
+    let bermudan = ContractData {
+        contract_style: ContractStyle::BERMUDAN,
+        exercise_schedule: monthly(12),
+        ..timestamp.clone()
+    };
+    let shorter = ContractData {
+        exercise_schedule: monthly(11),
+        ..bermudan.clone()
+    };
+    assert_ne!(european.get_seed(), timestamp.get_seed());
+    assert_ne!(bermudan.get_seed(), timestamp.get_seed());
+    assert_ne!(bermudan.get_seed(), european.get_seed());
+    assert_ne!(bermudan.get_seed(), shorter.get_seed());
+    assert_eq!(
+        ContractData::deserialize(&bermudan.serialize()).unwrap(),
+        bermudan
+    );
+    assert_eq!(
+        ContractData::deserialize(&european.serialize()).unwrap(),
+        european
+    );
+
+    let cash = ContractData {
+        settlement: SettlementType::CASH,
+        oracle: Some(Pubkey::new_unique()),
+        ..timestamp.clone()
+    };
+    let other_oracle = ContractData {
+        oracle: Some(Pubkey::new_unique()),
+        ..cash.clone()
+    };
+    assert_ne!(cash.get_seed(), timestamp.get_seed());
+    assert_ne!(cash.get_seed(), other_oracle.get_seed());
+    assert_eq!(ContractData::deserialize(&cash.serialize()).unwrap(), cash);
+
+    // holder tokens of every contract on the same per-lot terms share a mint
+    let lots = ContractData {
+        lots: 4,
+        ..timestamp.clone()
+    };
+    assert_eq!(lots.get_seed(), timestamp.get_seed());
+    assert_eq!(ContractData::deserialize(&lots.serialize()).unwrap(), lots);
+
+    // and whatever currency they were paid for in, though each offer keeps its own data PDA
+    let premium = ContractData {
+        premium_type: Pubkey::new_unique(),
+        premium_qty: 7,
+        ..timestamp.clone()
+    };
+    assert_eq!(premium.series_seed(), timestamp.series_seed());
+    assert_ne!(premium.get_seed(), timestamp.get_seed());
+    assert_eq!(
+        timestamp.series_seed(),
+        get_seed(&timestamp.serialize()[..88])
+    );
+    assert_ne!(slot.series_seed(), timestamp.series_seed());
+
+    // pooled contracts form their own series
+    let pooled = ContractData {
+        pooled: true,
+        ..timestamp.clone()
+    };
+    assert_ne!(pooled.get_seed(), timestamp.get_seed());
+    assert_eq!(
+        ContractData::deserialize(&pooled.serialize()).unwrap(),
+        pooled
+    );
+}
+
+#[test]
+fn exercise_schedule_bounds() {
+    let window = ExerciseWindow { start: 0, end: 1 };
+    assert!(ExerciseSchedule::new(vec![window; ExerciseSchedule::MAX_WINDOWS]).is_ok());
+    assert_eq!(
+        ExerciseSchedule::new(vec![window; ExerciseSchedule::MAX_WINDOWS + 1]),
+        Err(OptionsError::InvalidExerciseWindow.into())
+    );
+
+    let mut bytes = contract_data().serialize();
+    bytes[139] = ExerciseSchedule::MAX_WINDOWS as u8 + 1;
+    assert_eq!(
+        ContractData::deserialize(&bytes),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[cfg(feature = "test-bpf")]
+#[test]
+fn test_oracle_header() {
+    use sol_options::state::TestOracle;
+
+    let oracle = TestOracle {
+        price: 6,
+        decimals: 1,
+        publish_time: 1_650_000_000,
+    };
+    let mut src = [0; TestOracle::LEN];
+    oracle.pack_into_slice(&mut src);
+    assert_eq!(TestOracle::unpack_from_slice(&src).unwrap(), oracle);
+
+    // a zeroed program-owned account is not a price
+    assert_eq!(
+        TestOracle::unpack_from_slice(&[0; TestOracle::LEN]),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert_eq!(
+        TestOracle::unpack_from_slice(&src[..TestOracle::LEN - 1]),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn party_data_truncated() {
+    let party = party_data();
+    let bytes = party.to_bytes();
+    assert_eq!(PartyData::from_bytes(&bytes).unwrap(), party);
+    for len in 0..PartyData::LEN {
+        assert_eq!(
+            PartyData::from_bytes(&bytes[..len]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
+
+#[test]
+fn contract_pda_header() {
+    let pda = contract_pda();
+    let mut src = [0; ContractPDA::LEN];
+    pda.pack_into_slice(&mut src).unwrap();
+    assert_eq!(src[..8], ContractPDA::DISCRIMINATOR);
+
+    let mut outdated = src;
+    outdated[8] = ContractPDA::VERSION + 1;
+    assert_eq!(
+        ContractPDA::unpack_from_slice(&outdated),
+        Err(OptionsError::OutdatedAccountLayout.into())
+    );
+
+    let legacy = legacy_contract_bytes(&pda);
+    assert_eq!(
+        ContractPDA::unpack_from_slice(&legacy),
+        Err(OptionsError::OutdatedAccountLayout.into())
+    );
+    let mut migrated = ContractPDA::unpack_legacy(&legacy).unwrap();
+    // a legacy series was keyed by its premium type as well
+    assert_eq!(migrated.series_seed, pda.seed);
+    migrated.series_seed = pda.series_seed;
+    assert_eq!(migrated, pda);
+}
+
+// layout written before accounts carried a header
+fn legacy_contract_bytes(pda: &ContractPDA) -> Vec<u8> {
+    let cd = &pda.contract_data;
+    let mut v = vec![1];
+    v.extend_from_slice(&cd.token_type.to_bytes());
+    v.extend_from_slice(&cd.token_qty.to_le_bytes());
+    v.extend_from_slice(&cd.expiry_date.to_le_bytes());
+    v.extend_from_slice(&cd.strike_type.to_bytes());
+    v.extend_from_slice(&cd.strike_qty.to_le_bytes());
+    v.extend_from_slice(&cd.premium_type.to_bytes());
+    v.extend_from_slice(&cd.premium_qty.to_le_bytes());
+    v.push(2);
+    v.extend_from_slice(&pda.buyer_data.as_ref().unwrap().to_bytes());
+    v.extend_from_slice(&pda.writer_data.as_ref().unwrap().to_bytes());
+    v.push(pda.bump);
+    v.push(1);
+    v.push(1);
+    v.extend_from_slice(&pda.index_seed);
+    assert_eq!(v.len(), ContractPDA::LEGACY_LEN);
+    v
+}
+
+#[test]
+fn mint_pda_header() {
+    let mint = MintPDA {
+        open_interest: 3,
+        total_exercised: 2,
+        total_expired: 1,
+        ..MintPDA::new(
+            Pubkey::new_unique(),
+            ContractType::PUT,
+            &contract_data(),
+            1_650_000_000,
+        )
+    };
+    let mut src = [0; MintPDA::LEN];
+    mint.pack_into_slice(&mut src);
+    assert_eq!(MintPDA::unpack_from_slice(&src).unwrap(), mint);
+
+    // any program-owned account of the right size used to unpack as a MintPDA
+    let mut contract = [0; ContractPDA::LEN];
+    contract_pda().pack_into_slice(&mut contract).unwrap();
+    assert_eq!(
+        MintPDA::unpack_from_slice(&contract[..MintPDA::LEN]),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let legacy = mint.holder_mint.to_bytes();
+    assert_eq!(
+        MintPDA::unpack_from_slice(&legacy),
+        Err(OptionsError::OutdatedAccountLayout.into())
+    );
+    assert_eq!(MintPDA::unpack_legacy(&legacy).unwrap(), mint.holder_mint);
+}
+
+#[test]
+fn mint_pda_series_terms() {
+    let contract_data = contract_data();
+    let mint = MintPDA::new(
+        Pubkey::new_unique(),
+        ContractType::CALL,
+        &contract_data,
+        1_650_000_000,
+    );
+    // a series is what its mint PDA is derived from, not the first offer's premium or size
+    assert_eq!(mint.contract_data.premium_type, Pubkey::default());
+    assert_eq!(mint.contract_data.premium_qty, 0);
+    assert_eq!(mint.contract_data.lots, 0);
+    assert_eq!(
+        mint.contract_data.series_seed(),
+        contract_data.series_seed()
+    );
+
+    let mut mint = mint;
+    mint.record_fill(3).unwrap();
+    mint.record_exercise(1).unwrap();
+    mint.record_close_out(1);
+    mint.record_expiry(1).unwrap();
+    assert_eq!(
+        (mint.open_interest, mint.total_exercised, mint.total_expired),
+        (0, 1, 1)
+    );
+    mint.record_fill(1).unwrap();
+    assert_eq!(
+        mint.record_fill(u64::MAX),
+        Err(OptionsError::Overflow.into())
+    );
+    assert_eq!(
+        mint.record_expiry(u64::MAX),
+        Err(OptionsError::Overflow.into())
+    );
+}
+
+#[test]
+fn order_book_price_time_priority() {
+    let mut book = OrderBook::new(ContractType::CALL, &contract_data());
+    assert_eq!(book.contract_data.premium_qty, 0);
+    assert_eq!(book.contract_data.lots, 0);
+    let order = |premium_qty| Order {
+        premium_qty,
+        lots: 1,
+        data_pda: Pubkey::new_unique(),
+    };
+    let (bid_low, bid_high, bid_high_later) = (order(2), order(4), order(4));
+    let (ask_low, ask_high) = (order(5), order(7));
+    for (party, order) in [
+        (InitParty::BUYER, &bid_low),
+        (InitParty::BUYER, &bid_high),
+        (InitParty::BUYER, &bid_high_later),
+        (InitParty::WRITER, &ask_high),
+        (InitParty::WRITER, &ask_low),
+    ] {
+        book.insert(&party, order.clone()).unwrap();
+    }
+    assert_eq!(book.bids, vec![bid_high.clone(), bid_high_later, bid_low]);
+    assert_eq!(book.asks, vec![ask_low.clone(), ask_high]);
+    assert_eq!(book.opposite(&InitParty::BUYER)[0], ask_low);
+    assert_eq!(book.opposite(&InitParty::WRITER)[0], bid_high);
+
+    let mut dst = [0; OrderBook::LEN];
+    book.pack_into_slice(&mut dst);
+    assert_eq!(dst[..8], OrderBook::DISCRIMINATOR);
+    assert_eq!(OrderBook::unpack_from_slice(&dst).unwrap(), book);
+
+    assert_eq!(book.remove(&ask_low.data_pda), Some(ask_low.clone()));
+    assert_eq!(book.remove(&ask_low.data_pda), None);
+    book.pack_into_slice(&mut dst);
+    assert_eq!(OrderBook::unpack_from_slice(&dst).unwrap(), book);
+}
+
+#[test]
+fn order_book_full() {
+    let mut book = OrderBook::new(ContractType::PUT, &contract_data());
+    let order = |premium_qty| Order {
+        premium_qty,
+        lots: 1,
+        data_pda: Pubkey::new_unique(),
+    };
+    for _ in 0..OrderBook::MAX_ORDERS {
+        assert_eq!(book.insert(&InitParty::WRITER, order(3)).unwrap(), None);
+    }
+    // the newest of the worst priced asks makes room for a cheaper one
+    let worst = book.asks.last().unwrap().clone();
+    let cheaper = order(1);
+    assert_eq!(
+        book.insert(&InitParty::WRITER, cheaper.clone()).unwrap(),
+        Some(worst)
+    );
+    assert_eq!(book.asks.len(), OrderBook::MAX_ORDERS);
+    assert_eq!(book.asks[0], cheaper);
+    assert_eq!(
+        book.insert(&InitParty::WRITER, order(3)),
+        Err(OptionsError::OrderBookFull.into())
+    );
+    assert_eq!(book.insert(&InitParty::BUYER, order(1)).unwrap(), None);
+}